@@ -82,6 +82,13 @@ pub struct Opts {
     // Skip optional null pointer and boolean result argument signature flattening
     #[cfg_attr(feature = "clap", arg(long, default_value_t = false))]
     pub no_sig_flattening: bool,
+    /// Skip emitting the object file which embeds the component type, which
+    /// must then be embedded some other way.
+    ///
+    /// Worlds using futures or streams can only be generated with this, as
+    /// wit-component can't encode them into the component type yet.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub no_object_file: bool,
 }
 
 impl Opts {
@@ -115,10 +122,14 @@ enum Scalar {
 }
 
 impl WorldGenerator for C {
-    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
+        if !self.opts.no_object_file {
+            wit_bindgen_core::check_futures_and_streams(resolve, world)?;
+        }
         let name = &resolve.worlds[world].name;
         self.world = name.to_string();
         self.sizes.fill(resolve);
+        Ok(())
     }
 
     fn import_interface(
@@ -242,18 +253,20 @@ impl WorldGenerator for C {
         self.finish_types(resolve);
 
         let world = &resolve.worlds[id];
-        let linking_symbol = component_type_object::linking_symbol(&world.name);
         self.include("<stdlib.h>");
         let snake = world.name.to_snake_case();
-        uwrite!(
-            self.src.c_adapters,
-            "
-               extern void {linking_symbol}(void);
-               void {linking_symbol}_public_use_in_this_compilation_unit(void) {{
-                   {linking_symbol}();
-               }}
-           ",
-        );
+        if !self.opts.no_object_file {
+            let linking_symbol = component_type_object::linking_symbol(&world.name);
+            uwrite!(
+                self.src.c_adapters,
+                "
+                   extern void {linking_symbol}(void);
+                   void {linking_symbol}_public_use_in_this_compilation_unit(void) {{
+                       {linking_symbol}();
+                   }}
+               ",
+            );
+        }
 
         self.print_intrinsics();
 
@@ -422,12 +435,14 @@ impl WorldGenerator for C {
 
         files.push(&format!("{snake}.c"), c_str.as_bytes());
         files.push(&format!("{snake}.h"), h_str.as_bytes());
-        files.push(
-            &format!("{snake}_component_type.o",),
-            component_type_object::object(resolve, id, self.opts.string_encoding)
-                .unwrap()
-                .as_slice(),
-        );
+        if !self.opts.no_object_file {
+            files.push(
+                &format!("{snake}_component_type.o",),
+                component_type_object::object(resolve, id, self.opts.string_encoding)
+                    .unwrap()
+                    .as_slice(),
+            );
+        }
     }
 }
 
@@ -773,8 +788,8 @@ impl C {
                 self.src.h_defs("size_t len;\n");
                 self.src.h_defs("}");
             }
-            TypeDefKind::Future(_) => todo!("print_anonymous_type for future"),
-            TypeDefKind::Stream(_) => todo!("print_anonymous_type for stream"),
+            // Futures and streams are only the index of their handle.
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => self.src.h_defs("uint32_t"),
             TypeDefKind::Unknown => unreachable!(),
         }
         self.src.h_defs(" ");
@@ -899,8 +914,7 @@ impl C {
                 }
                 self.src.c_helpers("}\n");
             }
-            TypeDefKind::Future(_) => todo!("print_dtor for future"),
-            TypeDefKind::Stream(_) => todo!("print_dtor for stream"),
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {}
             TypeDefKind::Resource => unreachable!(),
             TypeDefKind::Handle(handle) => {
                 let handle_namespace = self.owner_namespace(resolve, id);
@@ -914,15 +928,36 @@ impl C {
                 let resource_namespace = self.owner_namespace(resolve, resource);
                 let name = resolve.types[resource].name.as_deref().unwrap();
                 let snake = name.to_snake_case();
+                // Named handles may be a distinct C type from the one the
+                // resource's intrinsics were generated for, so convert to
+                // that type here.
+                let info = &self.resources[&resource];
+                let (handle_namespace, arg) = match (handle, &resolve.types[id].name) {
+                    (Handle::Borrow(_), Some(_)) => {
+                        let borrow = info.borrow.unwrap();
+                        let ns = self.owner_namespace(resolve, borrow);
+                        let arg = format!("({ns}_borrow_{snake}_t) {{ ptr->__handle }}");
+                        (ns, arg)
+                    }
+                    (Handle::Own(_), Some(_)) => {
+                        let own = info.own.unwrap();
+                        let ns = self.owner_namespace(resolve, own);
+                        (
+                            handle_namespace,
+                            format!("({ns}_own_{snake}_t) {{ ptr->__handle }}"),
+                        )
+                    }
+                    (_, None) => (handle_namespace, "*ptr".to_string()),
+                };
                 match handle {
                     Handle::Borrow(_) => uwriteln!(
                         self.src.c_helpers,
-                        "{handle_namespace}_{snake}_drop_borrow(*ptr);"
+                        "{handle_namespace}_{snake}_drop_borrow({arg});"
                     ),
                     Handle::Own(_) => {
                         uwriteln!(
                             self.src.c_helpers,
-                            "{resource_namespace}_{snake}_drop_own(*ptr);"
+                            "{resource_namespace}_{snake}_drop_own({arg});"
                         )
                     }
                 }
@@ -1364,8 +1399,13 @@ impl Return {
         match &resolve.types[id].kind {
             TypeDefKind::Type(t) => return self.return_single(resolve, t, orig_ty, sig_flattening),
 
-            // Flags are returned as their bare values, and enums and handles are scalars
-            TypeDefKind::Flags(_) | TypeDefKind::Enum(_) | TypeDefKind::Handle(_) => {
+            // Flags are returned as their bare values, and enums, handles,
+            // futures and streams are scalars
+            TypeDefKind::Flags(_)
+            | TypeDefKind::Enum(_)
+            | TypeDefKind::Handle(_)
+            | TypeDefKind::Future(_)
+            | TypeDefKind::Stream(_) => {
                 self.scalar = Some(Scalar::Type(*orig_ty));
                 return;
             }
@@ -1402,8 +1442,6 @@ impl Return {
             | TypeDefKind::List(_)
             | TypeDefKind::Variant(_) => {}
 
-            TypeDefKind::Future(_) => todo!("return_single for future"),
-            TypeDefKind::Stream(_) => todo!("return_single for stream"),
             TypeDefKind::Resource => todo!("return_single for resource"),
             TypeDefKind::Unknown => unreachable!(),
         }
@@ -1651,6 +1689,105 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.finish_ty(id, prev);
     }
 
+    fn type_future(&mut self, id: TypeId, name: &str, _ty: &Option<Type>, docs: &Docs) {
+        let prev = mem::take(&mut self.src.h_defs);
+        self.src.h_defs("\n");
+        self.docs(docs, SourceType::HDefs);
+        self.src.h_defs("typedef uint32_t ");
+        self.print_typedef_target(id, name);
+        self.finish_ty(id, prev);
+    }
+
+    fn type_stream(&mut self, id: TypeId, name: &str, _stream: &Stream, docs: &Docs) {
+        let prev = mem::take(&mut self.src.h_defs);
+        self.src.h_defs("\n");
+        self.docs(docs, SourceType::HDefs);
+        self.src.h_defs("typedef uint32_t ");
+        self.print_typedef_target(id, name);
+        self.finish_ty(id, prev);
+    }
+
+    fn type_handle(&mut self, id: TypeId, name: &str, handle: &Handle, docs: &Docs) {
+        let prev = mem::take(&mut self.src.h_defs);
+        self.src.h_defs("\n");
+        self.docs(docs, SourceType::HDefs);
+
+        // If an anonymous handle of the same shape was defined before this
+        // one then alias that, otherwise this alias defines the anonymous
+        // handle type itself so that the resource's intrinsics, which operate
+        // on the anonymous type, accept this type too.
+        let existing = self
+            .resolve
+            .types
+            .iter()
+            .take_while(|(other, _)| *other != id)
+            .find(|(_, ty)| {
+                ty.name.is_none() && matches!(&ty.kind, TypeDefKind::Handle(h) if h == handle)
+            })
+            .map(|(other, _)| other);
+        self.src.h_defs("typedef ");
+        match (existing, handle) {
+            (Some(existing), _) => self.print_ty(SourceType::HDefs, &Type::Id(existing)),
+            (None, Handle::Borrow(resource))
+                if matches!(
+                    self.gen
+                        .resources
+                        .get(&dealias(self.resolve, *resource))
+                        .map(|info| &info.direction),
+                    Some(Direction::Export)
+                ) =>
+            {
+                self.print_ty(
+                    SourceType::HDefs,
+                    &Type::Id(dealias(self.resolve, *resource)),
+                );
+                self.src.h_defs("*");
+            }
+            (None, _) => {
+                let mut anonymous = self.gen.owner_namespace(self.resolve, id);
+                anonymous.push('_');
+                push_handle_name(
+                    self.resolve,
+                    handle,
+                    &self.gen.interface_names,
+                    &self.gen.world,
+                    &mut anonymous,
+                );
+                anonymous.push_str("_t");
+                if self.gen.names.insert(&anonymous).is_ok() {
+                    uwrite!(
+                        self.src.h_defs,
+                        "struct {{\nint32_t __handle;\n}} {anonymous};\ntypedef "
+                    );
+                }
+                self.src.h_defs(&anonymous);
+
+                let info = match handle {
+                    Handle::Own(resource) => {
+                        &mut self
+                            .gen
+                            .resources
+                            .entry(dealias(self.resolve, *resource))
+                            .or_default()
+                            .own
+                    }
+                    Handle::Borrow(resource) => {
+                        &mut self
+                            .gen
+                            .resources
+                            .entry(dealias(self.resolve, *resource))
+                            .or_default()
+                            .borrow
+                    }
+                };
+                info.get_or_insert(id);
+            }
+        }
+        self.src.h_defs(" ");
+        self.print_typedef_target(id, name);
+        self.finish_ty(id, prev);
+    }
+
     fn type_builtin(&mut self, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
         let _ = (id, name, ty, docs);
     }
@@ -2640,6 +2777,13 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             Instruction::EnumLower { .. } => results.push(format!("(int32_t) {}", operands[0])),
             Instruction::EnumLift { .. } => results.push(operands.pop().unwrap()),
 
+            Instruction::FutureLower { .. } | Instruction::StreamLower { .. } => {
+                results.push(format!("(int32_t) {}", operands[0]))
+            }
+            Instruction::FutureLift { .. } | Instruction::StreamLift { .. } => {
+                results.push(format!("(uint32_t) {}", operands[0]))
+            }
+
             Instruction::ListCanonLower { .. } | Instruction::StringLower { .. } => {
                 results.push(format!("(int32_t) ({}).ptr", operands[0]));
                 results.push(format!("(int32_t) ({}).len", operands[0]));
@@ -3131,8 +3275,7 @@ pub fn is_arg_by_pointer(resolve: &Resolve, ty: &Type) -> bool {
             TypeDefKind::Flags(_) => false,
             TypeDefKind::Handle(_) => false,
             TypeDefKind::Tuple(_) | TypeDefKind::Record(_) | TypeDefKind::List(_) => true,
-            TypeDefKind::Future(_) => todo!("is_arg_by_pointer for future"),
-            TypeDefKind::Stream(_) => todo!("is_arg_by_pointer for stream"),
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => false,
             TypeDefKind::Resource => todo!("is_arg_by_pointer for resource"),
             TypeDefKind::Unknown => unreachable!(),
         },
@@ -3199,8 +3342,9 @@ pub fn owns_anything(
             optional_owns_anything(resolve, r.ok.as_ref(), is_local_resource)
                 || optional_owns_anything(resolve, r.err.as_ref(), is_local_resource)
         }
-        TypeDefKind::Future(_) => todo!("owns_anything for future"),
-        TypeDefKind::Stream(_) => todo!("owns_anything for stream"),
+        // Nothing frees futures and streams, which are only the index of
+        // their handle.
+        TypeDefKind::Future(_) | TypeDefKind::Stream(_) => false,
         TypeDefKind::Resource => false,
         TypeDefKind::Handle(Handle::Borrow(resource)) if is_local_resource(resolve, *resource) => {
             false
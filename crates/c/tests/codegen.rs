@@ -2,6 +2,7 @@ use heck::*;
 use std::env;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use wit_bindgen_core::wit_parser::{Resolve, Type, TypeDefKind, UnresolvedPackage};
 
 macro_rules! codegen_test {
     // TODO: support importing and exporting the same interface containing one
@@ -12,6 +13,23 @@ macro_rules! codegen_test {
     (resource_local_alias $name:tt $test:tt) => {};
     (resources_with_lists $name:tt $test:tt) => {};
     (resources_in_aggregates $name:tt $test:tt) => {};
+    // wit-component can't encode futures and streams into the component type
+    // yet, so these are only generated without the object file embedding it.
+    (future_stream_alias $name:tt $test:tt) => {
+        #[test]
+        fn future_stream_alias() {
+            test_helpers::run_world_codegen_test(
+                "guest-c",
+                $test.as_ref(),
+                |resolve, world, files| {
+                    let mut opts = wit_bindgen_c::Opts::default();
+                    opts.no_object_file = true;
+                    opts.build().generate(resolve, world, files).unwrap()
+                },
+                verify,
+            );
+        }
+    };
     ($id:ident $name:tt $test:tt) => {
         #[test]
         fn $id() {
@@ -59,3 +77,59 @@ fn verify(dir: &Path, name: &str) {
 
     test_helpers::run_command(&mut cmd);
 }
+
+#[test]
+fn futures_and_streams() {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/codegen/future-stream-alias.wit");
+    let mut resolve = Resolve::default();
+    let pkg = resolve
+        .push(UnresolvedPackage::parse_file(&path).unwrap())
+        .unwrap();
+
+    // Futures and streams are the index of their handle, which is neither
+    // freed nor passed by pointer, and neither is a record of them.
+    for (id, ty) in resolve.types.iter() {
+        if ty.name.as_deref() == Some("request")
+            || matches!(ty.kind, TypeDefKind::Future(_) | TypeDefKind::Stream(_))
+        {
+            let ty = Type::Id(id);
+            assert!(!wit_bindgen_c::owns_anything(&resolve, &ty, &|_, _| false));
+            assert_eq!(
+                wit_bindgen_c::is_arg_by_pointer(&resolve, &ty),
+                matches!(resolve.types[id].kind, TypeDefKind::Record(_))
+            );
+        }
+    }
+
+    let world = resolve.select_world(pkg, None).unwrap();
+    let err = wit_bindgen_c::Opts::default()
+        .build()
+        .generate(&resolve, world, &mut Default::default())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "world `channel-aliases` uses `reply`, a future, which can't be embedded in the \
+         component type yet"
+    );
+
+    // Without the object file the aliases are defined as handle indices.
+    let opts = wit_bindgen_c::Opts {
+        no_object_file: true,
+        ..Default::default()
+    };
+    let mut files = Default::default();
+    opts.build().generate(&resolve, world, &mut files).unwrap();
+    let names = files.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(names, ["channel_aliases.c", "channel_aliases.h"]);
+    let (_, header) = files.iter().find(|(name, _)| name.ends_with(".h")).unwrap();
+    let header = std::str::from_utf8(header).unwrap();
+    for typedef in [
+        "typedef uint32_t my_channels_channels_reply_t;",
+        "typedef uint32_t my_channels_channels_bytes_t;",
+        "typedef uint32_t my_channels_exported_ticket_t;",
+        "typedef uint32_t channel_aliases_future_u32_t;",
+    ] {
+        assert!(header.contains(typedef), "missing `{typedef}`");
+    }
+}
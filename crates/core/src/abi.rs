@@ -1,6 +1,6 @@
 use wit_parser::{
     Enum, Flags, FlagsRepr, Function, Handle, Int, Record, Resolve, Result_, Results, SizeAlign,
-    Tuple, Type, TypeDefKind, TypeId, Variant,
+    Stream, Tuple, Type, TypeDefKind, TypeId, Variant,
 };
 
 pub use wit_parser::abi::{AbiVariant, WasmSignature, WasmType};
@@ -34,6 +34,15 @@ macro_rules! def_instruction {
         }
 
         impl $name<'_> {
+            /// The name of this instruction, without any of its fields.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$variant { .. } => stringify!($variant),
+                    )*
+                }
+            }
+
             /// How many operands does this instruction pop from the stack?
             #[allow(unused_variables)]
             pub fn operands_len(&self) -> usize {
@@ -277,13 +286,21 @@ def_instruction! {
         ///
         /// This will consume two `i32` values from the stack, a pointer and a
         /// length, and then produces an interface value list.
+        ///
+        /// When `borrowed` is set this is a parameter of an export which
+        /// `Bindgen::is_borrowed_param` chose to lend to the callee, so the
+        /// list isn't owned by the value produced and must be freed after
+        /// `CallInterface`.
         ListCanonLift {
             element: &'a Type,
             ty: TypeId,
+            borrowed: bool,
         } : [2] => [1],
 
         /// Same as `ListCanonLift`, but used for strings
-        StringLift : [2] => [1],
+        StringLift {
+            borrowed: bool,
+        } : [2] => [1],
 
         /// Lifts a list which into an interface types value.
         ///
@@ -341,6 +358,57 @@ def_instruction! {
             ty: TypeId,
         } : [1] => [1],
 
+        /// Create an `i32` from a future.
+        ///
+        /// Futures are represented in the canonical ABI as a handle, so this
+        /// behaves similarly to `HandleLower`. The `payload` is the type of
+        /// the value the future eventually resolves to, if any.
+        FutureLower {
+            payload: &'a Option<Type>,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Create a future from an `i32`.
+        FutureLift {
+            payload: &'a Option<Type>,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Create an `i32` from a stream.
+        ///
+        /// Streams are represented in the canonical ABI as a handle, so this
+        /// behaves similarly to `HandleLower`. The `stream` carries the
+        /// element and end types of the stream, if any.
+        StreamLower {
+            stream: &'a Stream,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Create a stream from an `i32`.
+        StreamLift {
+            stream: &'a Stream,
+            ty: TypeId,
+        } : [1] => [1],
+
+        /// Converts a value of a type which the generator represents with
+        /// some other type into a value of the WIT type `ty`, which is then
+        /// lowered as usual.
+        ///
+        /// This is only emitted for types where `Bindgen::is_remapped`
+        /// returns `true`. The `realloc` is the same as for `StringLower`,
+        /// indicating whether the lowered value may borrow from the
+        /// converted value.
+        LowerRemapped {
+            ty: TypeId,
+            realloc: Option<&'a str>,
+        } : [1] => [1],
+
+        /// Converts a lifted value of the WIT type `ty` into the type which
+        /// the generator represents it with, the inverse of `LowerRemapped`.
+        LiftRemapped {
+            ty: TypeId,
+        } : [1] => [1],
+
         /// Pops a tuple value off the stack, decomposes the tuple to all of
         /// its fields, and then pushes the fields onto the stack.
         TupleLower {
@@ -513,7 +581,7 @@ def_instruction! {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Bitcast {
     // Upcasts
     F32ToI32,
@@ -620,6 +688,26 @@ pub trait Bindgen {
     /// "canonical" form for lists. This dictates whether the `ListCanonLower`
     /// and `ListCanonLift` instructions are used or not.
     fn is_list_canonical(&self, resolve: &Resolve, element: &Type) -> bool;
+
+    /// Returns whether values of the named type `ty` are represented by some
+    /// other type in this language, in which case they're converted with the
+    /// `LowerRemapped` and `LiftRemapped` instructions.
+    ///
+    /// By default no types are remapped.
+    fn is_remapped(&self, ty: TypeId) -> bool {
+        let _ = ty;
+        false
+    }
+
+    /// Returns whether the export parameter of type `ty`, a string or a list
+    /// with a canonical representation, is lent to the callee rather than
+    /// given to it, in which case it's lifted with `borrowed` set.
+    ///
+    /// By default parameters are owned by the callee.
+    fn is_borrowed_param(&self, ty: &Type) -> bool {
+        let _ = ty;
+        false
+    }
 }
 
 /// Generates an abstract sequence of instructions which represents this
@@ -694,7 +782,9 @@ fn needs_post_return(resolve: &Resolve, ty: &Type) -> bool {
                 .filter_map(|t| t.as_ref())
                 .any(|t| needs_post_return(resolve, t)),
             TypeDefKind::Flags(_) | TypeDefKind::Enum(_) => false,
-            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => unimplemented!(),
+            // Futures and streams are handles, so ownership of them is
+            // transferred to the caller and nothing needs to be freed.
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => false,
             TypeDefKind::Unknown => unreachable!(),
         },
 
@@ -722,6 +812,8 @@ struct Generator<'a, B: Bindgen> {
     results: Vec<B::Operand>,
     stack: Vec<B::Operand>,
     return_pointer: Option<B::Operand>,
+    /// Whether the export parameter being lifted is borrowed by the callee.
+    borrow_param: bool,
 }
 
 impl<'a, B: Bindgen> Generator<'a, B> {
@@ -740,6 +832,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             results: Vec::new(),
             stack: Vec::new(),
             return_pointer: None,
+            borrow_param: false,
         }
     }
 
@@ -854,7 +947,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             self.emit(&Instruction::GetArg { nth: offset });
                             offset += 1;
                         }
+                        self.borrow_param = self.bindgen.is_borrowed_param(ty);
                         self.lift(ty);
+                        self.borrow_param = false;
                     }
                 } else {
                     // ... otherwise argument is read in succession from memory
@@ -865,7 +960,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     let ptr = self.stack.pop().unwrap();
                     for (_, ty) in func.params.iter() {
                         offset = align_to(offset, self.bindgen.sizes().align(ty));
+                        self.borrow_param = self.bindgen.is_borrowed_param(ty);
                         self.read_from_memory(ty, ptr.clone(), offset as i32);
+                        self.borrow_param = false;
                         offset += self.bindgen.sizes().size(ty);
                     }
                 }
@@ -1014,6 +1111,19 @@ impl<'a, B: Bindgen> Generator<'a, B> {
     }
 
     fn lower(&mut self, ty: &Type) {
+        match *ty {
+            Type::Id(id) if self.bindgen.is_remapped(id) => {
+                let realloc = self.list_realloc();
+                self.emit(&Instruction::LowerRemapped { ty: id, realloc });
+                self.lower_repr(ty);
+            }
+            _ => self.lower_repr(ty),
+        }
+    }
+
+    /// Lowers a value of `ty`, which has already been converted from its
+    /// remapped type, if any.
+    fn lower_repr(&mut self, ty: &Type) {
         use Instruction::*;
 
         match *ty {
@@ -1128,8 +1238,12 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         results: &results,
                     });
                 }
-                TypeDefKind::Future(_) => todo!("lower future"),
-                TypeDefKind::Stream(_) => todo!("lower stream"),
+                TypeDefKind::Future(payload) => {
+                    self.emit(&FutureLower { payload, ty: id });
+                }
+                TypeDefKind::Stream(stream) => {
+                    self.emit(&StreamLower { stream, ty: id });
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1203,6 +1317,16 @@ impl<'a, B: Bindgen> Generator<'a, B> {
     /// Note that in general everything in this function is the opposite of the
     /// `lower` function above. This is intentional and should be kept this way!
     fn lift(&mut self, ty: &Type) {
+        self.lift_repr(ty);
+        if let Type::Id(id) = *ty {
+            if self.bindgen.is_remapped(id) {
+                self.emit(&Instruction::LiftRemapped { ty: id });
+            }
+        }
+    }
+
+    /// Lifts a value of `ty` without converting it to its remapped type.
+    fn lift_repr(&mut self, ty: &Type) {
         use Instruction::*;
 
         match *ty {
@@ -1218,12 +1342,18 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             Type::Char => self.emit(&CharFromI32),
             Type::Float32 => self.emit(&Float32FromF32),
             Type::Float64 => self.emit(&Float64FromF64),
-            Type::String => self.emit(&StringLift),
+            Type::String => self.emit(&StringLift {
+                borrowed: self.borrow_param,
+            }),
             Type::Id(id) => match &self.resolve.types[id].kind {
                 TypeDefKind::Type(t) => self.lift(t),
                 TypeDefKind::List(element) => {
                     if self.bindgen.is_list_canonical(self.resolve, element) {
-                        self.emit(&ListCanonLift { element, ty: id });
+                        self.emit(&ListCanonLift {
+                            element,
+                            ty: id,
+                            borrowed: self.borrow_param,
+                        });
                     } else {
                         self.push_block();
                         self.emit(&IterBasePointer);
@@ -1313,8 +1443,13 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.emit(&ResultLift { result: r, ty: id });
                 }
 
-                TypeDefKind::Future(_) => todo!("lift future"),
-                TypeDefKind::Stream(_) => todo!("lift stream"),
+                TypeDefKind::Future(payload) => {
+                    self.emit(&FutureLift { payload, ty: id });
+                }
+
+                TypeDefKind::Stream(stream) => {
+                    self.emit(&StreamLift { stream, ty: id });
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1361,6 +1496,17 @@ impl<'a, B: Bindgen> Generator<'a, B> {
     }
 
     fn write_to_memory(&mut self, ty: &Type, addr: B::Operand, offset: i32) {
+        match *ty {
+            Type::Id(id) if self.bindgen.is_remapped(id) => {
+                let realloc = self.list_realloc();
+                self.emit(&Instruction::LowerRemapped { ty: id, realloc });
+                self.write_repr_to_memory(ty, addr, offset);
+            }
+            _ => self.write_repr_to_memory(ty, addr, offset),
+        }
+    }
+
+    fn write_repr_to_memory(&mut self, ty: &Type, addr: B::Operand, offset: i32) {
         use Instruction::*;
 
         match *ty {
@@ -1403,7 +1549,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                 }
 
                 TypeDefKind::Flags(f) => {
-                    self.lower(ty);
+                    self.lower_repr(ty);
                     match f.repr() {
                         FlagsRepr::U8 => {
                             self.stack.push(addr);
@@ -1467,13 +1613,14 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                 }
 
                 TypeDefKind::Enum(e) => {
-                    self.lower(ty);
+                    self.lower_repr(ty);
                     self.stack.push(addr);
                     self.store_intrepr(offset, e.tag());
                 }
 
-                TypeDefKind::Future(_) => todo!("write future to memory"),
-                TypeDefKind::Stream(_) => todo!("write stream to memory"),
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                    self.lower_and_emit(ty, addr, &I32Store { offset })
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1516,7 +1663,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
         // After lowering the list there's two i32 values on the stack
         // which we write into memory, writing the pointer into the low address
         // and the length into the high address.
-        self.lower(ty);
+        self.lower_repr(ty);
         self.stack.push(addr.clone());
         self.emit(&Instruction::I32Store { offset: offset + 4 });
         self.stack.push(addr);
@@ -1546,12 +1693,21 @@ impl<'a, B: Bindgen> Generator<'a, B> {
     }
 
     fn lower_and_emit(&mut self, ty: &Type, addr: B::Operand, instr: &Instruction) {
-        self.lower(ty);
+        self.lower_repr(ty);
         self.stack.push(addr);
         self.emit(instr);
     }
 
     fn read_from_memory(&mut self, ty: &Type, addr: B::Operand, offset: i32) {
+        self.read_repr_from_memory(ty, addr, offset);
+        if let Type::Id(id) = *ty {
+            if self.bindgen.is_remapped(id) {
+                self.emit(&Instruction::LiftRemapped { ty: id });
+            }
+        }
+    }
+
+    fn read_repr_from_memory(&mut self, ty: &Type, addr: B::Operand, offset: i32) {
         use Instruction::*;
 
         match *ty {
@@ -1613,7 +1769,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             }
                         }
                     }
-                    self.lift(ty);
+                    self.lift_repr(ty);
                 }
 
                 // Each case will get its own block, and we'll dispatch to the
@@ -1652,11 +1808,12 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                 TypeDefKind::Enum(e) => {
                     self.stack.push(addr.clone());
                     self.load_intrepr(offset, e.tag());
-                    self.lift(ty);
+                    self.lift_repr(ty);
                 }
 
-                TypeDefKind::Future(_) => todo!("read future from memory"),
-                TypeDefKind::Stream(_) => todo!("read stream from memory"),
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                    self.emit_and_lift(ty, addr, &I32Load { offset })
+                }
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
@@ -1693,7 +1850,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
         self.emit(&Instruction::I32Load { offset });
         self.stack.push(addr);
         self.emit(&Instruction::I32Load { offset: offset + 4 });
-        self.lift(ty);
+        self.lift_repr(ty);
     }
 
     fn read_fields_from_memory<'b>(
@@ -1710,7 +1867,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
     fn emit_and_lift(&mut self, ty: &Type, addr: B::Operand, instr: &Instruction) {
         self.stack.push(addr);
         self.emit(instr);
-        self.lift(ty);
+        self.lift_repr(ty);
     }
 
     fn load_intrepr(&mut self, offset: i32, repr: Int) {
@@ -1825,8 +1982,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
 
                 TypeDefKind::Enum(_) => {}
 
-                TypeDefKind::Future(_) => todo!("read future from memory"),
-                TypeDefKind::Stream(_) => todo!("read stream from memory"),
+                TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {}
                 TypeDefKind::Unknown => unreachable!(),
             },
         }
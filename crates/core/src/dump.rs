@@ -0,0 +1,440 @@
+//! Human-readable listings of the canonical ABI for the functions of a world.
+//!
+//! This prints each function's core wasm signature along with the
+//! [`Instruction`]s that [`abi::call`] and [`abi::post_return`] feed to a
+//! language generator, which is useful when debugging generated glue code.
+
+use crate::abi::{self, AbiVariant, Bindgen, Instruction, LiftLower, WasmSignature, WasmType};
+use std::fmt::Write;
+use wit_parser::{
+    Function, Handle, Resolve, SizeAlign, Type, TypeDefKind, WorldId, WorldItem, WorldKey,
+};
+
+/// Returns a listing of the ABI of every function imported or exported by
+/// `world`.
+///
+/// Imports are listed with the instructions used by a guest to call them and
+/// exports with the instructions used by a guest to implement them, plus
+/// their `post-return` if they need one. Lists of numbers are assumed to be
+/// canonical, as they are in most generators.
+pub fn world(resolve: &Resolve, world: WorldId) -> String {
+    let mut sizes = SizeAlign::default();
+    sizes.fill(resolve);
+    let mut out = String::new();
+
+    for (key, item) in resolve.worlds[world].imports.iter() {
+        for (module, func) in functions(resolve, key, item) {
+            let module = module.as_deref().unwrap_or("$root");
+            writeln!(out, "import {module:?} {:?}", func.name).unwrap();
+            function(&mut out, resolve, &sizes, func, AbiVariant::GuestImport);
+        }
+    }
+    for (key, item) in resolve.worlds[world].exports.iter() {
+        for (interface, func) in functions(resolve, key, item) {
+            let name = match interface {
+                Some(interface) => format!("{interface}#{}", func.name),
+                None => func.name.clone(),
+            };
+            writeln!(out, "export {name:?}").unwrap();
+            function(&mut out, resolve, &sizes, func, AbiVariant::GuestExport);
+            if abi::guest_export_needs_post_return(resolve, func) {
+                let mut printer = Printer::new(&sizes);
+                abi::post_return(resolve, func, &mut printer);
+                printer.finish(&mut out, &format!("post-return (cabi_post_{name})"));
+            }
+        }
+    }
+    out
+}
+
+/// Returns the functions of a world item along with the name of the
+/// interface they're in, if any.
+fn functions<'a>(
+    resolve: &'a Resolve,
+    key: &WorldKey,
+    item: &'a WorldItem,
+) -> Vec<(Option<String>, &'a Function)> {
+    match item {
+        WorldItem::Function(func) => vec![(None, func)],
+        WorldItem::Interface(id) => {
+            let name = resolve.name_world_key(key);
+            resolve.interfaces[*id]
+                .functions
+                .values()
+                .map(|func| (Some(name.clone()), func))
+                .collect()
+        }
+        WorldItem::Type(_) => Vec::new(),
+    }
+}
+
+fn function(
+    out: &mut String,
+    resolve: &Resolve,
+    sizes: &SizeAlign,
+    func: &Function,
+    variant: AbiVariant,
+) {
+    let sig = resolve.wasm_signature(variant, func);
+    writeln!(out, "  signature: {}", signature(&sig)).unwrap();
+
+    let (lift_lower, title) = match variant {
+        AbiVariant::GuestImport => (LiftLower::LowerArgsLiftResults, "lower args, lift results"),
+        AbiVariant::GuestExport => (LiftLower::LiftArgsLowerResults, "lift args, lower results"),
+    };
+    let mut printer = Printer::new(sizes);
+    abi::call(resolve, variant, lift_lower, func, &mut printer);
+    printer.finish(out, title);
+}
+
+fn signature(sig: &WasmSignature) -> String {
+    let list = |tys: &[WasmType]| {
+        tys.iter()
+            .map(|ty| wasm_type(*ty))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut s = format!("({}) -> ({})", list(&sig.params), list(&sig.results));
+    if sig.indirect_params {
+        s.push_str(" indirect-params");
+    }
+    if sig.retptr {
+        s.push_str(" retptr");
+    }
+    s
+}
+
+fn wasm_type(ty: WasmType) -> &'static str {
+    match ty {
+        WasmType::I32 => "i32",
+        WasmType::I64 => "i64",
+        WasmType::F32 => "f32",
+        WasmType::F64 => "f64",
+    }
+}
+
+/// A `Bindgen` which prints each instruction, naming operands `v0`, `v1`, ...
+struct Printer<'a> {
+    sizes: &'a SizeAlign,
+    tmp: usize,
+    blocks: Vec<Vec<String>>,
+    finished: Vec<Vec<String>>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(sizes: &'a SizeAlign) -> Printer<'a> {
+        Printer {
+            sizes,
+            tmp: 0,
+            blocks: vec![Vec::new()],
+            finished: Vec::new(),
+        }
+    }
+
+    fn finish(mut self, out: &mut String, title: &str) {
+        assert!(self.finished.is_empty());
+        writeln!(out, "  {title}:").unwrap();
+        for line in self.blocks.pop().unwrap() {
+            writeln!(out, "    {line}").unwrap();
+        }
+    }
+
+    fn tmp(&mut self) -> String {
+        self.tmp += 1;
+        format!("v{}", self.tmp - 1)
+    }
+
+    fn line(&mut self, line: String) {
+        self.blocks.last_mut().unwrap().push(line);
+    }
+}
+
+impl Bindgen for Printer<'_> {
+    type Operand = String;
+
+    fn emit(
+        &mut self,
+        resolve: &Resolve,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<String>,
+        results: &mut Vec<String>,
+    ) {
+        for _ in 0..inst.results_len() {
+            let tmp = self.tmp();
+            results.push(tmp);
+        }
+        let mut line = String::new();
+        if !results.is_empty() {
+            line.push_str(&results.join(", "));
+            line.push_str(" = ");
+        }
+        write!(line, "{}({})", inst.name(), operands.join(", ")).unwrap();
+        let details = details(resolve, inst);
+        if !details.is_empty() {
+            line.push(' ');
+            line.push_str(&details);
+        }
+        self.line(line);
+
+        let blocks = self.finished.split_off(self.finished.len() - blocks(inst));
+        for (i, block) in blocks.into_iter().enumerate() {
+            self.line(format!("  block {i}:"));
+            for line in block {
+                self.line(format!("    {line}"));
+            }
+        }
+    }
+
+    fn return_pointer(&mut self, size: usize, align: usize) -> String {
+        let tmp = self.tmp();
+        self.line(format!("{tmp} = return_pointer size={size} align={align}"));
+        tmp
+    }
+
+    fn push_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    fn finish_block(&mut self, operands: &mut Vec<String>) {
+        let mut block = self.blocks.pop().unwrap();
+        if !operands.is_empty() {
+            block.push(format!("yield {}", operands.join(", ")));
+        }
+        self.finished.push(block);
+    }
+
+    fn sizes(&self) -> &SizeAlign {
+        self.sizes
+    }
+
+    fn is_list_canonical(&self, _resolve: &Resolve, element: &Type) -> bool {
+        matches!(
+            element,
+            Type::U8
+                | Type::S8
+                | Type::U16
+                | Type::S16
+                | Type::U32
+                | Type::S32
+                | Type::U64
+                | Type::S64
+                | Type::Float32
+                | Type::Float64
+        )
+    }
+}
+
+/// Returns how many blocks `inst` consumes.
+fn blocks(inst: &Instruction<'_>) -> usize {
+    use Instruction::*;
+
+    match inst {
+        ListLower { .. } | ListLift { .. } | GuestDeallocateList { .. } => 1,
+        VariantLower { variant, .. } | VariantLift { variant, .. } => variant.cases.len(),
+        OptionLower { .. } | OptionLift { .. } | ResultLower { .. } | ResultLift { .. } => 2,
+        GuestDeallocateVariant { blocks } => *blocks,
+        _ => 0,
+    }
+}
+
+fn details(resolve: &Resolve, inst: &Instruction<'_>) -> String {
+    use Instruction::*;
+
+    match inst {
+        GetArg { nth } => format!("nth={nth}"),
+        I32Const { val } => format!("val={val}"),
+        Bitcasts { casts } => format!("casts={casts:?}"),
+        ConstZero { tys } => {
+            let tys = tys.iter().map(|ty| wasm_type(*ty)).collect::<Vec<_>>();
+            format!("tys=[{}]", tys.join(", "))
+        }
+        I32Load { offset }
+        | I32Load8U { offset }
+        | I32Load8S { offset }
+        | I32Load16U { offset }
+        | I32Load16S { offset }
+        | I64Load { offset }
+        | F32Load { offset }
+        | F64Load { offset }
+        | I32Store { offset }
+        | I32Store8 { offset }
+        | I32Store16 { offset }
+        | I64Store { offset }
+        | F32Store { offset }
+        | F64Store { offset } => format!("offset={offset}"),
+        ListCanonLower { element, realloc } | ListLower { element, realloc } => format!(
+            "element={} realloc={}",
+            type_name(resolve, element),
+            realloc.unwrap_or("none")
+        ),
+        StringLower { realloc } => format!("realloc={}", realloc.unwrap_or("none")),
+        ListCanonLift { element, .. }
+        | ListLift { element, .. }
+        | IterElem { element }
+        | GuestDeallocateList { element } => {
+            format!("element={}", type_name(resolve, element))
+        }
+        RecordLower { name, .. }
+        | RecordLift { name, .. }
+        | HandleLower { name, .. }
+        | HandleLift { name, .. }
+        | FlagsLower { name, .. }
+        | FlagsLift { name, .. }
+        | VariantLower { name, .. }
+        | VariantLift { name, .. }
+        | EnumLower { name, .. }
+        | EnumLift { name, .. } => format!("name={name}"),
+        TupleLower { ty, .. }
+        | TupleLift { ty, .. }
+        | FutureLower { ty, .. }
+        | FutureLift { ty, .. }
+        | StreamLower { ty, .. }
+        | StreamLift { ty, .. }
+        | LowerRemapped { ty, .. }
+        | LiftRemapped { ty }
+        | OptionLower { ty, .. }
+        | OptionLift { ty, .. }
+        | ResultLower { ty, .. }
+        | ResultLift { ty, .. } => format!("ty={}", type_name(resolve, &Type::Id(*ty))),
+        CallWasm { name, .. } => format!("name={name}"),
+        CallInterface { func } => format!("func={}", func.name),
+        Return { amt, .. } => format!("amt={amt}"),
+        Malloc {
+            realloc,
+            size,
+            align,
+        } => format!("realloc={realloc} size={size} align={align}"),
+        GuestDeallocate { size, align } => format!("size={size} align={align}"),
+        GuestDeallocateVariant { blocks } => format!("blocks={blocks}"),
+        _ => String::new(),
+    }
+}
+
+/// Returns the WIT syntax for `ty`, using the name of named types.
+fn type_name(resolve: &Resolve, ty: &Type) -> String {
+    let id = match ty {
+        Type::Bool => return "bool".to_string(),
+        Type::U8 => return "u8".to_string(),
+        Type::U16 => return "u16".to_string(),
+        Type::U32 => return "u32".to_string(),
+        Type::U64 => return "u64".to_string(),
+        Type::S8 => return "s8".to_string(),
+        Type::S16 => return "s16".to_string(),
+        Type::S32 => return "s32".to_string(),
+        Type::S64 => return "s64".to_string(),
+        Type::Float32 => return "float32".to_string(),
+        Type::Float64 => return "float64".to_string(),
+        Type::Char => return "char".to_string(),
+        Type::String => return "string".to_string(),
+        Type::Id(id) => *id,
+    };
+    let ty = &resolve.types[id];
+    if let Some(name) = &ty.name {
+        return name.clone();
+    }
+    let opt = |ty: &Option<Type>| match ty {
+        Some(ty) => type_name(resolve, ty),
+        None => "_".to_string(),
+    };
+    match &ty.kind {
+        TypeDefKind::Type(t) => type_name(resolve, t),
+        TypeDefKind::List(t) => format!("list<{}>", type_name(resolve, t)),
+        TypeDefKind::Option(t) => format!("option<{}>", type_name(resolve, t)),
+        TypeDefKind::Result(r) => format!("result<{}, {}>", opt(&r.ok), opt(&r.err)),
+        TypeDefKind::Tuple(t) => {
+            let types = t
+                .types
+                .iter()
+                .map(|t| type_name(resolve, t))
+                .collect::<Vec<_>>();
+            format!("tuple<{}>", types.join(", "))
+        }
+        TypeDefKind::Handle(Handle::Own(r)) => {
+            format!("own<{}>", type_name(resolve, &Type::Id(*r)))
+        }
+        TypeDefKind::Handle(Handle::Borrow(r)) => {
+            format!("borrow<{}>", type_name(resolve, &Type::Id(*r)))
+        }
+        TypeDefKind::Future(t) => format!("future<{}>", opt(t)),
+        TypeDefKind::Stream(s) => format!("stream<{}, {}>", opt(&s.element), opt(&s.end)),
+        _ => unreachable!("anonymous {:?}", ty.kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wit_parser::UnresolvedPackage;
+
+    #[test]
+    fn listing() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    "dump.wit".as_ref(),
+                    "
+                        package test:dump;
+
+                        interface i {
+                            get: func(key: string) -> option<list<u8>>;
+                        }
+
+                        world w {
+                            import i;
+                            export names: func() -> list<string>;
+                        }
+                    ",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let world = resolve.select_world(pkg, None).unwrap();
+        let listing = super::world(&resolve, world);
+        let expected = "\
+import \"test:dump/i\" \"get\"
+  signature: (i32, i32, i32) -> () retptr
+  lower args, lift results:
+    v0 = GetArg() nth=0
+    v1, v2 = StringLower(v0) realloc=none
+    v3 = return_pointer size=12 align=4
+    CallWasm(v1, v2, v3) name=get
+    v4 = I32Load8U(v3) offset=0
+    v8 = OptionLift(v4) ty=option<list<u8>>
+      block 0:
+      block 1:
+        v5 = I32Load(v3) offset=4
+        v6 = I32Load(v3) offset=8
+        v7 = ListCanonLift(v5, v6) element=u8
+        yield v7
+    Return(v8) amt=1
+export \"names\"
+  signature: () -> (i32) retptr
+  lift args, lower results:
+    v0 = CallInterface() func=names
+    v1 = return_pointer size=8 align=4
+    v6, v7 = ListLower(v0) element=string realloc=cabi_realloc
+      block 0:
+        v2 = IterElem() element=string
+        v3 = IterBasePointer()
+        v4, v5 = StringLower(v2) realloc=cabi_realloc
+        I32Store(v5, v3) offset=4
+        I32Store(v4, v3) offset=0
+    I32Store(v7, v1) offset=4
+    I32Store(v6, v1) offset=0
+    Return(v1) amt=1
+  post-return (cabi_post_names):
+    v0 = GetArg() nth=0
+    v4 = I32Load(v0) offset=0
+    v5 = I32Load(v0) offset=4
+    GuestDeallocateList(v4, v5) element=string
+      block 0:
+        v1 = IterBasePointer()
+        v2 = I32Load(v1) offset=0
+        v3 = I32Load(v1) offset=4
+        GuestDeallocateString(v2, v3)
+    Return() amt=0
+";
+        assert_eq!(listing, expected);
+    }
+}
@@ -0,0 +1,1536 @@
+//! A reference interpreter for the instruction streams produced by
+//! [`abi::call`] and [`abi::post_return`].
+//!
+//! Language generators translate [`Instruction`]s into source code, so a
+//! mistake in the ABI glue usually only shows up once the generated code is
+//! compiled and run inside of a component. This module instead executes the
+//! instruction streams directly: component values are represented with
+//! [`Value`], the guest's linear memory is simulated with [`Memory`], and
+//! `cabi_realloc` hands out allocations which are tracked until freed.
+//!
+//! An [`Interpreter`] plays both sides of a call. Arguments are lowered by
+//! the caller, lifted by the callee, handed to a closure implementing the
+//! function, and the results travel back the same way. Invalid frees and
+//! accesses to freed memory are reported as an [`Error`], and anything left
+//! allocated afterwards is available from [`Memory::leaks`].
+
+use crate::abi::{self, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use wit_parser::{Function, Resolve, SizeAlign, Type};
+
+/// A component model value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U8(u8),
+    S8(i8),
+    U16(u16),
+    S16(i16),
+    U32(u32),
+    S32(i32),
+    U64(u64),
+    S64(i64),
+    Float32(f32),
+    Float64(f64),
+    Char(char),
+    String(String),
+    List(Vec<Value>),
+    Record(Vec<Value>),
+    Tuple(Vec<Value>),
+    /// The index of a case and its payload, if any.
+    Variant(u32, Option<Box<Value>>),
+    /// The index of a case.
+    Enum(u32),
+    Option(Option<Box<Value>>),
+    Result(Result<Option<Box<Value>>, Option<Box<Value>>>),
+    /// Whether each flag, in declaration order, is set.
+    Flags(Vec<bool>),
+    /// Both `own` and `borrow` handles, represented by their index.
+    Handle(u32),
+    Future(u32),
+    Stream(u32),
+}
+
+/// A block of memory handed out by `cabi_realloc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    pub ptr: u32,
+    pub size: usize,
+    pub align: usize,
+}
+
+/// Errors raised while executing an instruction stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Memory was accessed outside of any allocation.
+    OutOfBounds { addr: u32, len: usize },
+    /// Memory was accessed after the allocation containing it was freed.
+    UseAfterFree { addr: u32 },
+    /// An allocation was freed more than once.
+    DoubleFree { ptr: u32 },
+    /// A pointer which wasn't returned by `cabi_realloc` was freed.
+    InvalidFree { ptr: u32 },
+    /// An allocation was freed with a different size or alignment than it
+    /// was allocated with.
+    FreeMismatch {
+        allocated: Allocation,
+        freed: Allocation,
+    },
+    /// An operand didn't have the shape an instruction expected.
+    TypeMismatch { expected: String, found: String },
+    /// A variant or enum discriminant was out of range.
+    InvalidDiscriminant { discriminant: u32, cases: usize },
+    /// A `char` was lifted from an invalid scalar value.
+    InvalidChar(u32),
+    /// A `string` was lifted from bytes which aren't valid UTF-8.
+    InvalidUtf8 { ptr: u32, len: usize },
+    /// A function implementation returned the wrong number of results.
+    ResultCount { expected: usize, found: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OutOfBounds { addr, len } => {
+                write!(f, "out-of-bounds access of {len} bytes at {addr:#x}")
+            }
+            Error::UseAfterFree { addr } => write!(f, "use of freed memory at {addr:#x}"),
+            Error::DoubleFree { ptr } => write!(f, "double free of {ptr:#x}"),
+            Error::InvalidFree { ptr } => write!(f, "free of unallocated pointer {ptr:#x}"),
+            Error::FreeMismatch { allocated, freed } => write!(
+                f,
+                "{:#x} allocated with size {} and align {} but freed with size {} and align {}",
+                allocated.ptr, allocated.size, allocated.align, freed.size, freed.align
+            ),
+            Error::TypeMismatch { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            Error::InvalidDiscriminant {
+                discriminant,
+                cases,
+            } => write!(
+                f,
+                "discriminant {discriminant} out of range for {cases} cases"
+            ),
+            Error::InvalidChar(c) => write!(f, "invalid char {c:#x}"),
+            Error::InvalidUtf8 { ptr, len } => {
+                write!(f, "invalid utf-8 in string of {len} bytes at {ptr:#x}")
+            }
+            Error::ResultCount { expected, found } => {
+                write!(f, "expected {expected} results, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Live,
+    Freed,
+    /// Return areas, which are never freed.
+    Scratch,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    size: usize,
+    align: usize,
+    state: State,
+}
+
+/// A simulated linear memory.
+///
+/// Memory is never reused once freed so that stale pointers can always be
+/// detected, and only bytes within an allocation may be accessed.
+#[derive(Debug, Default)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    regions: BTreeMap<u32, Region>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    /// Returns all allocations which haven't been freed yet.
+    pub fn leaks(&self) -> Vec<Allocation> {
+        self.regions
+            .iter()
+            .filter(|(_, r)| r.state == State::Live)
+            .map(|(ptr, r)| Allocation {
+                ptr: *ptr,
+                size: r.size,
+                align: r.align,
+            })
+            .collect()
+    }
+
+    /// Emulates `cabi_realloc(0, 0, align, size)`.
+    ///
+    /// Zero-sized allocations aren't tracked and return `align` as a
+    /// dangling pointer.
+    pub fn malloc(&mut self, size: usize, align: usize) -> u32 {
+        self.alloc(size, align, State::Live)
+    }
+
+    /// Frees an allocation previously returned from [`Memory::malloc`].
+    pub fn free(&mut self, ptr: u32, size: usize, align: usize) -> Result<(), Error> {
+        if size == 0 {
+            return Ok(());
+        }
+        match self.regions.get_mut(&ptr) {
+            Some(r) if r.state == State::Freed => Err(Error::DoubleFree { ptr }),
+            Some(r) if r.state == State::Live => {
+                if r.size != size || r.align != align {
+                    return Err(Error::FreeMismatch {
+                        allocated: Allocation {
+                            ptr,
+                            size: r.size,
+                            align: r.align,
+                        },
+                        freed: Allocation { ptr, size, align },
+                    });
+                }
+                r.state = State::Freed;
+                Ok(())
+            }
+            _ => Err(Error::InvalidFree { ptr }),
+        }
+    }
+
+    pub fn read(&self, addr: u32, len: usize) -> Result<&[u8], Error> {
+        self.check(addr, len)?;
+        if len == 0 {
+            return Ok(&[]);
+        }
+        let start = addr as usize;
+        Ok(&self.bytes[start..start + len])
+    }
+
+    pub fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+        self.check(addr, data.len())?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let start = addr as usize;
+        self.bytes[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn alloc(&mut self, size: usize, align: usize, state: State) -> u32 {
+        if size == 0 {
+            return align as u32;
+        }
+        // Keep the first few bytes unallocated so a null pointer is never
+        // handed out.
+        let ptr = align_to(self.bytes.len().max(8), align);
+        self.bytes.resize(ptr + size, 0);
+        let ptr = u32::try_from(ptr).expect("simulated memory exhausted");
+        self.regions.insert(ptr, Region { size, align, state });
+        ptr
+    }
+
+    fn check(&self, addr: u32, len: usize) -> Result<(), Error> {
+        if len == 0 {
+            return Ok(());
+        }
+        let region = self
+            .regions
+            .range(..=addr)
+            .next_back()
+            .filter(|(start, r)| addr as usize + len <= **start as usize + r.size);
+        match region {
+            Some((_, r)) if r.state == State::Freed => Err(Error::UseAfterFree { addr }),
+            Some(_) => Ok(()),
+            None => Err(Error::OutOfBounds { addr, len }),
+        }
+    }
+}
+
+/// Executes the glue for functions against a simulated guest.
+///
+/// The guest side of each call follows the ownership rules of guest
+/// languages: lists and strings it lifts are freed once read, and memory it
+/// lowers for an import without `realloc` is freed when the import returns.
+/// The host side never frees what it lifts, relying on `post-return`
+/// instead.
+pub struct Interpreter<'a> {
+    resolve: &'a Resolve,
+    sizes: SizeAlign,
+    memory: Memory,
+    /// Whether lists of numbers use the `ListCanon*` instructions rather than
+    /// being lifted and lowered element by element. Defaults to `true`.
+    pub canonical_lists: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(resolve: &'a Resolve) -> Interpreter<'a> {
+        let mut sizes = SizeAlign::default();
+        sizes.fill(resolve);
+        Interpreter {
+            resolve,
+            sizes,
+            memory: Memory::new(),
+            canonical_lists: true,
+        }
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Calls `func`, exported by the guest, from the host.
+    ///
+    /// `imp` is the guest's implementation of the export which receives the
+    /// arguments after they've been lifted in the guest. The returned results
+    /// are lowered by the guest and lifted by the host, after which the
+    /// function's `post-return` is run, if it has one.
+    pub fn call_export(
+        &mut self,
+        func: &Function,
+        args: Vec<Value>,
+        mut imp: impl FnMut(Vec<Value>) -> Vec<Value>,
+    ) -> Result<Vec<Value>, Error> {
+        let host = self.record(
+            func,
+            AbiVariant::GuestExport,
+            LiftLower::LowerArgsLiftResults,
+        );
+        let guest = self.record(
+            func,
+            AbiVariant::GuestExport,
+            LiftLower::LiftArgsLowerResults,
+        );
+        let mut guest_results = Vec::new();
+        let results = host.run(
+            &mut self.memory,
+            args.into_iter().map(Slot::Value).collect(),
+            false,
+            &mut |memory: &mut Memory, args: Vec<Slot>| {
+                let results = guest.run(memory, args, true, &mut |_: &mut Memory, args| {
+                    call_interface(func, &mut imp, args)
+                })?;
+                guest_results = results.clone();
+                Ok(results)
+            },
+        )?;
+
+        if abi::guest_export_needs_post_return(self.resolve, func) {
+            let mut bindgen = Recorder::new(&self.sizes, self.canonical_lists);
+            abi::post_return(self.resolve, func, &mut bindgen);
+            bindgen
+                .finish()
+                .run(&mut self.memory, guest_results, true, &mut |_, _| {
+                    unreachable!("post-return doesn't call functions")
+                })?;
+        }
+
+        results.into_iter().map(value).collect()
+    }
+
+    /// Calls `func`, imported by the guest, from the guest.
+    ///
+    /// `imp` is the host's implementation of the import which receives the
+    /// arguments after they've been lifted by the host. The returned results
+    /// are lowered into the guest's memory and then lifted by the guest.
+    pub fn call_import(
+        &mut self,
+        func: &Function,
+        args: Vec<Value>,
+        mut imp: impl FnMut(Vec<Value>) -> Vec<Value>,
+    ) -> Result<Vec<Value>, Error> {
+        let guest = self.record(
+            func,
+            AbiVariant::GuestImport,
+            LiftLower::LowerArgsLiftResults,
+        );
+        let host = self.record(
+            func,
+            AbiVariant::GuestImport,
+            LiftLower::LiftArgsLowerResults,
+        );
+        let results = guest.run(
+            &mut self.memory,
+            args.into_iter().map(Slot::Value).collect(),
+            true,
+            &mut |memory: &mut Memory, args: Vec<Slot>| {
+                host.run(memory, args, false, &mut |_: &mut Memory, args| {
+                    call_interface(func, &mut imp, args)
+                })
+            },
+        )?;
+        results.into_iter().map(value).collect()
+    }
+
+    fn record(&self, func: &Function, variant: AbiVariant, lift_lower: LiftLower) -> Program {
+        let mut bindgen = Recorder::new(&self.sizes, self.canonical_lists);
+        abi::call(self.resolve, variant, lift_lower, func, &mut bindgen);
+        bindgen.finish()
+    }
+}
+
+fn call_interface(
+    func: &Function,
+    imp: &mut dyn FnMut(Vec<Value>) -> Vec<Value>,
+    args: Vec<Slot>,
+) -> Result<Vec<Slot>, Error> {
+    let args = args.into_iter().map(value).collect::<Result<_, _>>()?;
+    let results = imp(args);
+    if results.len() != func.results.len() {
+        return Err(Error::ResultCount {
+            expected: func.results.len(),
+            found: results.len(),
+        });
+    }
+    Ok(results.into_iter().map(Slot::Value).collect())
+}
+
+/// A core wasm value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CoreValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+/// The contents of a register: either side of a lift or lower.
+#[derive(Debug, Clone)]
+enum Slot {
+    Core(CoreValue),
+    Value(Value),
+    /// The payload of a variant case without one.
+    Unit,
+}
+
+type Reg = usize;
+
+/// Instructions can't outlive the call to `Bindgen::emit`, so they're
+/// recorded as `Op`s with any type information they need resolved up front.
+#[derive(Debug)]
+struct Op {
+    kind: OpKind,
+    operands: Vec<Reg>,
+    results: Vec<Reg>,
+}
+
+#[derive(Debug, Default)]
+struct Block {
+    ops: Vec<Op>,
+    results: Vec<Reg>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Load {
+    I32,
+    U8,
+    S8,
+    U16,
+    S16,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Store {
+    I32,
+    I8,
+    I16,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum VariantKind {
+    Variant,
+    Option,
+    Result,
+}
+
+#[derive(Debug)]
+enum OpKind {
+    GetArg(usize),
+    Const(CoreValue),
+    Bitcasts(Vec<Bitcast>),
+    ConstZero(Vec<WasmType>),
+    Load(Load, i32),
+    Store(Store, i32),
+    Lower(Type),
+    Lift(Type),
+    ListCanonLower {
+        element: Type,
+        size: usize,
+        align: usize,
+        realloc: bool,
+    },
+    StringLower {
+        realloc: bool,
+    },
+    ListLower {
+        size: usize,
+        align: usize,
+        realloc: bool,
+        block: Block,
+    },
+    ListCanonLift {
+        element: Type,
+        size: usize,
+        align: usize,
+    },
+    StringLift,
+    ListLift {
+        size: usize,
+        align: usize,
+        block: Block,
+    },
+    IterElem,
+    IterBasePointer,
+    RecordLower,
+    RecordLift,
+    TupleLower,
+    TupleLift,
+    HandleLower,
+    HandleLift,
+    FutureLower,
+    FutureLift,
+    StreamLower,
+    StreamLift,
+    /// Values are always WIT values here, so conversions to and from
+    /// remapped types pass them through as-is.
+    Remapped,
+    FlagsLower(usize),
+    FlagsLift(usize),
+    VariantPayloadName,
+    VariantLower(VariantKind, Vec<Block>),
+    VariantLift(VariantKind, Vec<Block>),
+    EnumLower,
+    EnumLift(usize),
+    Call,
+    Return,
+    ReturnPointer {
+        size: usize,
+        align: usize,
+    },
+    Malloc {
+        size: usize,
+        align: usize,
+    },
+    GuestDeallocate {
+        size: usize,
+        align: usize,
+    },
+    GuestDeallocateString,
+    GuestDeallocateList {
+        size: usize,
+        align: usize,
+        block: Block,
+    },
+    GuestDeallocateVariant(Vec<Block>),
+}
+
+/// A `Bindgen` which records the instruction stream for later execution.
+struct Recorder<'a> {
+    sizes: &'a SizeAlign,
+    canonical_lists: bool,
+    regs: usize,
+    blocks: Vec<Vec<Op>>,
+    finished: Vec<Block>,
+}
+
+impl<'a> Recorder<'a> {
+    fn new(sizes: &'a SizeAlign, canonical_lists: bool) -> Recorder<'a> {
+        Recorder {
+            sizes,
+            canonical_lists,
+            regs: 0,
+            blocks: vec![Vec::new()],
+            finished: Vec::new(),
+        }
+    }
+
+    fn finish(mut self) -> Program {
+        assert_eq!(self.blocks.len(), 1);
+        assert!(self.finished.is_empty());
+        Program {
+            block: Block {
+                ops: self.blocks.pop().unwrap(),
+                results: Vec::new(),
+            },
+            regs: self.regs,
+        }
+    }
+
+    fn push(&mut self, kind: OpKind, operands: Vec<Reg>, results: usize) -> Vec<Reg> {
+        let results = (0..results)
+            .map(|_| {
+                self.regs += 1;
+                self.regs - 1
+            })
+            .collect::<Vec<_>>();
+        self.blocks.last_mut().unwrap().push(Op {
+            kind,
+            operands,
+            results: results.clone(),
+        });
+        results
+    }
+
+    fn take_blocks(&mut self, n: usize) -> Vec<Block> {
+        self.finished.split_off(self.finished.len() - n)
+    }
+
+    fn take_block(&mut self) -> Block {
+        self.finished.pop().unwrap()
+    }
+}
+
+impl Bindgen for Recorder<'_> {
+    type Operand = Reg;
+
+    fn emit(
+        &mut self,
+        _resolve: &Resolve,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<Reg>,
+        results: &mut Vec<Reg>,
+    ) {
+        use Instruction::*;
+
+        let kind = match inst {
+            GetArg { nth } => OpKind::GetArg(*nth),
+            I32Const { val } => OpKind::Const(CoreValue::I32(*val)),
+            Bitcasts { casts } => OpKind::Bitcasts(casts.to_vec()),
+            ConstZero { tys } => OpKind::ConstZero(tys.to_vec()),
+
+            I32Load { offset } => OpKind::Load(Load::I32, *offset),
+            I32Load8U { offset } => OpKind::Load(Load::U8, *offset),
+            I32Load8S { offset } => OpKind::Load(Load::S8, *offset),
+            I32Load16U { offset } => OpKind::Load(Load::U16, *offset),
+            I32Load16S { offset } => OpKind::Load(Load::S16, *offset),
+            I64Load { offset } => OpKind::Load(Load::I64, *offset),
+            F32Load { offset } => OpKind::Load(Load::F32, *offset),
+            F64Load { offset } => OpKind::Load(Load::F64, *offset),
+            I32Store { offset } => OpKind::Store(Store::I32, *offset),
+            I32Store8 { offset } => OpKind::Store(Store::I8, *offset),
+            I32Store16 { offset } => OpKind::Store(Store::I16, *offset),
+            I64Store { offset } => OpKind::Store(Store::I64, *offset),
+            F32Store { offset } => OpKind::Store(Store::F32, *offset),
+            F64Store { offset } => OpKind::Store(Store::F64, *offset),
+
+            I32FromChar => OpKind::Lower(Type::Char),
+            I64FromU64 => OpKind::Lower(Type::U64),
+            I64FromS64 => OpKind::Lower(Type::S64),
+            I32FromU32 => OpKind::Lower(Type::U32),
+            I32FromS32 => OpKind::Lower(Type::S32),
+            I32FromU16 => OpKind::Lower(Type::U16),
+            I32FromS16 => OpKind::Lower(Type::S16),
+            I32FromU8 => OpKind::Lower(Type::U8),
+            I32FromS8 => OpKind::Lower(Type::S8),
+            F32FromFloat32 => OpKind::Lower(Type::Float32),
+            F64FromFloat64 => OpKind::Lower(Type::Float64),
+            I32FromBool => OpKind::Lower(Type::Bool),
+            S8FromI32 => OpKind::Lift(Type::S8),
+            U8FromI32 => OpKind::Lift(Type::U8),
+            S16FromI32 => OpKind::Lift(Type::S16),
+            U16FromI32 => OpKind::Lift(Type::U16),
+            S32FromI32 => OpKind::Lift(Type::S32),
+            U32FromI32 => OpKind::Lift(Type::U32),
+            S64FromI64 => OpKind::Lift(Type::S64),
+            U64FromI64 => OpKind::Lift(Type::U64),
+            CharFromI32 => OpKind::Lift(Type::Char),
+            Float32FromF32 => OpKind::Lift(Type::Float32),
+            Float64FromF64 => OpKind::Lift(Type::Float64),
+            BoolFromI32 => OpKind::Lift(Type::Bool),
+
+            ListCanonLower { element, realloc } => OpKind::ListCanonLower {
+                element: **element,
+                size: self.sizes.size(element),
+                align: self.sizes.align(element),
+                realloc: realloc.is_some(),
+            },
+            StringLower { realloc } => OpKind::StringLower {
+                realloc: realloc.is_some(),
+            },
+            ListLower { element, realloc } => OpKind::ListLower {
+                size: self.sizes.size(element),
+                align: self.sizes.align(element),
+                realloc: realloc.is_some(),
+                block: self.take_block(),
+            },
+            ListCanonLift { element, .. } => OpKind::ListCanonLift {
+                element: **element,
+                size: self.sizes.size(element),
+                align: self.sizes.align(element),
+            },
+            StringLift { .. } => OpKind::StringLift,
+            ListLift { element, .. } => OpKind::ListLift {
+                size: self.sizes.size(element),
+                align: self.sizes.align(element),
+                block: self.take_block(),
+            },
+            IterElem { .. } => OpKind::IterElem,
+            IterBasePointer => OpKind::IterBasePointer,
+
+            RecordLower { .. } => OpKind::RecordLower,
+            RecordLift { .. } => OpKind::RecordLift,
+            TupleLower { .. } => OpKind::TupleLower,
+            TupleLift { .. } => OpKind::TupleLift,
+            HandleLower { .. } => OpKind::HandleLower,
+            HandleLift { .. } => OpKind::HandleLift,
+            FutureLower { .. } => OpKind::FutureLower,
+            FutureLift { .. } => OpKind::FutureLift,
+            StreamLower { .. } => OpKind::StreamLower,
+            StreamLift { .. } => OpKind::StreamLift,
+            LowerRemapped { .. } | LiftRemapped { .. } => OpKind::Remapped,
+            FlagsLower { flags, .. } => OpKind::FlagsLower(flags.flags.len()),
+            FlagsLift { flags, .. } => OpKind::FlagsLift(flags.flags.len()),
+
+            VariantPayloadName => OpKind::VariantPayloadName,
+            VariantLower { variant, .. } => {
+                OpKind::VariantLower(VariantKind::Variant, self.take_blocks(variant.cases.len()))
+            }
+            VariantLift { variant, .. } => {
+                OpKind::VariantLift(VariantKind::Variant, self.take_blocks(variant.cases.len()))
+            }
+            OptionLower { .. } => OpKind::VariantLower(VariantKind::Option, self.take_blocks(2)),
+            OptionLift { .. } => OpKind::VariantLift(VariantKind::Option, self.take_blocks(2)),
+            ResultLower { .. } => OpKind::VariantLower(VariantKind::Result, self.take_blocks(2)),
+            ResultLift { .. } => OpKind::VariantLift(VariantKind::Result, self.take_blocks(2)),
+            EnumLower { .. } => OpKind::EnumLower,
+            EnumLift { enum_, .. } => OpKind::EnumLift(enum_.cases.len()),
+
+            CallWasm { .. } | CallInterface { .. } => OpKind::Call,
+            Return { .. } => OpKind::Return,
+            Malloc { size, align, .. } => OpKind::Malloc {
+                size: *size,
+                align: *align,
+            },
+            GuestDeallocate { size, align } => OpKind::GuestDeallocate {
+                size: *size,
+                align: *align,
+            },
+            GuestDeallocateString => OpKind::GuestDeallocateString,
+            GuestDeallocateList { element } => OpKind::GuestDeallocateList {
+                size: self.sizes.size(element),
+                align: self.sizes.align(element),
+                block: self.take_block(),
+            },
+            GuestDeallocateVariant { blocks } => {
+                OpKind::GuestDeallocateVariant(self.take_blocks(*blocks))
+            }
+        };
+        let regs = self.push(kind, mem::take(operands), inst.results_len());
+        results.extend(regs);
+    }
+
+    fn return_pointer(&mut self, size: usize, align: usize) -> Reg {
+        self.push(OpKind::ReturnPointer { size, align }, Vec::new(), 1)[0]
+    }
+
+    fn push_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    fn finish_block(&mut self, operands: &mut Vec<Reg>) {
+        let ops = self.blocks.pop().unwrap();
+        self.finished.push(Block {
+            ops,
+            results: mem::take(operands),
+        });
+    }
+
+    fn sizes(&self) -> &SizeAlign {
+        self.sizes
+    }
+
+    fn is_list_canonical(&self, _resolve: &Resolve, element: &Type) -> bool {
+        self.canonical_lists
+            && matches!(
+                element,
+                Type::U8
+                    | Type::S8
+                    | Type::U16
+                    | Type::S16
+                    | Type::U32
+                    | Type::S32
+                    | Type::U64
+                    | Type::S64
+                    | Type::Float32
+                    | Type::Float64
+            )
+    }
+}
+
+/// A recorded instruction stream.
+struct Program {
+    block: Block,
+    regs: usize,
+}
+
+type Call<'a> = dyn FnMut(&mut Memory, Vec<Slot>) -> Result<Vec<Slot>, Error> + 'a;
+
+impl Program {
+    /// Runs this program with `args`, returning the operands of its
+    /// `Return`.
+    ///
+    /// `owns_lifts` indicates whether lifted lists and strings are freed once
+    /// read and `call` implements `CallWasm` and `CallInterface`.
+    fn run(
+        &self,
+        memory: &mut Memory,
+        args: Vec<Slot>,
+        owns_lifts: bool,
+        call: &mut Call<'_>,
+    ) -> Result<Vec<Slot>, Error> {
+        let mut machine = Machine {
+            memory,
+            call,
+            args,
+            regs: vec![Slot::Unit; self.regs],
+            owns_lifts,
+            temporaries: Vec::new(),
+            elems: Vec::new(),
+            bases: Vec::new(),
+            payloads: Vec::new(),
+            ret: None,
+        };
+        machine.run_block(&self.block)?;
+        Ok(machine.ret.expect("program didn't return"))
+    }
+}
+
+struct Machine<'a, 'b> {
+    memory: &'a mut Memory,
+    call: &'a mut Call<'b>,
+    args: Vec<Slot>,
+    regs: Vec<Slot>,
+    owns_lifts: bool,
+    /// Memory lowered without `realloc`, freed on `Return`.
+    temporaries: Vec<Allocation>,
+    // The values of `IterElem`, `IterBasePointer` and `VariantPayloadName`
+    // for the innermost block being executed.
+    elems: Vec<Value>,
+    bases: Vec<u32>,
+    payloads: Vec<Slot>,
+    ret: Option<Vec<Slot>>,
+}
+
+impl Machine<'_, '_> {
+    fn run_block(&mut self, block: &Block) -> Result<Vec<Slot>, Error> {
+        for op in block.ops.iter() {
+            let operands = op.operands.iter().map(|r| self.regs[*r].clone()).collect();
+            let results = self.exec(op, operands)?;
+            assert_eq!(results.len(), op.results.len(), "wrong results for {op:?}");
+            for (reg, result) in op.results.iter().zip(results) {
+                self.regs[*reg] = result;
+            }
+        }
+        Ok(block
+            .results
+            .iter()
+            .map(|r| self.regs[*r].clone())
+            .collect())
+    }
+
+    fn exec(&mut self, op: &Op, operands: Vec<Slot>) -> Result<Vec<Slot>, Error> {
+        let mut operands = operands.into_iter();
+        let mut operand = || operands.next().unwrap();
+        let results = match &op.kind {
+            OpKind::GetArg(nth) => vec![self.args[*nth].clone()],
+            OpKind::Const(val) => vec![Slot::Core(*val)],
+            OpKind::Bitcasts(casts) => casts
+                .iter()
+                .map(|cast| Ok(Slot::Core(bitcast(*cast, core(operand())?)?)))
+                .collect::<Result<_, Error>>()?,
+            OpKind::ConstZero(tys) => tys
+                .iter()
+                .map(|ty| {
+                    Slot::Core(match ty {
+                        WasmType::I32 => CoreValue::I32(0),
+                        WasmType::I64 => CoreValue::I64(0),
+                        WasmType::F32 => CoreValue::F32(0.0),
+                        WasmType::F64 => CoreValue::F64(0.0),
+                    })
+                })
+                .collect(),
+            OpKind::Load(load, offset) => {
+                let addr = address(operand(), *offset)?;
+                vec![Slot::Core(self.load(*load, addr)?)]
+            }
+            OpKind::Store(store, offset) => {
+                let val = core(operand())?;
+                let addr = address(operand(), *offset)?;
+                self.store(*store, addr, val)?;
+                Vec::new()
+            }
+            OpKind::Lower(ty) => vec![Slot::Core(lower(ty, value(operand())?)?)],
+            OpKind::Lift(ty) => vec![Slot::Value(lift(ty, core(operand())?)?)],
+
+            OpKind::ListCanonLower {
+                element,
+                size,
+                align,
+                realloc,
+            } => {
+                let elems = list(value(operand())?)?;
+                let len = elems.len();
+                let ptr = self.alloc(len * size, *align, *realloc);
+                let (_, store) = access(element);
+                for (i, elem) in elems.into_iter().enumerate() {
+                    self.store(store, ptr + (i * size) as u32, lower(element, elem)?)?;
+                }
+                vec![i32(ptr), i32(len as u32)]
+            }
+            OpKind::StringLower { realloc } => {
+                let s = match value(operand())? {
+                    Value::String(s) => s,
+                    other => return Err(mismatch("string", other)),
+                };
+                let ptr = self.alloc(s.len(), 1, *realloc);
+                self.memory.write(ptr, s.as_bytes())?;
+                vec![i32(ptr), i32(s.len() as u32)]
+            }
+            OpKind::ListLower {
+                size,
+                align,
+                realloc,
+                block,
+            } => {
+                let elems = list(value(operand())?)?;
+                let len = elems.len();
+                let ptr = self.alloc(len * size, *align, *realloc);
+                for (i, elem) in elems.into_iter().enumerate() {
+                    self.elems.push(elem);
+                    self.bases.push(ptr + (i * size) as u32);
+                    let result = self.run_block(block);
+                    self.elems.pop();
+                    self.bases.pop();
+                    result?;
+                }
+                vec![i32(ptr), i32(len as u32)]
+            }
+            OpKind::ListCanonLift {
+                element,
+                size,
+                align,
+            } => {
+                let ptr = addr(operand())?;
+                let len = addr(operand())? as usize;
+                let (load, _) = access(element);
+                let elems = (0..len)
+                    .map(|i| lift(element, self.load(load, ptr + (i * size) as u32)?))
+                    .collect::<Result<_, _>>()?;
+                self.release(ptr, len * size, *align)?;
+                vec![Slot::Value(Value::List(elems))]
+            }
+            OpKind::StringLift => {
+                let ptr = addr(operand())?;
+                let len = addr(operand())? as usize;
+                let bytes = self.memory.read(ptr, len)?.to_vec();
+                let s = String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 { ptr, len })?;
+                self.release(ptr, len, 1)?;
+                vec![Slot::Value(Value::String(s))]
+            }
+            OpKind::ListLift { size, align, block } => {
+                let ptr = addr(operand())?;
+                let len = addr(operand())? as usize;
+                let mut elems = Vec::with_capacity(len);
+                for i in 0..len {
+                    self.bases.push(ptr + (i * size) as u32);
+                    let result = self.run_block(block);
+                    self.bases.pop();
+                    elems.push(value(result?.pop().unwrap())?);
+                }
+                self.release(ptr, len * size, *align)?;
+                vec![Slot::Value(Value::List(elems))]
+            }
+            OpKind::IterElem => vec![Slot::Value(self.elems.last().unwrap().clone())],
+            OpKind::IterBasePointer => vec![i32(*self.bases.last().unwrap())],
+
+            OpKind::RecordLower => match value(operand())? {
+                Value::Record(fields) if fields.len() == op.results.len() => {
+                    fields.into_iter().map(Slot::Value).collect()
+                }
+                other => return Err(mismatch("record", other)),
+            },
+            OpKind::TupleLower => match value(operand())? {
+                Value::Tuple(fields) if fields.len() == op.results.len() => {
+                    fields.into_iter().map(Slot::Value).collect()
+                }
+                other => return Err(mismatch("tuple", other)),
+            },
+            OpKind::RecordLift => {
+                let fields = operands.map(value).collect::<Result<_, _>>()?;
+                vec![Slot::Value(Value::Record(fields))]
+            }
+            OpKind::TupleLift => {
+                let fields = operands.map(value).collect::<Result<_, _>>()?;
+                vec![Slot::Value(Value::Tuple(fields))]
+            }
+            OpKind::HandleLower => match value(operand())? {
+                Value::Handle(h) => vec![i32(h)],
+                other => return Err(mismatch("handle", other)),
+            },
+            OpKind::FutureLower => match value(operand())? {
+                Value::Future(h) => vec![i32(h)],
+                other => return Err(mismatch("future", other)),
+            },
+            OpKind::StreamLower => match value(operand())? {
+                Value::Stream(h) => vec![i32(h)],
+                other => return Err(mismatch("stream", other)),
+            },
+            OpKind::HandleLift => vec![Slot::Value(Value::Handle(addr(operand())?))],
+            OpKind::FutureLift => vec![Slot::Value(Value::Future(addr(operand())?))],
+            OpKind::StreamLift => vec![Slot::Value(Value::Stream(addr(operand())?))],
+            OpKind::Remapped => vec![operand()],
+            OpKind::FlagsLower(count) => {
+                let flags = match value(operand())? {
+                    Value::Flags(flags) if flags.len() == *count => flags,
+                    other => return Err(mismatch(&format!("{count} flags"), other)),
+                };
+                let mut words = vec![0u32; op.results.len()];
+                for (i, _) in flags.iter().enumerate().filter(|(_, set)| **set) {
+                    words[i / 32] |= 1 << (i % 32);
+                }
+                words.into_iter().map(i32).collect()
+            }
+            OpKind::FlagsLift(count) => {
+                let words = operands.map(addr).collect::<Result<Vec<_>, _>>()?;
+                let flags = (0..*count)
+                    .map(|i| words[i / 32] & (1 << (i % 32)) != 0)
+                    .collect();
+                vec![Slot::Value(Value::Flags(flags))]
+            }
+
+            OpKind::VariantPayloadName => vec![self.payloads.last().unwrap().clone()],
+            OpKind::VariantLower(kind, blocks) => {
+                let (case, payload) = kind.split(value(operand())?)?;
+                let block = case_block(blocks, case)?;
+                self.payloads
+                    .push(payload.map_or(Slot::Unit, |v| Slot::Value(*v)));
+                let result = self.run_block(block);
+                self.payloads.pop();
+                result?
+            }
+            OpKind::VariantLift(kind, blocks) => {
+                let case = addr(operand())?;
+                let payload = match self.run_block(case_block(blocks, case)?)?.pop() {
+                    Some(payload) => Some(Box::new(value(payload)?)),
+                    None => None,
+                };
+                vec![Slot::Value(kind.join(case, payload))]
+            }
+            OpKind::EnumLower => match value(operand())? {
+                Value::Enum(case) => vec![i32(case)],
+                other => return Err(mismatch("enum", other)),
+            },
+            OpKind::EnumLift(cases) => {
+                let case = addr(operand())?;
+                if case as usize >= *cases {
+                    return Err(Error::InvalidDiscriminant {
+                        discriminant: case,
+                        cases: *cases,
+                    });
+                }
+                vec![Slot::Value(Value::Enum(case))]
+            }
+
+            OpKind::Call => (self.call)(self.memory, operands.collect())?,
+            OpKind::Return => {
+                for a in mem::take(&mut self.temporaries) {
+                    self.memory.free(a.ptr, a.size, a.align)?;
+                }
+                self.ret = Some(operands.collect());
+                Vec::new()
+            }
+            OpKind::ReturnPointer { size, align } => {
+                vec![i32(self.memory.alloc(*size, *align, State::Scratch))]
+            }
+            OpKind::Malloc { size, align } => vec![i32(self.memory.malloc(*size, *align))],
+            OpKind::GuestDeallocate { size, align } => {
+                self.memory.free(addr(operand())?, *size, *align)?;
+                Vec::new()
+            }
+            OpKind::GuestDeallocateString => {
+                let ptr = addr(operand())?;
+                let len = addr(operand())? as usize;
+                self.memory.free(ptr, len, 1)?;
+                Vec::new()
+            }
+            OpKind::GuestDeallocateList { size, align, block } => {
+                let ptr = addr(operand())?;
+                let len = addr(operand())? as usize;
+                for i in 0..len {
+                    self.bases.push(ptr + (i * size) as u32);
+                    let result = self.run_block(block);
+                    self.bases.pop();
+                    result?;
+                }
+                self.memory.free(ptr, len * size, *align)?;
+                Vec::new()
+            }
+            OpKind::GuestDeallocateVariant(blocks) => {
+                let case = addr(operand())?;
+                self.run_block(case_block(blocks, case)?)?;
+                Vec::new()
+            }
+        };
+        Ok(results)
+    }
+
+    fn alloc(&mut self, size: usize, align: usize, realloc: bool) -> u32 {
+        let ptr = self.memory.malloc(size, align);
+        if !realloc && size > 0 {
+            self.temporaries.push(Allocation { ptr, size, align });
+        }
+        ptr
+    }
+
+    fn release(&mut self, ptr: u32, size: usize, align: usize) -> Result<(), Error> {
+        if self.owns_lifts {
+            self.memory.free(ptr, size, align)?;
+        }
+        Ok(())
+    }
+
+    fn load(&self, load: Load, addr: u32) -> Result<CoreValue, Error> {
+        fn bytes<const N: usize>(memory: &Memory, addr: u32) -> Result<[u8; N], Error> {
+            Ok(memory.read(addr, N)?.try_into().unwrap())
+        }
+        let m = &*self.memory;
+        Ok(match load {
+            Load::I32 => CoreValue::I32(i32::from_le_bytes(bytes(m, addr)?)),
+            Load::U8 => CoreValue::I32(u8::from_le_bytes(bytes(m, addr)?).into()),
+            Load::S8 => CoreValue::I32(i8::from_le_bytes(bytes(m, addr)?).into()),
+            Load::U16 => CoreValue::I32(u16::from_le_bytes(bytes(m, addr)?).into()),
+            Load::S16 => CoreValue::I32(i16::from_le_bytes(bytes(m, addr)?).into()),
+            Load::I64 => CoreValue::I64(i64::from_le_bytes(bytes(m, addr)?)),
+            Load::F32 => CoreValue::F32(f32::from_le_bytes(bytes(m, addr)?)),
+            Load::F64 => CoreValue::F64(f64::from_le_bytes(bytes(m, addr)?)),
+        })
+    }
+
+    fn store(&mut self, store: Store, addr: u32, val: CoreValue) -> Result<(), Error> {
+        match (store, val) {
+            (Store::I32, CoreValue::I32(v)) => self.memory.write(addr, &v.to_le_bytes()),
+            (Store::I8, CoreValue::I32(v)) => self.memory.write(addr, &(v as u8).to_le_bytes()),
+            (Store::I16, CoreValue::I32(v)) => self.memory.write(addr, &(v as u16).to_le_bytes()),
+            (Store::I64, CoreValue::I64(v)) => self.memory.write(addr, &v.to_le_bytes()),
+            (Store::F32, CoreValue::F32(v)) => self.memory.write(addr, &v.to_le_bytes()),
+            (Store::F64, CoreValue::F64(v)) => self.memory.write(addr, &v.to_le_bytes()),
+            (store, val) => Err(mismatch(&format!("operand of {store:?} store"), val)),
+        }
+    }
+}
+
+impl VariantKind {
+    fn split(&self, val: Value) -> Result<(u32, Option<Box<Value>>), Error> {
+        Ok(match (self, val) {
+            (VariantKind::Variant, Value::Variant(case, payload)) => (case, payload),
+            (VariantKind::Option, Value::Option(None)) => (0, None),
+            (VariantKind::Option, Value::Option(Some(payload))) => (1, Some(payload)),
+            (VariantKind::Result, Value::Result(Ok(payload))) => (0, payload),
+            (VariantKind::Result, Value::Result(Err(payload))) => (1, payload),
+            (kind, other) => return Err(mismatch(&format!("{kind:?}").to_lowercase(), other)),
+        })
+    }
+
+    fn join(&self, case: u32, payload: Option<Box<Value>>) -> Value {
+        match self {
+            VariantKind::Variant => Value::Variant(case, payload),
+            VariantKind::Option => Value::Option(payload),
+            VariantKind::Result if case == 0 => Value::Result(Ok(payload)),
+            VariantKind::Result => Value::Result(Err(payload)),
+        }
+    }
+}
+
+fn case_block(blocks: &[Block], case: u32) -> Result<&Block, Error> {
+    blocks.get(case as usize).ok_or(Error::InvalidDiscriminant {
+        discriminant: case,
+        cases: blocks.len(),
+    })
+}
+
+/// Returns the loads and stores used for elements of canonical lists.
+fn access(ty: &Type) -> (Load, Store) {
+    match ty {
+        Type::Bool | Type::U8 => (Load::U8, Store::I8),
+        Type::S8 => (Load::S8, Store::I8),
+        Type::U16 => (Load::U16, Store::I16),
+        Type::S16 => (Load::S16, Store::I16),
+        Type::U32 | Type::S32 | Type::Char => (Load::I32, Store::I32),
+        Type::U64 | Type::S64 => (Load::I64, Store::I64),
+        Type::Float32 => (Load::F32, Store::F32),
+        Type::Float64 => (Load::F64, Store::F64),
+        _ => unreachable!("{ty:?} isn't a canonical list element"),
+    }
+}
+
+fn lower(ty: &Type, val: Value) -> Result<CoreValue, Error> {
+    Ok(match (ty, val) {
+        (Type::Bool, Value::Bool(v)) => CoreValue::I32(v.into()),
+        (Type::U8, Value::U8(v)) => CoreValue::I32(v.into()),
+        (Type::S8, Value::S8(v)) => CoreValue::I32(v.into()),
+        (Type::U16, Value::U16(v)) => CoreValue::I32(v.into()),
+        (Type::S16, Value::S16(v)) => CoreValue::I32(v.into()),
+        (Type::U32, Value::U32(v)) => CoreValue::I32(v as i32),
+        (Type::S32, Value::S32(v)) => CoreValue::I32(v),
+        (Type::U64, Value::U64(v)) => CoreValue::I64(v as i64),
+        (Type::S64, Value::S64(v)) => CoreValue::I64(v),
+        (Type::Float32, Value::Float32(v)) => CoreValue::F32(v),
+        (Type::Float64, Value::Float64(v)) => CoreValue::F64(v),
+        (Type::Char, Value::Char(v)) => CoreValue::I32(u32::from(v) as i32),
+        (ty, other) => return Err(mismatch(&format!("{ty:?}").to_lowercase(), other)),
+    })
+}
+
+fn lift(ty: &Type, val: CoreValue) -> Result<Value, Error> {
+    Ok(match (ty, val) {
+        (Type::Bool, CoreValue::I32(v)) => Value::Bool(v != 0),
+        (Type::U8, CoreValue::I32(v)) => Value::U8(v as u8),
+        (Type::S8, CoreValue::I32(v)) => Value::S8(v as i8),
+        (Type::U16, CoreValue::I32(v)) => Value::U16(v as u16),
+        (Type::S16, CoreValue::I32(v)) => Value::S16(v as i16),
+        (Type::U32, CoreValue::I32(v)) => Value::U32(v as u32),
+        (Type::S32, CoreValue::I32(v)) => Value::S32(v),
+        (Type::U64, CoreValue::I64(v)) => Value::U64(v as u64),
+        (Type::S64, CoreValue::I64(v)) => Value::S64(v),
+        (Type::Float32, CoreValue::F32(v)) => Value::Float32(v),
+        (Type::Float64, CoreValue::F64(v)) => Value::Float64(v),
+        (Type::Char, CoreValue::I32(v)) => {
+            Value::Char(char::from_u32(v as u32).ok_or(Error::InvalidChar(v as u32))?)
+        }
+        (ty, other) => return Err(mismatch(&format!("core value for {ty:?}"), other)),
+    })
+}
+
+fn bitcast(cast: Bitcast, val: CoreValue) -> Result<CoreValue, Error> {
+    use CoreValue::*;
+
+    Ok(match (cast, val) {
+        (Bitcast::None, val) => val,
+        (Bitcast::F32ToI32, F32(v)) => I32(v.to_bits() as i32),
+        (Bitcast::F64ToI64, F64(v)) => I64(v.to_bits() as i64),
+        (Bitcast::I32ToI64, I32(v)) => I64(v.into()),
+        (Bitcast::F32ToI64, F32(v)) => I64(v.to_bits().into()),
+        (Bitcast::I32ToF32, I32(v)) => F32(f32::from_bits(v as u32)),
+        (Bitcast::I64ToF64, I64(v)) => F64(f64::from_bits(v as u64)),
+        (Bitcast::I64ToI32, I64(v)) => I32(v as i32),
+        (Bitcast::I64ToF32, I64(v)) => F32(f32::from_bits(v as u32)),
+        (cast, other) => return Err(mismatch(&format!("operand of {cast:?}"), other)),
+    })
+}
+
+fn i32(val: u32) -> Slot {
+    Slot::Core(CoreValue::I32(val as i32))
+}
+
+fn address(slot: Slot, offset: i32) -> Result<u32, Error> {
+    let base = addr(slot)?;
+    base.checked_add(offset as u32)
+        .ok_or(Error::OutOfBounds { addr: base, len: 0 })
+}
+
+fn addr(slot: Slot) -> Result<u32, Error> {
+    match core(slot)? {
+        CoreValue::I32(v) => Ok(v as u32),
+        other => Err(mismatch("i32", other)),
+    }
+}
+
+fn core(slot: Slot) -> Result<CoreValue, Error> {
+    match slot {
+        Slot::Core(v) => Ok(v),
+        other => Err(mismatch("core value", other)),
+    }
+}
+
+fn value(slot: Slot) -> Result<Value, Error> {
+    match slot {
+        Slot::Value(v) => Ok(v),
+        other => Err(mismatch("component value", other)),
+    }
+}
+
+fn list(val: Value) -> Result<Vec<Value>, Error> {
+    match val {
+        Value::List(elems) => Ok(elems),
+        other => Err(mismatch("list", other)),
+    }
+}
+
+fn mismatch(expected: &str, found: impl fmt::Debug) -> Error {
+    Error::TypeMismatch {
+        expected: expected.to_string(),
+        found: format!("{found:?}"),
+    }
+}
+
+fn align_to(val: usize, align: usize) -> usize {
+    (val + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wit_parser::UnresolvedPackage;
+
+    fn point(x: i32, name: &str) -> Value {
+        Value::Record(vec![
+            Value::S32(x),
+            Value::Float64(x as f64 / 2.0),
+            Value::String(name.to_string()),
+        ])
+    }
+
+    fn some(v: Value) -> Option<Box<Value>> {
+        Some(Box::new(v))
+    }
+
+    fn cases() -> Vec<(&'static str, Value)> {
+        let string = |s: &str| Value::String(s.to_string());
+        vec![
+            ("bool", Value::Bool(true)),
+            ("u8", Value::U8(u8::MAX)),
+            ("s8", Value::S8(i8::MIN)),
+            ("u16", Value::U16(u16::MAX)),
+            ("s16", Value::S16(i16::MIN)),
+            ("u32", Value::U32(u32::MAX)),
+            ("s32", Value::S32(i32::MIN)),
+            ("u64", Value::U64(u64::MAX)),
+            ("s64", Value::S64(i64::MIN)),
+            ("float32", Value::Float32(1.5)),
+            ("float64", Value::Float64(-2.25)),
+            ("char", Value::Char('☃')),
+            ("string", string("hello")),
+            ("string", string("")),
+            ("list<u8>", Value::List(vec![Value::U8(1), Value::U8(2)])),
+            ("list<u64>", Value::List(vec![])),
+            ("list<bool>", Value::List(vec![Value::Bool(false)])),
+            ("list<string>", Value::List(vec![string("a"), string("")])),
+            (
+                "list<list<s16>>",
+                Value::List(vec![Value::List(vec![Value::S16(-1)]), Value::List(vec![])]),
+            ),
+            ("point", point(3, "p")),
+            (
+                "list<point>",
+                Value::List(vec![point(1, "a"), point(2, "b")]),
+            ),
+            ("shape", Value::Variant(0, None)),
+            ("shape", Value::Variant(1, some(Value::Float32(0.5)))),
+            (
+                "shape",
+                Value::Variant(2, some(Value::List(vec![point(4, "c")]))),
+            ),
+            (
+                "shape",
+                Value::Variant(3, some(Value::Option(some(string("x"))))),
+            ),
+            ("shape", Value::Variant(4, some(Value::U64(1 << 40)))),
+            ("color", Value::Enum(2)),
+            ("small", Value::Flags(vec![true, false, true])),
+            ("big", Value::Flags((0..40).map(|i| i % 3 == 0).collect())),
+            (
+                "tuple<u8, s16, char>",
+                Value::Tuple(vec![Value::U8(1), Value::S16(-2), Value::Char('x')]),
+            ),
+            ("option<u32>", Value::Option(None)),
+            (
+                "option<option<string>>",
+                Value::Option(some(Value::Option(some(string("y"))))),
+            ),
+            (
+                "result<string, list<u8>>",
+                Value::Result(Ok(some(string("ok")))),
+            ),
+            (
+                "result<string, list<u8>>",
+                Value::Result(Err(some(Value::List(vec![Value::U8(9)])))),
+            ),
+            ("result", Value::Result(Err(None))),
+            ("result<_, color>", Value::Result(Err(some(Value::Enum(1))))),
+            ("own<r>", Value::Handle(3)),
+            ("future<u32>", Value::Future(7)),
+            ("stream<u8>", Value::Stream(9)),
+        ]
+    }
+
+    fn resolve() -> Resolve {
+        let mut src = String::from(
+            "
+                package test:interp;
+
+                interface echo {
+                    resource r;
+                    record point { x: s32, y: float64, name: string }
+                    variant shape {
+                        empty,
+                        circle(float32),
+                        poly(list<point>),
+                        label(option<string>),
+                        big(u64),
+                    }
+                    enum color { red, green, blue }
+                    flags small { a, b, c }
+            ",
+        );
+        let flags = (0..40).map(|i| format!("f{i}")).collect::<Vec<_>>();
+        src.push_str(&format!("flags big {{ {} }}\n", flags.join(", ")));
+        src.push_str(
+            "many: func(a: string, b: string, c: string, d: string, e: string,
+                        f: string, g: string, h: string, i: string) -> list<string>;\n",
+        );
+        for (i, (ty, _)) in cases().iter().enumerate() {
+            src.push_str(&format!("echo{i}: func(x: {ty}) -> {ty};\n"));
+        }
+        src.push('}');
+
+        let mut resolve = Resolve::default();
+        resolve
+            .push(UnresolvedPackage::parse("interp.wit".as_ref(), &src).unwrap())
+            .unwrap();
+        resolve
+    }
+
+    fn func<'a>(resolve: &'a Resolve, name: &str) -> &'a Function {
+        let (_, iface) = resolve.interfaces.iter().next().unwrap();
+        &iface.functions[name]
+    }
+
+    #[test]
+    fn round_trip() {
+        let resolve = resolve();
+        for canonical_lists in [true, false] {
+            let mut interp = Interpreter::new(&resolve);
+            interp.canonical_lists = canonical_lists;
+            for (i, (ty, val)) in cases().into_iter().enumerate() {
+                let func = func(&resolve, &format!("echo{i}"));
+                let mut seen = None;
+                let results = interp
+                    .call_export(func, vec![val.clone()], |args| {
+                        seen = Some(args.clone());
+                        args
+                    })
+                    .unwrap_or_else(|e| panic!("export of {ty}: {e}"));
+                assert_eq!(seen, Some(vec![val.clone()]), "export of {ty}");
+                assert_eq!(results, vec![val.clone()], "export of {ty}");
+
+                let results = interp
+                    .call_import(func, vec![val.clone()], |args| args)
+                    .unwrap_or_else(|e| panic!("import of {ty}: {e}"));
+                assert_eq!(results, vec![val], "import of {ty}");
+                assert_eq!(interp.memory().leaks(), Vec::new(), "{ty}");
+            }
+        }
+    }
+
+    #[test]
+    fn indirect_params() {
+        let resolve = resolve();
+        let func = func(&resolve, "many");
+        let args = (0..9)
+            .map(|i| Value::String(i.to_string()))
+            .collect::<Vec<_>>();
+        let mut interp = Interpreter::new(&resolve);
+        let results = interp
+            .call_export(func, args.clone(), |args| vec![Value::List(args)])
+            .unwrap();
+        assert_eq!(results, vec![Value::List(args.clone())]);
+        let results = interp
+            .call_import(func, args.clone(), |args| vec![Value::List(args)])
+            .unwrap();
+        assert_eq!(results, vec![Value::List(args)]);
+        assert_eq!(interp.memory().leaks(), Vec::new());
+    }
+
+    #[test]
+    fn invalid_results() {
+        let resolve = resolve();
+        let mut interp = Interpreter::new(&resolve);
+        let (i, _) = cases()
+            .iter()
+            .enumerate()
+            .find(|(_, (ty, _))| *ty == "color")
+            .unwrap();
+        let func = func(&resolve, &format!("echo{i}"));
+        assert_eq!(
+            interp.call_export(func, vec![Value::Enum(0)], |_| vec![Value::Enum(3)]),
+            Err(Error::InvalidDiscriminant {
+                discriminant: 3,
+                cases: 3
+            })
+        );
+        assert_eq!(
+            interp.call_import(func, vec![Value::Enum(0)], |_| Vec::new()),
+            Err(Error::ResultCount {
+                expected: 1,
+                found: 0
+            })
+        );
+        assert!(matches!(
+            interp.call_import(func, vec![Value::U8(0)], |args| args),
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn memory_errors() {
+        let mut memory = Memory::new();
+        let a = memory.malloc(8, 4);
+        let b = memory.malloc(3, 1);
+        assert_eq!(
+            memory.leaks(),
+            vec![
+                Allocation {
+                    ptr: a,
+                    size: 8,
+                    align: 4
+                },
+                Allocation {
+                    ptr: b,
+                    size: 3,
+                    align: 1
+                },
+            ]
+        );
+        assert_eq!(
+            memory.free(a, 4, 4),
+            Err(Error::FreeMismatch {
+                allocated: Allocation {
+                    ptr: a,
+                    size: 8,
+                    align: 4
+                },
+                freed: Allocation {
+                    ptr: a,
+                    size: 4,
+                    align: 4
+                },
+            })
+        );
+        assert_eq!(
+            memory.free(a + 4, 4, 4),
+            Err(Error::InvalidFree { ptr: a + 4 })
+        );
+        memory.free(a, 8, 4).unwrap();
+        assert_eq!(memory.free(a, 8, 4), Err(Error::DoubleFree { ptr: a }));
+        assert_eq!(memory.read(a, 4), Err(Error::UseAfterFree { addr: a }));
+        assert_eq!(
+            memory.write(b + 2, &[0, 0]),
+            Err(Error::OutOfBounds {
+                addr: b + 2,
+                len: 2
+            })
+        );
+        assert_eq!(memory.leaks().len(), 1);
+    }
+}
@@ -1,17 +1,21 @@
-use anyhow::Result;
-use std::collections::{btree_map::Entry, BTreeMap, HashMap};
+use anyhow::{bail, Result};
+use std::collections::{btree_map::Entry, BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Write};
 use std::ops::Deref;
 use wit_parser::*;
 
 pub use wit_parser;
 pub mod abi;
+pub mod dump;
+pub mod interp;
+pub mod load;
 mod ns;
 pub use ns::Ns;
 
 #[derive(Default)]
 pub struct Types {
     type_info: HashMap<TypeId, TypeInfo>,
+    foreign: HashSet<TypeId>,
 }
 
 #[derive(Default, Clone, Copy, Debug)]
@@ -45,6 +49,13 @@ pub struct TypeInfo {
 
     /// Whether or not this type (transitively) has an own handle.
     pub has_own_handle: bool,
+
+    /// Whether or not this type (transitively) has a float.
+    pub has_float: bool,
+
+    /// Whether or not this type (transitively) has a type marked with
+    /// `Types::mark_foreign`, whose traits aren't known.
+    pub has_foreign: bool,
 }
 
 impl std::ops::BitOrAssign for TypeInfo {
@@ -56,6 +67,8 @@ impl std::ops::BitOrAssign for TypeInfo {
         self.has_resource |= rhs.has_resource;
         self.has_borrow_handle |= rhs.has_borrow_handle;
         self.has_own_handle |= rhs.has_own_handle;
+        self.has_float |= rhs.has_float;
+        self.has_foreign |= rhs.has_foreign;
     }
 }
 
@@ -66,9 +79,27 @@ impl TypeInfo {
     pub fn is_copy(&self) -> bool {
         !self.has_list && !self.has_resource
     }
+    /// Whether values of this type can be compared for (partial) equality
+    /// and ordering, which handles can't, nor foreign types as far as we
+    /// know.
+    pub fn is_partial_eq(&self) -> bool {
+        !self.has_resource && !self.has_foreign
+    }
+    /// Whether values of this type have a total equality and ordering and
+    /// can be hashed, which additionally rules out floats.
+    pub fn is_eq(&self) -> bool {
+        self.is_partial_eq() && !self.has_float
+    }
 }
 
 impl Types {
+    /// Marks `id` as represented by a type defined outside of the generated
+    /// code, such as a remapped type. This must be done before `analyze`.
+    pub fn mark_foreign(&mut self, id: TypeId) {
+        assert!(self.type_info.is_empty());
+        self.foreign.insert(id);
+    }
+
     pub fn analyze(&mut self, resolve: &Resolve) {
         for (t, _) in resolve.types.iter() {
             self.type_id_info(resolve, t);
@@ -209,6 +240,7 @@ impl Types {
             }
             TypeDefKind::Unknown => unreachable!(),
         }
+        info.has_foreign |= self.foreign.contains(&ty);
         let prev = self.type_info.insert(ty, info);
         assert!(prev.is_none());
         info
@@ -218,6 +250,7 @@ impl Types {
         let mut info = TypeInfo::default();
         match ty {
             Type::String => info.has_list = true,
+            Type::Float32 | Type::Float64 => info.has_float = true,
             Type::Id(id) => return self.type_id_info(resolve, *id),
             _ => {}
         }
@@ -371,6 +404,32 @@ pub fn generated_preamble(src: &mut Source, version: &str) {
     uwriteln!(src, "// Generated by `wit-bindgen` {version}. DO NOT EDIT!")
 }
 
+/// Fails if `world` uses a future or stream, which `wit-component` can't
+/// encode into the component type embedded in bindings yet.
+pub fn check_futures_and_streams(resolve: &Resolve, world: WorldId) -> Result<()> {
+    let mut live = LiveTypes::default();
+    live.add_world(resolve, world);
+    for id in live.iter() {
+        let ty = &resolve.types[id];
+        let kind = match ty.kind {
+            TypeDefKind::Future(_) => "future",
+            TypeDefKind::Stream(_) => "stream",
+            _ => continue,
+        };
+        let world = &resolve.worlds[world].name;
+        match &ty.name {
+            Some(name) => bail!(
+                "world `{world}` uses `{name}`, a {kind}, which can't be embedded in the \
+                 component type yet"
+            ),
+            None => bail!(
+                "world `{world}` uses a {kind}, which can't be embedded in the component type yet"
+            ),
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::Source;
@@ -425,7 +484,7 @@ mod tests {
 pub trait WorldGenerator {
     fn generate(&mut self, resolve: &Resolve, id: WorldId, files: &mut Files) -> Result<()> {
         let world = &resolve.worlds[id];
-        self.preprocess(resolve, id);
+        self.preprocess(resolve, id)?;
 
         fn unwrap_name(key: &WorldKey) -> &str {
             match key {
@@ -477,12 +536,20 @@ pub trait WorldGenerator {
         Ok(())
     }
 
+    /// Returns advice for command line users on how to fix `err`, an error
+    /// previously returned from `generate`.
+    fn cli_hint(&self, err: &anyhow::Error) -> Option<String> {
+        let _ = err;
+        None
+    }
+
     fn finish_imports(&mut self, resolve: &Resolve, world: WorldId, files: &mut Files) {
         let _ = (resolve, world, files);
     }
 
-    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
         let _ = (resolve, world);
+        Ok(())
     }
 
     fn import_interface(
@@ -546,6 +613,27 @@ pub trait InterfaceGenerator<'a> {
     fn type_list(&mut self, id: TypeId, name: &str, ty: &Type, docs: &Docs);
     fn type_builtin(&mut self, id: TypeId, name: &str, ty: &Type, docs: &Docs);
 
+    /// Generates a named `future<T>` type.
+    ///
+    /// By default nothing is generated for futures.
+    fn type_future(&mut self, id: TypeId, name: &str, ty: &Option<Type>, docs: &Docs) {
+        let _ = (id, name, ty, docs);
+    }
+
+    /// Generates a named `stream<T, U>` type.
+    ///
+    /// By default nothing is generated for streams.
+    fn type_stream(&mut self, id: TypeId, name: &str, stream: &Stream, docs: &Docs) {
+        let _ = (id, name, stream, docs);
+    }
+
+    /// Generates a named `own<T>` or `borrow<T>` type.
+    ///
+    /// By default nothing is generated for handles.
+    fn type_handle(&mut self, id: TypeId, name: &str, handle: &Handle, docs: &Docs) {
+        let _ = (id, name, handle, docs);
+    }
+
     fn types(&mut self, iface: InterfaceId) {
         let iface = &self.resolve().interfaces[iface];
         for (name, id) in iface.types.iter() {
@@ -566,9 +654,9 @@ pub trait InterfaceGenerator<'a> {
             TypeDefKind::Result(r) => self.type_result(id, name, r, &ty.docs),
             TypeDefKind::List(t) => self.type_list(id, name, t, &ty.docs),
             TypeDefKind::Type(t) => self.type_alias(id, name, t, &ty.docs),
-            TypeDefKind::Future(_) => todo!("generate for future"),
-            TypeDefKind::Stream(_) => todo!("generate for stream"),
-            TypeDefKind::Handle(_) => todo!("generate for handle"),
+            TypeDefKind::Future(t) => self.type_future(id, name, t, &ty.docs),
+            TypeDefKind::Stream(s) => self.type_stream(id, name, s, &ty.docs),
+            TypeDefKind::Handle(h) => self.type_handle(id, name, h, &ty.docs),
             TypeDefKind::Unknown => unreachable!(),
         }
     }
@@ -0,0 +1,428 @@
+//! Loading of WIT packages from several places into one [`Resolve`].
+//!
+//! Bindings are generated for a world of a main package, which may `use`
+//! packages found in the `deps` directory next to it, in further root
+//! directories, or at paths given explicitly per package. Text packages from
+//! all of these are resolved together, so they can depend on each other
+//! regardless of where each one was found.
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use wit_component::DecodedWasm;
+use wit_parser::{PackageId, PackageName, Resolve, UnresolvedPackage};
+
+/// An error in WIT text, along with the location it points at.
+///
+/// Its `Display` is the error as `wit-parser` renders it, including the
+/// location and a snippet of the WIT.
+#[derive(Debug)]
+pub struct WitError {
+    /// The error without its location and snippet.
+    pub message: String,
+    /// The file, line and column the error points at, counted from one.
+    pub location: (PathBuf, u32, u32),
+    rendered: String,
+}
+
+impl WitError {
+    /// Wraps an error returned by `wit-parser` in a [`WitError`] if it
+    /// points at a location in WIT, and returns it unchanged otherwise.
+    ///
+    /// `wit-parser` doesn't expose the locations of its errors other than
+    /// rendered below their message, in the form `--> file:line:column`, so
+    /// they're recovered from there as soon as the error is returned.
+    pub fn wrap(err: anyhow::Error) -> anyhow::Error {
+        let rendered = err.to_string();
+        let Some((message, rest)) = rendered.rsplit_once("\n     --> ") else {
+            return err;
+        };
+        let location = rest.lines().next().unwrap_or("");
+        let mut parts = location.rsplitn(3, ':');
+        let (Some(column), Some(line), Some(file)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return err;
+        };
+        let (Ok(line), Ok(column)) = (line.parse(), column.parse()) else {
+            return err;
+        };
+        anyhow::Error::new(WitError {
+            message: message.to_string(),
+            location: (PathBuf::from(file), line, column),
+            rendered,
+        })
+    }
+}
+
+impl fmt::Display for WitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rendered)
+    }
+}
+
+impl std::error::Error for WitError {}
+
+/// Collects WIT packages to resolve them together with [`Loader::finish`].
+///
+/// When several sources define the same package the first one is used, so
+/// packages given with [`Loader::push_dep`] before the roots take precedence
+/// over copies vendored in their `deps` directories.
+#[derive(Default)]
+pub struct Loader {
+    /// Packages decoded from wasm binaries, which are already resolved.
+    resolve: Resolve,
+    /// WIT text packages which are yet to be resolved.
+    pending: BTreeMap<PackageName, UnresolvedPackage>,
+    /// The package of the first root.
+    main: Option<PackageName>,
+    /// Every file which was read.
+    files: Vec<PathBuf>,
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    /// Adds the root at `path`, which is either a directory of WIT files
+    /// along with the packages in its `deps` directory, a single WIT file, or
+    /// a wasm binary that is either a component or a binary-encoded WIT
+    /// package.
+    ///
+    /// The package of the first root pushed is the main one.
+    pub fn push_path(&mut self, path: &Path) -> Result<()> {
+        let name = self.load(path)?;
+        self.main.get_or_insert(name);
+        Ok(())
+    }
+
+    /// Adds a root of WIT text, with `path` naming it in errors but not
+    /// recorded as a file which was read.
+    pub fn push_str(&mut self, path: &Path, contents: &str) -> Result<()> {
+        let pkg = UnresolvedPackage::parse(path, contents).map_err(WitError::wrap)?;
+        let name = pkg.name.clone();
+        self.add(pkg);
+        self.main.get_or_insert(name);
+        Ok(())
+    }
+
+    /// Adds the package `name` from `path`, which is loaded like a root but
+    /// must define that package.
+    ///
+    /// `name` may omit the version of the package.
+    pub fn push_dep(&mut self, name: &str, path: &Path) -> Result<()> {
+        let found = self.load(path)?;
+        let unversioned = format!("{}:{}", found.namespace, found.name);
+        if name != found.to_string() && name != unversioned {
+            bail!("expected package `{name}` in {path:?} but found `{found}`");
+        }
+        Ok(())
+    }
+
+    /// Resolves every package added, returning them along with the main
+    /// package and the paths of all files which were read.
+    pub fn finish(mut self) -> Result<(Resolve, PackageId, Vec<PathBuf>)> {
+        let Some(main) = self.main.take() else {
+            bail!("no WIT package to generate bindings for");
+        };
+
+        // Packages are resolved after those they depend on. Dependencies
+        // which aren't pending are either already resolved or missing, which
+        // the `Resolve` reports with the location of the `use`.
+        let mut order = Vec::new();
+        let mut visiting = HashSet::new();
+        for name in self.pending.keys() {
+            visit(name, &self.pending, &mut order, &mut visiting)?;
+        }
+        for name in order {
+            if self.resolve.package_names.contains_key(&name) {
+                continue;
+            }
+            let pkg = self.pending.remove(&name).unwrap();
+            let missing = pkg
+                .foreign_deps
+                .keys()
+                .find(|dep| !self.resolve.package_names.contains_key(*dep))
+                .cloned();
+            let pushed = self.resolve.push(pkg).map_err(WitError::wrap);
+            match missing {
+                Some(dep) => pushed.with_context(|| {
+                    format!("failed to find package `{dep}` in any root or dependency")
+                })?,
+                None => pushed?,
+            };
+        }
+
+        let pkg = self.resolve.package_names[&main];
+        return Ok((self.resolve, pkg, self.files));
+
+        fn visit(
+            name: &PackageName,
+            pending: &BTreeMap<PackageName, UnresolvedPackage>,
+            order: &mut Vec<PackageName>,
+            visiting: &mut HashSet<PackageName>,
+        ) -> Result<()> {
+            if order.contains(name) {
+                return Ok(());
+            }
+            if !visiting.insert(name.clone()) {
+                bail!("package `{name}` depends on itself");
+            }
+            for dep in pending[name].foreign_deps.keys() {
+                if pending.contains_key(dep) {
+                    visit(dep, pending, order, visiting)?;
+                }
+            }
+            visiting.remove(name);
+            order.push(name.clone());
+            Ok(())
+        }
+    }
+
+    /// Loads the package at `path`, returning its name.
+    fn load(&mut self, path: &Path) -> Result<PackageName> {
+        if path.is_dir() {
+            let pkg = UnresolvedPackage::parse_dir(path)
+                .map_err(WitError::wrap)
+                .with_context(|| format!("failed to parse package: {}", path.display()))?;
+            let name = pkg.name.clone();
+            self.add_read(pkg);
+            let deps = path.join("deps");
+            if deps.is_dir() {
+                self.load_deps_dir(&deps).with_context(|| {
+                    format!("failed to parse dependency directory: {}", deps.display())
+                })?;
+            }
+            return Ok(name);
+        }
+
+        let contents =
+            std::fs::read(path).with_context(|| format!("failed to read file {path:?}"))?;
+        // Components and binary-encoded WIT packages are decoded into their
+        // own `Resolve`, while anything else is parsed as WIT text.
+        if contents.starts_with(b"\0asm") {
+            let decoded = wit_component::decode(&contents)
+                .with_context(|| format!("failed to decode {path:?}"))?;
+            let pkg = decoded.package();
+            let resolve = match decoded {
+                DecodedWasm::WitPackage(resolve, _) => resolve,
+                DecodedWasm::Component(resolve, _) => resolve,
+            };
+            let name = resolve.packages[pkg].name.clone();
+            self.resolve.merge(resolve)?;
+            self.files.push(path.to_owned());
+            return Ok(name);
+        }
+        let pkg = UnresolvedPackage::parse_file(path).map_err(WitError::wrap)?;
+        let name = pkg.name.clone();
+        self.add_read(pkg);
+        Ok(name)
+    }
+
+    /// Loads every package in a `deps` directory, which are each a directory
+    /// of their own.
+    fn load_deps_dir(&mut self, path: &Path) -> Result<()> {
+        let mut found = HashSet::new();
+        for dep in path.read_dir().context("failed to read directory")? {
+            let dep = dep.context("failed to read directory iterator")?;
+            let path = dep.path();
+            // Files are ignored to skip things like `.DS_Store`.
+            if path.is_file() {
+                continue;
+            }
+            let pkg = UnresolvedPackage::parse_dir(&path)
+                .map_err(WitError::wrap)
+                .with_context(|| format!("failed to parse package: {}", path.display()))?;
+            if !found.insert(pkg.name.clone()) {
+                bail!("duplicate definitions of package `{}` found", pkg.name);
+            }
+            self.add_read(pkg);
+        }
+        Ok(())
+    }
+
+    /// Adds a package parsed from files, which are recorded.
+    fn add_read(&mut self, pkg: UnresolvedPackage) {
+        self.files.extend(pkg.source_files().map(|p| p.to_owned()));
+        self.add(pkg);
+    }
+
+    fn add(&mut self, pkg: UnresolvedPackage) {
+        if !self.resolve.package_names.contains_key(&pkg.name) {
+            self.pending.entry(pkg.name.clone()).or_insert(pkg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory for a test to write files to, which is removed again.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir()
+                .join(format!("wit-bindgen-load-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const MAIN: &str = "package my:test;\n\nworld w {\n  import my:dep/i;\n}\n";
+
+    /// Returns the names of the functions of interface `i` of `name`.
+    fn functions(resolve: &Resolve, name: &str) -> Vec<String> {
+        let pkg = resolve
+            .package_names
+            .iter()
+            .find(|(n, _)| n.to_string() == name)
+            .map(|(_, id)| *id)
+            .unwrap();
+        let iface = resolve.packages[pkg].interfaces["i"];
+        resolve.interfaces[iface]
+            .functions
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn dep_takes_precedence() {
+        let dir = TempDir::new("precedence");
+        dir.write("wit/test.wit", MAIN);
+        dir.write(
+            "wit/deps/dep/i.wit",
+            "package my:dep;\n\ninterface i {\n  vendored: func();\n}\n",
+        );
+        let dep = dir.write(
+            "dep/i.wit",
+            "package my:dep;\n\ninterface i {\n  given: func();\n}\n",
+        );
+
+        let mut loader = Loader::new();
+        loader.push_dep("my:dep", &dep).unwrap();
+        loader.push_path(&dir.0.join("wit")).unwrap();
+        let (resolve, main, _) = loader.finish().unwrap();
+        assert_eq!(resolve.packages[main].name.to_string(), "my:test");
+        assert_eq!(functions(&resolve, "my:dep"), ["given"]);
+
+        // Without it the copy in `deps` is used.
+        let mut loader = Loader::new();
+        loader.push_path(&dir.0.join("wit")).unwrap();
+        let (resolve, _, files) = loader.finish().unwrap();
+        assert_eq!(functions(&resolve, "my:dep"), ["vendored"]);
+        assert!(files.contains(&dir.0.join("wit/deps/dep/i.wit")));
+    }
+
+    #[test]
+    fn dep_names() {
+        let dir = TempDir::new("names");
+        let dep = dir.write(
+            "dep.wit",
+            "package my:dep@1.0.0;\n\ninterface i {\n  f: func();\n}\n",
+        );
+
+        Loader::new().push_dep("my:dep@1.0.0", &dep).unwrap();
+        Loader::new().push_dep("my:dep", &dep).unwrap();
+
+        let err = Loader::new().push_dep("my:other", &dep).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("expected package `my:other` in {dep:?} but found `my:dep@1.0.0`")
+        );
+        let err = Loader::new().push_dep("my:dep@2.0.0", &dep).unwrap_err();
+        assert!(err.to_string().contains("found `my:dep@1.0.0`"), "{err}");
+    }
+
+    #[test]
+    fn cycle() {
+        let mut loader = Loader::new();
+        loader
+            .push_str(
+                "a.wit".as_ref(),
+                "package my:a;\n\ninterface i {\n  use my:b/i.{t};\n}\n",
+            )
+            .unwrap();
+        loader
+            .push_str(
+                "b.wit".as_ref(),
+                "package my:b;\n\ninterface i {\n  use my:a/i.{t};\n}\n",
+            )
+            .unwrap();
+        let err = loader.finish().err().unwrap();
+        assert_eq!(err.to_string(), "package `my:a` depends on itself");
+    }
+
+    #[test]
+    fn missing() {
+        let mut loader = Loader::new();
+        loader.push_str("test.wit".as_ref(), MAIN).unwrap();
+        let err = loader.finish().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "failed to find package `my:dep` in any root or dependency"
+        );
+
+        let err = Loader::new().finish().err().unwrap();
+        assert_eq!(err.to_string(), "no WIT package to generate bindings for");
+    }
+
+    #[test]
+    fn merge_wasm() {
+        let dir = TempDir::new("wasm");
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    "dep.wit".as_ref(),
+                    "package my:dep;\n\ninterface i {\n  encoded: func();\n}\n",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let wasm = dir.0.join("dep.wasm");
+        std::fs::write(&wasm, wit_component::encode(None, &resolve, pkg).unwrap()).unwrap();
+        let wit = dir.write("test.wit", MAIN);
+
+        // A text package may use a decoded one whichever is pushed first,
+        // and the decoded one takes precedence over text copies.
+        for dep_first in [false, true] {
+            let mut loader = Loader::new();
+            if dep_first {
+                loader.push_dep("my:dep", &wasm).unwrap();
+                loader.push_path(&wit).unwrap();
+                loader
+                    .push_str(
+                        "copy.wit".as_ref(),
+                        "package my:dep;\n\ninterface i {\n  copy: func();\n}\n",
+                    )
+                    .unwrap();
+            } else {
+                loader.push_path(&wit).unwrap();
+                loader.push_path(&wasm).unwrap();
+            }
+            let (resolve, main, files) = loader.finish().unwrap();
+            assert_eq!(resolve.packages[main].name.to_string(), "my:test");
+            assert_eq!(functions(&resolve, "my:dep"), ["encoded"]);
+            assert!(files.contains(&wasm));
+            assert!(files.contains(&wit));
+        }
+    }
+}
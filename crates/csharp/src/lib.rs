@@ -124,10 +124,12 @@ impl CSharp {
 }
 
 impl WorldGenerator for CSharp {
-    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
+        wit_bindgen_core::check_futures_and_streams(resolve, world)?;
         let name = &resolve.worlds[world].name;
         self.name = name.to_string();
         self.sizes.fill(resolve);
+        Ok(())
     }
 
     fn import_interface(
@@ -1236,8 +1238,9 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
             TypeDefKind::Result(r) => self.type_result(id, name, r, &ty.docs),
             TypeDefKind::List(t) => self.type_list(id, name, t, &ty.docs),
             TypeDefKind::Type(t) => self.type_alias(id, name, t, &ty.docs),
-            TypeDefKind::Future(_) => todo!("generate for future"),
-            TypeDefKind::Stream(_) => todo!("generate for stream"),
+            TypeDefKind::Future(_) | TypeDefKind::Stream(_) => {
+                unreachable!("rejected by check_futures_and_streams")
+            }
             TypeDefKind::Resource => todo!("generate for resource"),
             TypeDefKind::Handle(_) => todo!("generate for handle"),
             TypeDefKind::Unknown => unreachable!(),
@@ -1568,6 +1571,16 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 name: _,
                 ty: _dir,
             } => todo!("HandleLeft"),
+            Instruction::FutureLower { .. }
+            | Instruction::FutureLift { .. }
+            | Instruction::StreamLower { .. }
+            | Instruction::StreamLift { .. } => {
+                unreachable!("rejected by check_futures_and_streams")
+            }
+
+            Instruction::LowerRemapped { .. } | Instruction::LiftRemapped { .. } => {
+                unreachable!("no types are remapped")
+            }
         }
     }
 
@@ -14,6 +14,7 @@ macro_rules! codegen_test {
                     if [
                         "conventions",
                         "flags",
+                        "future-stream-alias",
                         "guest-name",
                         "import-and-export-resource",
                         "import-and-export-resource-alias",
@@ -37,6 +38,7 @@ macro_rules! codegen_test {
                         "resource-alias",
                         "resource-borrow-in-record",
                         "resource-borrow-in-record-export",
+                        "resource-handle-alias",
                         "resource-local-alias",
                         "resource-local-alias-borrow",
                         "resource-local-alias-borrow-import",
@@ -115,10 +115,12 @@ impl TinyGo {
 }
 
 impl WorldGenerator for TinyGo {
-    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
+        wit_bindgen_core::check_futures_and_streams(resolve, world)?;
         let name = &resolve.worlds[world].name;
         self.world = name.to_string();
         self.sizes.fill(resolve);
+        Ok(())
     }
 
     fn import_interface(
@@ -0,0 +1,131 @@
+//! Runtime support for the `alloc_stats` feature of this crate.
+//!
+//! With the feature enabled this crate counts the bytes allocated by
+//! `cabi_realloc` for arguments given to exports and for lists they lower
+//! into their results, and the bytes freed again by generated bindings, such
+//! as in `cabi_post_*` functions. Generated bindings report the counts of
+//! each export call to the hook installed with [`set_hook`] once the call is
+//! over, and [`totals`] returns the counts since the component started.
+//!
+//! A call's counts include the arguments allocated before the export was
+//! entered, since they cover everything from the end of the previous call.
+//! Arguments lifted into owned lists and strings belong to the export from
+//! then on, so they're not counted as freed when those are dropped.
+//! Calls of async exports may overlap, in which case their counts are mixed.
+//! With the `arena` feature everything allocated in the arena is counted as
+//! freed when it's reset once the call is over.
+//!
+//! Without the feature nothing is counted, the hook is never called, and
+//! [`totals`] is always zero.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Whether the `alloc_stats` feature is enabled.
+pub const ENABLED: bool = cfg!(feature = "alloc_stats");
+
+/// Bytes allocated and freed through the canonical ABI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocated: usize,
+    pub freed: usize,
+}
+
+/// The counts of a single export call, as given to the hook.
+#[derive(Debug, Clone, Copy)]
+pub struct CallStats<'a> {
+    /// The name of the interface, or `None` for a function of the world.
+    pub interface: Option<&'a str>,
+    /// The name of the function in WIT, such as `[method]file.read`.
+    pub function: &'a str,
+    /// Everything counted since the previous export call ended.
+    pub stats: AllocStats,
+}
+
+/// The hook installed with [`set_hook`], or null.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Installs `hook` to be called with the counts of every export call.
+pub fn set_hook(hook: fn(&CallStats<'_>)) {
+    HOOK.store(hook as *mut (), Ordering::Relaxed);
+}
+
+/// Returns the bytes allocated and freed since the component started.
+pub fn totals() -> AllocStats {
+    #[cfg(feature = "alloc_stats")]
+    {
+        imp::COUNTERS.with(|counters| counters.total)
+    }
+    #[cfg(not(feature = "alloc_stats"))]
+    {
+        AllocStats::default()
+    }
+}
+
+/// Counts `size` bytes allocated through the canonical ABI.
+#[inline]
+pub(crate) fn record_alloc(size: usize) {
+    #[cfg(feature = "alloc_stats")]
+    imp::COUNTERS.with(|counters| {
+        counters.call.allocated += size;
+        counters.total.allocated += size;
+    });
+    let _ = size;
+}
+
+/// Counts `size` bytes freed through the canonical ABI.
+#[inline]
+pub(crate) fn record_free(size: usize) {
+    #[cfg(feature = "alloc_stats")]
+    imp::COUNTERS.with(|counters| {
+        counters.call.freed += size;
+        counters.total.freed += size;
+    });
+    let _ = size;
+}
+
+/// Reports the counts of the export call which is over to the hook, and
+/// starts counting the next one.
+///
+/// Counting isn't restarted when an export is entered, so that a call's
+/// counts include the `cabi_realloc`s of the host for its arguments, along
+/// with anything else counted since the previous call ended.
+#[doc(hidden)]
+#[inline]
+pub fn end_call(interface: Option<&str>, function: &str) {
+    #[cfg(feature = "alloc_stats")]
+    {
+        let stats = imp::COUNTERS.with(|counters| core::mem::take(&mut counters.call));
+        let hook = HOOK.load(Ordering::Relaxed);
+        if hook.is_null() {
+            return;
+        }
+        let hook = unsafe { core::mem::transmute::<*mut (), fn(&CallStats<'_>)>(hook) };
+        hook(&CallStats {
+            interface,
+            function,
+            stats,
+        });
+    }
+    let _ = (interface, function);
+}
+
+#[cfg(feature = "alloc_stats")]
+mod imp {
+    use super::AllocStats;
+
+    const ZERO: AllocStats = AllocStats {
+        allocated: 0,
+        freed: 0,
+    };
+
+    pub struct Counters {
+        /// The counts since the last call ended.
+        pub call: AllocStats,
+        pub total: AllocStats,
+    }
+
+    pub static COUNTERS: crate::State<Counters> = crate::State::new(|| Counters {
+        call: ZERO,
+        total: ZERO,
+    });
+}
@@ -0,0 +1,266 @@
+//! Runtime support for the `arena` feature of this crate.
+//!
+//! With the feature enabled `cabi_realloc` serves allocations from a bump
+//! arena rather than the global allocator. Bindings generated with the
+//! `arena` option lower the results of exports into the same arena, and
+//! nothing in it is freed individually: they call [`reset`] once an export
+//! call is over, either from its `cabi_post_*` function or, if it has none,
+//! when it returns.
+//!
+//! Those bindings pass strings and lists of canonical values to exports as
+//! borrows of the arena, and copy other owned lists and strings out of it
+//! with [`list_lift`], since memory in the arena can't be handed to a `Vec`
+//! or `String`. Bindings generated without the option would free memory in
+//! the arena one value at a time, so both must be used together, which the
+//! bindings check with [`ENABLED`] when they're compiled.
+//!
+//! Only the canonical ABI should allocate with `cabi_realloc` then, since
+//! anything else it allocates is freed by the next reset too. Adapters such
+//! as the WASI preview1 adapter keep their allocations, so they're given
+//! `cabi_realloc_adapter` instead, which uses the global allocator.
+
+#[cfg(feature = "arena")]
+use crate::alloc::{alloc::Layout, vec::Vec};
+
+/// Whether the `arena` feature is enabled.
+pub const ENABLED: bool = cfg!(feature = "arena");
+
+/// Frees everything allocated in the arena since the last reset.
+///
+/// This is skipped while async exports are suspended, since their pending
+/// imports may still be given results in the arena.
+///
+/// # Safety
+///
+/// Nothing may refer to memory in the arena after this is called.
+#[cfg(feature = "arena")]
+pub unsafe fn reset() {
+    if crate::async_support::pending_tasks() == 0 {
+        imp::reset();
+    }
+}
+
+/// Allocates the memory for a list which is lowered to be returned from an
+/// export, which lives until the arena is reset.
+#[cfg(feature = "arena")]
+pub unsafe fn list_alloc(layout: Layout) -> *mut u8 {
+    crate::alloc_stats::record_alloc(layout.size());
+    imp::alloc(layout)
+}
+
+/// Lowers a list of canonical values into the arena to be returned from an
+/// export, returning its pointer and length.
+#[cfg(feature = "arena")]
+pub fn list_lower<T>(mut list: Vec<T>) -> (i32, i32) {
+    let len = list.len();
+    crate::alloc_stats::record_alloc(len * core::mem::size_of::<T>());
+    unsafe {
+        let ptr = imp::alloc(Layout::array::<T>(len).unwrap()) as *mut T;
+        core::ptr::copy_nonoverlapping(list.as_ptr(), ptr, len);
+        list.set_len(0);
+        (ptr as i32, len as i32)
+    }
+}
+
+/// Copies a list of canonical values given to this component with
+/// `cabi_realloc` out of the arena.
+#[cfg(feature = "arena")]
+pub unsafe fn list_lift<T>(ptr: i32, len: usize) -> Vec<T> {
+    let mut list = Vec::with_capacity(len);
+    core::ptr::copy_nonoverlapping(ptr as *const T, list.as_mut_ptr(), len);
+    list.set_len(len);
+    list
+}
+
+#[cfg(feature = "arena")]
+pub(crate) use imp::realloc;
+
+#[cfg(feature = "arena")]
+mod imp {
+    use crate::alloc::alloc::{self as global, Layout};
+    use crate::alloc::vec::Vec;
+    use core::ptr;
+
+    /// The size of the first chunk; later chunks double in size.
+    pub const CHUNK_SIZE: usize = 64 * 1024;
+
+    /// Alignment of every chunk, which covers all canonical ABI types.
+    pub const CHUNK_ALIGN: usize = 8;
+
+    pub struct Arena {
+        /// Every chunk allocated since the last reset, the current one last.
+        pub chunks: Vec<(*mut u8, usize)>,
+        /// The next free byte in the current chunk.
+        next: usize,
+        /// The end of the current chunk.
+        end: usize,
+        /// The bytes allocated since the last reset, which counts them as
+        /// freed.
+        used: usize,
+    }
+
+    /// Only generated bindings and `cabi_realloc` use the arena.
+    pub static ARENA: crate::State<Arena> = crate::State::new(|| Arena {
+        chunks: Vec::new(),
+        next: 0,
+        end: 0,
+        used: 0,
+    });
+
+    impl Drop for Arena {
+        fn drop(&mut self) {
+            for &(chunk, size) in self.chunks.iter() {
+                unsafe {
+                    global::dealloc(chunk, Layout::from_size_align_unchecked(size, CHUNK_ALIGN));
+                }
+            }
+        }
+    }
+
+    pub unsafe fn alloc(layout: Layout) -> *mut u8 {
+        ARENA.with(|arena| {
+            let align = layout.align();
+            let mut start = (arena.next + align - 1) & !(align - 1);
+            if arena.chunks.is_empty() || start + layout.size() > arena.end {
+                let prev = arena.chunks.last().map_or(0, |&(_, size)| size);
+                let size = (prev * 2)
+                    .max(CHUNK_SIZE)
+                    .max(layout.size() + layout.align());
+                let chunk_layout = Layout::from_size_align_unchecked(size, CHUNK_ALIGN);
+                let chunk = global::alloc(chunk_layout);
+                if chunk.is_null() {
+                    global::handle_alloc_error(chunk_layout);
+                }
+                arena.chunks.push((chunk, size));
+                arena.end = chunk as usize + size;
+                start = (chunk as usize + align - 1) & !(align - 1);
+            }
+            arena.next = start + layout.size();
+            arena.used += layout.size();
+            start as *mut u8
+        })
+    }
+
+    pub unsafe fn realloc(
+        old_ptr: *mut u8,
+        old_len: usize,
+        align: usize,
+        new_len: usize,
+    ) -> *mut u8 {
+        if new_len == 0 {
+            return align as *mut u8;
+        }
+        let ptr = alloc(Layout::from_size_align_unchecked(new_len, align));
+        if old_len != 0 {
+            ptr::copy_nonoverlapping(old_ptr, ptr, old_len.min(new_len));
+        }
+        ptr
+    }
+
+    pub unsafe fn reset() {
+        ARENA.with(|arena| {
+            crate::alloc_stats::record_free(core::mem::take(&mut arena.used));
+            // The last chunk is the largest, so keep it around for the next
+            // call and free the rest.
+            let last = arena.chunks.pop();
+            for (chunk, size) in arena.chunks.drain(..) {
+                global::dealloc(chunk, Layout::from_size_align_unchecked(size, CHUNK_ALIGN));
+            }
+            if let Some((chunk, size)) = last {
+                arena.chunks.push((chunk, size));
+                arena.next = chunk as usize;
+                arena.end = chunk as usize + size;
+            }
+        })
+    }
+}
+
+#[cfg(all(test, feature = "arena"))]
+mod tests {
+    use super::imp::{alloc, realloc, reset, ARENA, CHUNK_ALIGN, CHUNK_SIZE};
+    use crate::alloc::alloc::Layout;
+    use crate::alloc::vec::Vec;
+
+    fn chunk_sizes() -> Vec<usize> {
+        ARENA.with(|arena| arena.chunks.iter().map(|&(_, size)| size).collect())
+    }
+
+    unsafe fn alloc_bytes(size: usize, align: usize) -> *mut u8 {
+        let ptr = alloc(Layout::from_size_align(size, align).unwrap());
+        // Writing all of it makes sure it's within a chunk.
+        ptr.write_bytes(0xab, size);
+        ptr
+    }
+
+    #[test]
+    fn alloc_aligns() {
+        unsafe {
+            let a = alloc_bytes(1, 1);
+            let b = alloc_bytes(8, 8);
+            assert_eq!(b as usize % 8, 0);
+            assert!(b as usize > a as usize);
+            // Alignments above that of chunks are aligned within the chunk.
+            let c = alloc_bytes(3, 4 * CHUNK_ALIGN);
+            assert_eq!(c as usize % (4 * CHUNK_ALIGN), 0);
+            assert_eq!(chunk_sizes(), [CHUNK_SIZE]);
+            reset();
+        }
+    }
+
+    #[test]
+    fn chunks_grow() {
+        unsafe {
+            let a = alloc_bytes(CHUNK_SIZE / 2, 1);
+            let b = alloc_bytes(CHUNK_SIZE / 2, 1);
+            assert_eq!(b as usize, a as usize + CHUNK_SIZE / 2);
+            assert_eq!(chunk_sizes(), [CHUNK_SIZE]);
+
+            // A full chunk is followed by one twice its size.
+            alloc_bytes(1, 1);
+            assert_eq!(chunk_sizes(), [CHUNK_SIZE, 2 * CHUNK_SIZE]);
+
+            // An allocation larger than that gets a chunk of its own which
+            // fits it at any alignment.
+            let big = 8 * CHUNK_SIZE;
+            let c = alloc_bytes(big, 64);
+            assert_eq!(c as usize % 64, 0);
+            assert_eq!(chunk_sizes(), [CHUNK_SIZE, 2 * CHUNK_SIZE, big + 64]);
+            reset();
+        }
+    }
+
+    #[test]
+    fn realloc_copies() {
+        unsafe {
+            assert_eq!(realloc(core::ptr::null_mut(), 0, 4, 0), 4 as *mut u8);
+            let a = realloc(core::ptr::null_mut(), 0, 4, 4);
+            a.copy_from_nonoverlapping([1, 2, 3, 4].as_ptr(), 4);
+            let b = realloc(a, 4, 4, 8);
+            assert_ne!(a, b);
+            assert_eq!(b as usize % 4, 0);
+            assert_eq!(*b.cast::<[u8; 4]>(), [1, 2, 3, 4]);
+            let c = realloc(b, 8, 4, 2);
+            assert_eq!(*c.cast::<[u8; 2]>(), [1, 2]);
+            reset();
+        }
+    }
+
+    #[test]
+    fn reset_keeps_last_chunk() {
+        unsafe {
+            let first = alloc_bytes(16, 8);
+            alloc_bytes(CHUNK_SIZE, 1);
+            assert_eq!(chunk_sizes(), [CHUNK_SIZE, 2 * CHUNK_SIZE]);
+            reset();
+            assert_eq!(chunk_sizes(), [2 * CHUNK_SIZE]);
+
+            // Allocation starts over at the beginning of the kept chunk.
+            let chunk = ARENA.with(|arena| arena.chunks[0].0);
+            let a = alloc_bytes(16, 8);
+            assert_eq!(a, chunk);
+            assert_ne!(a, first);
+            reset();
+            assert_eq!(chunk_sizes(), [2 * CHUNK_SIZE]);
+        }
+    }
+}
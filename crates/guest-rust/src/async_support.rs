@@ -0,0 +1,268 @@
+//! Runtime support for the `unstable_async` mode of the Rust bindings
+//! generator.
+//!
+//! This implements a small single-threaded executor which is driven entirely
+//! by the component model's async callbacks:
+//!
+//! * An async export is started with [`first_poll`]. If the future it creates
+//!   completes immediately then `0` is returned to the host, otherwise the
+//!   task is stored in a table and a nonzero context, one more than its index
+//!   there, is handed back to the host.
+//! * The host then invokes the `[callback]` export of the function with that
+//!   context whenever an event is ready, which lands in [`callback`]. Events
+//!   are recorded and the task is polled again.
+//! * Async imports return a status which is either `0`, meaning the call has
+//!   already completed, or the handle of a subtask. [`await_result`] turns
+//!   that status into a future which is resolved once the host delivers a
+//!   completion event for the subtask.
+//!
+//! This is an early draft of the `callback` form of async lifts and of the
+//! async lowering of imports in the component model's async proposal,
+//! `design/mvp/Async.md` in the WebAssembly/component-model repository, and
+//! not any revision of it which a runtime implements. Exactly, it is:
+//!
+//! * An async export `f` is exported as `[async]f` with the core signature of
+//!   its parameters and an `i32` result, which is `0` once it has finished or
+//!   its context otherwise. Its results are passed to `[task-return]f`,
+//!   imported from `[export]` followed by the name of the export's interface
+//!   or `$root`, after which post-return runs within the export.
+//! * `[callback]f` has the signature `(ctx, event, handle, status) -> i32`
+//!   and returns `0` once the task has finished and `1` otherwise.
+//! * An async import `f` is imported as `[async]f`, taking a pointer for its
+//!   result after its parameters if it has one, and returning `0` or the
+//!   handle of a subtask.
+//! * The only event acted on is [`EVENT_CALL_DONE`], for a finished subtask.
+//!
+//! As the proposal changes this will too, so the codes are all defined below
+//! to be updated together.
+//!
+//! Waking is implicit: a task is re-polled on every event delivered for it.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// The event code delivered to [`callback`] when an async import finishes,
+/// `CALL_DONE` in the proposal. The `handle` of the event is the subtask.
+pub const EVENT_CALL_DONE: i32 = 1;
+
+/// Value returned to the host by [`first_poll`] and [`callback`] when the
+/// task has finished and no further callbacks are needed. The proposal has
+/// the task call `[task-return]` before this, which generated bindings do.
+const TASK_DONE: i32 = 0;
+
+/// Value returned to the host by [`callback`] when the task is still waiting
+/// on one or more events, which the proposal delivers as further callbacks.
+const TASK_WAITING: i32 = 1;
+
+struct State {
+    /// Subtasks which the host has reported as finished but which have not
+    /// yet been observed by the future awaiting them.
+    ///
+    /// A subtask's `CALL_DONE` event is delivered to the callback of the
+    /// task which started it, which may be before the future awaiting the
+    /// subtask is polled again, so the handle is recorded here until then.
+    /// Async imports which complete synchronously return `0` rather than a
+    /// handle, and the host doesn't reuse a handle until its event has been
+    /// delivered, so each handle here belongs to exactly one future.
+    completed: BTreeSet<i32>,
+    /// Tasks which have been handed to the host, at one less than their
+    /// context. A slot is empty once its task has finished, and while the
+    /// task is being polled.
+    tasks: Vec<Option<Box<Task>>>,
+    /// The number of tasks which have been handed to the host and haven't
+    /// finished yet.
+    pending: usize,
+}
+
+// Tasks are never polled within `STATE.with`.
+static STATE: crate::State<State> = crate::State::new(|| State {
+    completed: BTreeSet::new(),
+    tasks: Vec::new(),
+    pending: 0,
+});
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+struct Task {
+    future: BoxFuture,
+}
+
+/// Starts executing `future` on behalf of an async export.
+///
+/// The future is polled once. If it's ready then `0` is returned and the
+/// export is complete. Otherwise a nonzero context value is returned which
+/// the host passes back to [`callback`] when events arrive.
+///
+/// # Safety
+///
+/// This must only be called from generated export shims.
+pub unsafe fn first_poll<T>(future: T) -> i32
+where
+    T: Future<Output = ()> + 'static,
+{
+    let mut task = Box::new(Task {
+        future: Box::pin(future),
+    });
+    if poll(&mut task) {
+        return TASK_DONE;
+    }
+    STATE.with(|state| {
+        state.pending += 1;
+        let index = match state.tasks.iter().position(|t| t.is_none()) {
+            Some(index) => index,
+            None => {
+                state.tasks.push(None);
+                state.tasks.len() - 1
+            }
+        };
+        state.tasks[index] = Some(task);
+        index as i32 + 1
+    })
+}
+
+/// Delivers an event from the host to the task identified by `ctx`.
+///
+/// Returns `0` once the task has completed, at which point its context is
+/// freed, and `1` if the task is still waiting on further events.
+///
+/// # Safety
+///
+/// `ctx` must be a value previously returned by [`first_poll`] for a task
+/// which has not yet completed.
+pub unsafe fn callback(ctx: i32, event: i32, handle: i32, _status: i32) -> i32 {
+    let index = ctx as usize - 1;
+    let mut task = STATE.with(|state| {
+        if event == EVENT_CALL_DONE {
+            state.completed.insert(handle);
+        }
+        state.tasks[index]
+            .take()
+            .expect("callback for a task which isn't pending")
+    });
+    // The task is polled outside of `with` since it may await imports, and
+    // its slot stays empty meanwhile so it's not reused.
+    if poll(&mut task) {
+        STATE.with(|state| state.pending -= 1);
+        TASK_DONE
+    } else {
+        STATE.with(|state| state.tasks[index] = Some(task));
+        TASK_WAITING
+    }
+}
+
+/// Returns the number of async exports which are suspended.
+pub fn pending_tasks() -> usize {
+    STATE.with(|state| state.pending)
+}
+
+/// Returns a future which resolves once the async import call which
+/// returned `status` has completed.
+///
+/// A `status` of `0` indicates that the call completed synchronously and the
+/// returned future is immediately ready.
+pub fn await_result(status: i32) -> impl Future<Output = ()> {
+    Subtask { handle: status }
+}
+
+struct Subtask {
+    handle: i32,
+}
+
+impl Future for Subtask {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.handle == 0 {
+            return Poll::Ready(());
+        }
+        if STATE.with(|state| state.completed.remove(&self.handle)) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+fn poll(task: &mut Task) -> bool {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    task.future.as_mut().poll(&mut cx).is_ready()
+}
+
+/// Tasks are re-polled on every event the host delivers for them, so there's
+/// nothing for a waker to do.
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    // Test that a task waiting on two subtasks stays pending until both
+    // have completed, whichever order their events arrive in.
+    #[test]
+    fn pending_to_done() {
+        let finished = Rc::new(Cell::new(false));
+        let ctx = unsafe {
+            first_poll({
+                let finished = finished.clone();
+                async move {
+                    await_result(0).await;
+                    await_result(5).await;
+                    await_result(6).await;
+                    finished.set(true);
+                }
+            })
+        };
+        assert_ne!(ctx, TASK_DONE);
+        assert_eq!(pending_tasks(), 1);
+
+        unsafe {
+            // Unrelated events and the later subtask don't wake the task.
+            assert_eq!(callback(ctx, 0, 0, 0), TASK_WAITING);
+            assert_eq!(callback(ctx, EVENT_CALL_DONE, 6, 0), TASK_WAITING);
+            assert!(!finished.get());
+            assert_eq!(callback(ctx, EVENT_CALL_DONE, 5, 0), TASK_DONE);
+        }
+        assert!(finished.get());
+        assert_eq!(pending_tasks(), 0);
+        assert!(STATE.with(|state| state.completed.is_empty()));
+    }
+
+    #[test]
+    fn ready_immediately() {
+        let ctx = unsafe { first_poll(async { await_result(0).await }) };
+        assert_eq!(ctx, TASK_DONE);
+        assert_eq!(pending_tasks(), 0);
+    }
+
+    #[test]
+    fn contexts_are_distinct() {
+        unsafe {
+            let first = first_poll(await_result(1));
+            let second = first_poll(await_result(2));
+            assert_ne!(first, second);
+            assert_eq!(pending_tasks(), 2);
+            assert_eq!(callback(second, EVENT_CALL_DONE, 2, 0), TASK_DONE);
+            let third = first_poll(await_result(3));
+            assert_ne!(first, third);
+            assert_eq!(callback(first, EVENT_CALL_DONE, 1, 0), TASK_DONE);
+            assert_eq!(callback(third, EVENT_CALL_DONE, 3, 0), TASK_DONE);
+        }
+        assert_eq!(pending_tasks(), 0);
+    }
+}
@@ -0,0 +1,83 @@
+//! Lifting of values from the host which are always validated.
+//!
+//! Bindings generated with the `validate_lifts` option call these directly.
+//! Otherwise the lifting functions in `rt` call them in debug builds and with
+//! the `validate_lifts` feature, and assume values are valid in release
+//! builds. Unknown bits of flags are only rejected with the option or the
+//! feature, while unknown discriminants of variants are always rejected.
+//!
+//! An invalid value traps, or calls the hook installed with
+//! [`set_invalid_lift_hook`].
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Whether the `validate_lifts` feature is enabled, which also validates the
+/// bits of flags lifted by bindings generated without the option.
+pub const ENABLED: bool = cfg!(feature = "validate_lifts");
+
+/// The hook installed with [`set_invalid_lift_hook`], or null.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Installs `hook` to be called with a description of the value whenever an
+/// invalid value is lifted from the host, e.g. to panic with a message.
+///
+/// Without a hook debug builds panic and release builds trap.
+pub fn set_invalid_lift_hook(hook: fn(&str) -> !) {
+    HOOK.store(hook as *mut (), Ordering::Relaxed);
+}
+
+/// Reports that an invalid `what` was lifted from the host.
+#[cold]
+pub fn invalid_lift(what: &str) -> ! {
+    let hook = HOOK.load(Ordering::Relaxed);
+    if !hook.is_null() {
+        let hook = unsafe { core::mem::transmute::<*mut (), fn(&str) -> !>(hook) };
+        hook(what);
+    }
+    if cfg!(debug_assertions) {
+        panic!("invalid {what} lifted from the host");
+    }
+    #[cfg(target_arch = "wasm32")]
+    core::arch::wasm32::unreachable();
+    #[cfg(not(target_arch = "wasm32"))]
+    panic!("invalid {what} lifted from the host");
+}
+
+pub fn string_lift(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => invalid_lift("UTF-8 string"),
+    }
+}
+
+pub fn str_lift(bytes: &[u8]) -> &str {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => invalid_lift("UTF-8 string"),
+    }
+}
+
+pub fn char_lift(val: u32) -> char {
+    match char::from_u32(val) {
+        Some(c) => c,
+        None => invalid_lift("char"),
+    }
+}
+
+pub fn bool_lift(val: u8) -> bool {
+    match val {
+        0 => false,
+        1 => true,
+        _ => invalid_lift("bool"),
+    }
+}
+
+pub fn invalid_enum_discriminant<T>() -> T {
+    invalid_lift("discriminant")
+}
+
+pub fn invalid_flags<T>() -> T {
+    invalid_lift("flags")
+}
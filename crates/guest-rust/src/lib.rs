@@ -1,8 +1,11 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(any(feature = "mock", test))]
+extern crate std;
 
 use alloc::boxed::Box;
+use alloc::string::String;
 use core::fmt;
 use core::marker;
 use core::mem::ManuallyDrop;
@@ -15,12 +18,45 @@ pub use wit_bindgen_rust_macro::*;
 #[doc(hidden)]
 pub use bitflags;
 
+pub mod alloc_stats;
+
+#[doc(hidden)]
+pub mod arena;
+
+#[doc(hidden)]
+pub mod async_support;
+
+#[doc(hidden)]
+pub mod checked;
+pub use checked::set_invalid_lift_hook;
+
+#[doc(hidden)]
+#[cfg(feature = "mock")]
+pub mod mock;
+
+pub mod rep;
+pub mod trace;
+
+#[doc(hidden)]
+pub mod tracking;
+
 #[doc(hidden)]
 pub mod rt {
     use crate::alloc::string::String;
     use crate::alloc::vec::Vec;
 
-    pub use crate::{Resource, RustResource, WasmResource};
+    pub use crate::alloc_stats;
+    pub use crate::arena;
+    pub use crate::async_support;
+    pub use crate::checked;
+    #[cfg(feature = "mock")]
+    pub use crate::mock;
+    pub use crate::rep;
+    pub use crate::trace;
+    pub use crate::tracking::{self, live_resources, LiveResources};
+    pub use crate::{Borrow, Own};
+    pub use crate::{FutureHandle, Resource, RustResource, StreamHandle, WasmResource};
+    pub use crate::{InvalidValue, UnknownName};
 
     /// Provide a hook for generated export functions to run static
     /// constructors at most once. wit-bindgen-rust generates a call to this
@@ -56,7 +92,7 @@ pub mod rt {
     // Re-export things from liballoc for convenient use.
     pub use super::alloc::{alloc, boxed, string, vec};
 
-    #[cfg(feature = "realloc")]
+    #[cfg(feature = "arena")]
     #[no_mangle]
     unsafe extern "C" fn cabi_realloc(
         old_ptr: *mut u8,
@@ -64,6 +100,46 @@ pub mod rt {
         align: usize,
         new_len: usize,
     ) -> *mut u8 {
+        // The old allocation stays in the arena, and is counted as freed
+        // once that's reset.
+        crate::alloc_stats::record_alloc(new_len);
+        crate::arena::realloc(old_ptr, old_len, align, new_len)
+    }
+
+    #[cfg(all(feature = "realloc", not(feature = "arena")))]
+    #[no_mangle]
+    unsafe extern "C" fn cabi_realloc(
+        old_ptr: *mut u8,
+        old_len: usize,
+        align: usize,
+        new_len: usize,
+    ) -> *mut u8 {
+        let ptr = realloc(old_ptr, old_len, align, new_len);
+        crate::alloc_stats::record_free(old_len);
+        crate::alloc_stats::record_alloc(new_len);
+        ptr
+    }
+
+    /// Allocates memory for adapters, such as the WASI preview1 adapter,
+    /// which `wit-component` gives this instead of `cabi_realloc`.
+    ///
+    /// Adapters keep what they allocate for as long as the component runs,
+    /// so it mustn't come from the arena which is reset after every export
+    /// call.
+    #[cfg(feature = "arena")]
+    #[no_mangle]
+    unsafe extern "C" fn cabi_realloc_adapter(
+        old_ptr: *mut u8,
+        old_len: usize,
+        align: usize,
+        new_len: usize,
+    ) -> *mut u8 {
+        realloc(old_ptr, old_len, align, new_len)
+    }
+
+    /// Reallocates memory with the global allocator.
+    #[cfg(feature = "realloc")]
+    unsafe fn realloc(old_ptr: *mut u8, old_len: usize, align: usize, new_len: usize) -> *mut u8 {
         let layout;
         let ptr = if old_len == 0 {
             if new_len == 0 {
@@ -89,10 +165,13 @@ pub mod rt {
                 unreachable!();
             }
         }
-        return ptr;
+        ptr
     }
 
+    /// Frees memory given to an export by `cabi_realloc`, or lowered into by
+    /// an export for its caller.
     pub unsafe fn dealloc(ptr: i32, size: usize, align: usize) {
+        crate::alloc_stats::record_free(size);
         if size == 0 {
             return;
         }
@@ -100,6 +179,28 @@ pub mod rt {
         alloc::dealloc(ptr as *mut u8, layout);
     }
 
+    /// Allocates the memory for a list which is lowered to be returned from
+    /// an export, which is freed again in the export's post-return.
+    pub unsafe fn list_alloc(layout: Layout) -> *mut u8 {
+        crate::alloc_stats::record_alloc(layout.size());
+        alloc::alloc(layout)
+    }
+
+    /// Lowers a list of canonical values to be returned from an export,
+    /// returning its pointer and length.
+    pub fn list_lower<T>(list: Vec<T>) -> (i32, i32) {
+        let len = list.len();
+        crate::alloc_stats::record_alloc(len * core::mem::size_of::<T>());
+        let ptr = crate::alloc::boxed::Box::into_raw(list.into_boxed_slice()) as *mut T;
+        (ptr as i32, len as i32)
+    }
+
+    /// Takes ownership of a list of canonical values given to this component
+    /// with `cabi_realloc`.
+    pub unsafe fn list_lift<T>(ptr: i32, len: usize) -> Vec<T> {
+        Vec::from_raw_parts(ptr as *mut T, len, len)
+    }
+
     macro_rules! as_traits {
         ($(($trait_:ident $func:ident $ty:ident <=> $($tys:ident)*))*) => ($(
             pub fn $func<T: $trait_>(t: T) -> $ty {
@@ -135,37 +236,47 @@ pub mod rt {
         (AsF64 as_f64 f64 <=> f64)
     }
 
+    /// Whether the lifting functions here validate their values rather than
+    /// assuming they're valid.
+    pub const VALIDATE_LIFTS: bool = cfg!(any(debug_assertions, feature = "validate_lifts"));
+
     pub unsafe fn string_lift(bytes: Vec<u8>) -> String {
-        if cfg!(debug_assertions) {
-            String::from_utf8(bytes).unwrap()
+        if VALIDATE_LIFTS {
+            checked::string_lift(bytes)
         } else {
             String::from_utf8_unchecked(bytes)
         }
     }
 
+    /// Like `string_lift`, but for strings which are borrowed from the
+    /// caller's buffer rather than owned.
+    pub unsafe fn str_lift(bytes: &[u8]) -> &str {
+        if VALIDATE_LIFTS {
+            checked::str_lift(bytes)
+        } else {
+            core::str::from_utf8_unchecked(bytes)
+        }
+    }
+
     pub unsafe fn invalid_enum_discriminant<T>() -> T {
-        if cfg!(debug_assertions) {
-            panic!("invalid enum discriminant")
+        if VALIDATE_LIFTS {
+            checked::invalid_enum_discriminant()
         } else {
             core::hint::unreachable_unchecked()
         }
     }
 
     pub unsafe fn char_lift(val: u32) -> char {
-        if cfg!(debug_assertions) {
-            core::char::from_u32(val).unwrap()
+        if VALIDATE_LIFTS {
+            checked::char_lift(val)
         } else {
             core::char::from_u32_unchecked(val)
         }
     }
 
     pub unsafe fn bool_lift(val: u8) -> bool {
-        if cfg!(debug_assertions) {
-            match val {
-                0 => false,
-                1 => true,
-                _ => panic!("invalid bool discriminant"),
-            }
+        if VALIDATE_LIFTS {
+            checked::bool_lift(val)
         } else {
             core::mem::transmute::<u8, bool>(val)
         }
@@ -203,7 +314,11 @@ pub unsafe trait WasmResource {
 /// those exported from this component.
 ///
 /// This generally is implemented by generated code, not user-facing code.
-pub unsafe trait RustResource: WasmResource {
+pub unsafe trait RustResource: WasmResource + Sized {
+    /// How values of this resource are stored, which is [`rep::Boxed`]
+    /// unless configured otherwise with the `resource_reps` option.
+    type Rep: rep::Representation<Self>;
+
     /// Invokes the `[resource-new]...` intrinsic.
     unsafe fn new(rep: usize) -> u32;
     /// Invokes the `[resource-rep]...` intrinsic.
@@ -213,6 +328,7 @@ pub unsafe trait RustResource: WasmResource {
 impl<T: WasmResource> Resource<T> {
     #[doc(hidden)]
     pub unsafe fn from_handle(handle: u32) -> Self {
+        tracking::acquire::<T>(handle);
         Self {
             handle,
             _marker: marker::PhantomData,
@@ -221,24 +337,26 @@ impl<T: WasmResource> Resource<T> {
 
     #[doc(hidden)]
     pub fn into_handle(resource: Resource<T>) -> u32 {
+        tracking::transfer::<T>(resource.handle);
         ManuallyDrop::new(resource).handle
     }
 
     #[doc(hidden)]
     pub fn handle(resource: &Resource<T>) -> u32 {
+        tracking::check_use::<T>(resource.handle);
         resource.handle
     }
 
-    /// Creates a new Rust-defined resource from the underlying representation
-    /// `T`.
+    /// Creates a new Rust-defined resource from `val`.
     ///
-    /// This will move `T` onto the heap to create a single pointer to represent
-    /// it which is then wrapped up in a component model resource.
-    pub fn new(val: T) -> Resource<T>
+    /// With the default representation `val` is a `T` which is moved onto the
+    /// heap, and for other representations it's what the representation
+    /// stores, such as an `Rc<T>`.
+    pub fn new(val: <T::Rep as rep::Representation<T>>::Value) -> Resource<T>
     where
         T: RustResource,
     {
-        let rep = Box::into_raw(Box::new(val)) as usize;
+        let rep = <T::Rep as rep::Representation<T>>::into_rep(val);
         unsafe {
             let handle = T::new(rep);
             Resource::from_handle(handle)
@@ -250,7 +368,15 @@ impl<T: WasmResource> Resource<T> {
     where
         T: RustResource,
     {
-        let _ = Box::from_raw(rep as *mut T);
+        <T::Rep as rep::Representation<T>>::drop(rep)
+    }
+
+    #[doc(hidden)]
+    pub unsafe fn lift_borrow<'a>(rep: usize) -> &'a T
+    where
+        T: RustResource,
+    {
+        <T::Rep as rep::Representation<T>>::get(rep)
     }
 }
 
@@ -258,18 +384,25 @@ impl<T: RustResource> Deref for Resource<T> {
     type Target = T;
 
     fn deref(&self) -> &T {
+        tracking::check_use::<T>(self.handle);
         unsafe {
             let rep = T::rep(self.handle);
-            &*(rep as *const T)
+            <T::Rep as rep::Representation<T>>::get(rep)
         }
     }
 }
 
+/// # Panics
+///
+/// Panics if the value is shared, such as by other clones of an `Rc` passed
+/// to [`Resource::new`].
 impl<T: RustResource> DerefMut for Resource<T> {
     fn deref_mut(&mut self) -> &mut T {
+        tracking::check_use::<T>(self.handle);
         unsafe {
             let rep = T::rep(self.handle);
-            &mut *(rep as *mut T)
+            <T::Rep as rep::Representation<T>>::get_mut(rep)
+                .expect("cannot mutably borrow a shared resource")
         }
     }
 }
@@ -284,8 +417,339 @@ impl<T: WasmResource> fmt::Debug for Resource<T> {
 
 impl<T: WasmResource> Drop for Resource<T> {
     fn drop(&mut self) {
+        tracking::release::<T>(self.handle);
         unsafe {
             T::drop(self.handle);
         }
     }
 }
+
+/// A `borrow<T>` handle to a resource which is only valid for `'a`.
+///
+/// Bindings generated with the `typed_handles` option use this for
+/// borrowed handles, so for example a borrow passed to an export can't be
+/// kept after the export returns. This dereferences to the resource.
+///
+/// ```compile_fail,E0521
+/// use std::cell::RefCell;
+/// use wit_bindgen::Borrow;
+///
+/// wit_bindgen::generate!({
+///     inline: "
+///         package my:inline;
+///
+///         interface counters {
+///             resource counter {
+///                 constructor();
+///             }
+///             peek: func(c: borrow<counter>);
+///         }
+///
+///         world guest {
+///             export counters;
+///         }
+///     ",
+///     exports: {
+///         "my:inline/counters": Counters,
+///         "my:inline/counters/counter": Counter,
+///     },
+///     typed_handles,
+/// });
+///
+/// pub struct Counter;
+///
+/// impl exports::my::inline::counters::GuestCounter for Counter {
+///     fn new() -> Self {
+///         Counter
+///     }
+/// }
+///
+/// thread_local! {
+///     static KEPT: RefCell<Option<Borrow<'static, Counter>>> = RefCell::new(None);
+/// }
+///
+/// pub struct Counters;
+///
+/// impl exports::my::inline::counters::Guest for Counters {
+///     fn peek(c: Borrow<'_, Counter>) {
+///         // The borrow is only valid until `peek` returns.
+///         KEPT.with(|kept| *kept.borrow_mut() = Some(c));
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[repr(transparent)]
+pub struct Borrow<'a, T: ?Sized> {
+    // This is public so that generated code can borrow a handle which only
+    // lives until the end of the export call it was passed to.
+    #[doc(hidden)]
+    pub rep: &'a T,
+}
+
+impl<'a, T: ?Sized> Borrow<'a, T> {
+    /// Borrows `rep` for the lifetime of the reference.
+    pub fn new(rep: &'a T) -> Self {
+        Borrow { rep }
+    }
+
+    /// Returns the reference to the resource with the full lifetime `'a`.
+    pub fn get(self) -> &'a T {
+        self.rep
+    }
+}
+
+impl<T: ?Sized> Clone for Borrow<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Borrow<'_, T> {}
+
+impl<T: ?Sized> Deref for Borrow<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.rep
+    }
+}
+
+impl<'a, T: ?Sized> From<&'a T> for Borrow<'a, T> {
+    fn from(rep: &'a T) -> Self {
+        Borrow { rep }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Borrow<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.rep.fmt(f)
+    }
+}
+
+/// An `own<T>` handle to an imported resource.
+///
+/// Bindings generated with the `typed_handles` option use this for owned
+/// handles of imported resources, so that passing one to an import, which
+/// transfers ownership to the host, is explicit at the call site. This
+/// dereferences to the resource.
+#[repr(transparent)]
+pub struct Own<T> {
+    value: T,
+}
+
+impl<T> Own<T> {
+    /// Wraps `value` to be moved into an import.
+    pub fn new(value: T) -> Self {
+        Own { value }
+    }
+
+    /// Returns the resource, which is then owned by the caller.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Own<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Own<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for Own<T> {
+    fn from(value: T) -> Self {
+        Own { value }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Own<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// A handle to a component model `future<T>`.
+///
+/// This is used in generated code wherever a `future` is named in WIT and
+/// only records the handle's index along with its payload type.
+#[repr(transparent)]
+pub struct FutureHandle<T> {
+    handle: u32,
+    _marker: marker::PhantomData<T>,
+}
+
+impl<T> FutureHandle<T> {
+    #[doc(hidden)]
+    pub unsafe fn from_handle(handle: u32) -> Self {
+        Self {
+            handle,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn into_handle(self) -> u32 {
+        self.handle
+    }
+
+    #[doc(hidden)]
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+}
+
+impl<T> fmt::Debug for FutureHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FutureHandle")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+/// A handle to a component model `stream<T, U>`, where `T` is the type of
+/// each element and `U` is the type of the value the stream ends with.
+#[repr(transparent)]
+pub struct StreamHandle<T, U> {
+    handle: u32,
+    _marker: marker::PhantomData<(T, U)>,
+}
+
+impl<T, U> StreamHandle<T, U> {
+    #[doc(hidden)]
+    pub unsafe fn from_handle(handle: u32) -> Self {
+        Self {
+            handle,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn into_handle(self) -> u32 {
+        self.handle
+    }
+
+    #[doc(hidden)]
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+}
+
+impl<T, U> fmt::Debug for StreamHandle<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamHandle")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+/// The error returned by the `FromStr` implementations of generated enums and
+/// flags when a name isn't the WIT name of one of their cases or flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownName {
+    type_name: &'static str,
+    name: String,
+}
+
+impl UnknownName {
+    #[doc(hidden)]
+    pub fn new(type_name: &'static str, name: &str) -> Self {
+        Self {
+            type_name,
+            name: name.into(),
+        }
+    }
+
+    /// The name which was parsed.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is not a name in `{}`", self.name, self.type_name)
+    }
+}
+
+/// The error returned by the `TryFrom` implementations of generated enums and
+/// flags when an integer isn't the discriminant of a case or contains bits
+/// which aren't flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidValue {
+    type_name: &'static str,
+}
+
+impl InvalidValue {
+    #[doc(hidden)]
+    pub fn new(type_name: &'static str) -> Self {
+        Self { type_name }
+    }
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value for `{}`", self.type_name)
+    }
+}
+
+/// Global state of the runtime support, created with `init` on first use.
+///
+/// Wasm is single-threaded, so this is a plain `static` there. Tests of the
+/// `mock` feature run on several threads though, which each get their own
+/// copy. `with` must not be reentered for the same state.
+pub(crate) struct State<T: 'static> {
+    init: fn() -> T,
+    #[cfg(not(any(feature = "mock", test)))]
+    value: core::cell::UnsafeCell<Option<T>>,
+}
+
+#[cfg(not(any(feature = "mock", test)))]
+unsafe impl<T> Sync for State<T> {}
+
+impl<T: 'static> State<T> {
+    pub(crate) const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            #[cfg(not(any(feature = "mock", test)))]
+            value: core::cell::UnsafeCell::new(None),
+        }
+    }
+
+    #[cfg(not(any(feature = "mock", test)))]
+    pub(crate) fn with<R>(&'static self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(unsafe { (*self.value.get()).get_or_insert_with(self.init) })
+    }
+
+    #[cfg(any(feature = "mock", test))]
+    pub(crate) fn with<R>(&'static self, f: impl FnOnce(&mut T) -> R) -> R {
+        use alloc::collections::BTreeMap;
+        use alloc::rc::Rc;
+        use core::any::Any;
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static STATES: RefCell<BTreeMap<usize, Rc<dyn Any>>> = const {
+                RefCell::new(BTreeMap::new())
+            };
+        }
+
+        let key = self as *const Self as usize;
+        let state = STATES.with(|states| {
+            states
+                .borrow_mut()
+                .entry(key)
+                .or_insert_with(|| Rc::new(RefCell::new((self.init)())))
+                .clone()
+        });
+        let state = state.downcast_ref::<RefCell<T>>().unwrap();
+        let result = f(&mut state.borrow_mut());
+        result
+    }
+}
@@ -0,0 +1,161 @@
+//! Runtime support for the `mock` mode of the Rust bindings generator.
+//!
+//! On non-wasm targets bindings generated with `mock` don't call any wasm
+//! imports. Instead:
+//!
+//! * Each imported interface has a `Host` trait, and its functions call the
+//!   implementation installed with the interface's generated `set_host`,
+//!   which is stored here with [`set_host`] and found with [`host`].
+//! * The `[resource-new]`, `[resource-rep]` and `[resource-drop]`
+//!   intrinsics are emulated with a table of handles, so exported resources
+//!   can be created and used with `Resource<T>` as usual. Imported resources
+//!   are created by hosts with [`resource_new_state`], which owns some state
+//!   for the host to find again with [`resource_state`].
+//!
+//! Tests usually run on multiple threads, so both hosts and handles are
+//! per-thread which lets each test install its own hosts.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use core::any::{type_name, Any, TypeId};
+use core::cell::RefCell;
+
+std::thread_local! {
+    static HOSTS: RefCell<BTreeMap<TypeId, Box<dyn Any>>> = RefCell::new(BTreeMap::new());
+    static HANDLES: RefCell<Handles> = RefCell::new(Handles::default());
+}
+
+#[derive(Default)]
+struct Handles {
+    next: u32,
+    entries: BTreeMap<u32, Entry>,
+}
+
+struct Entry {
+    rep: usize,
+    dtor: unsafe fn(usize),
+    /// Whether `rep` is a `Box<dyn Any>` from `resource_new_state`.
+    state: bool,
+}
+
+/// Installs `host` as the implementation of the host trait `T` for this
+/// thread, replacing any previous one.
+pub fn set_host<T: ?Sized + 'static>(host: Box<T>) {
+    let host: Rc<T> = host.into();
+    HOSTS.with(|hosts| {
+        hosts.borrow_mut().insert(TypeId::of::<T>(), Box::new(host));
+    });
+}
+
+/// Returns the implementation of the host trait `T` for this thread.
+///
+/// # Panics
+///
+/// Panics if `set_host` wasn't called for `T` on this thread.
+pub fn host<T: ?Sized + 'static>() -> Rc<T> {
+    // The host is cloned out of the map so that it may itself call imports
+    // or install other hosts.
+    HOSTS.with(|hosts| {
+        hosts
+            .borrow()
+            .get(&TypeId::of::<T>())
+            .and_then(|host| host.downcast_ref::<Rc<T>>())
+            .cloned()
+            .unwrap_or_else(|| {
+                panic!(
+                    "no host installed for `{}`, call `set_host` first",
+                    type_name::<T>()
+                )
+            })
+    })
+}
+
+/// Emulates `[resource-new]`, returning a new handle for `rep` which calls
+/// `dtor` when it's dropped.
+pub fn resource_new(rep: usize, dtor: unsafe fn(usize)) -> u32 {
+    new_entry(Entry {
+        rep,
+        dtor,
+        state: false,
+    })
+}
+
+/// Emulates `[resource-rep]`.
+///
+/// # Panics
+///
+/// Panics if `handle` isn't a live handle.
+pub fn resource_rep(handle: u32) -> usize {
+    HANDLES.with(|handles| match handles.borrow().entries.get(&handle) {
+        Some(entry) => entry.rep,
+        None => panic!("unknown resource handle {handle}"),
+    })
+}
+
+/// Emulates `[resource-drop]`, running the destructor of the handle.
+///
+/// # Panics
+///
+/// Panics if `handle` isn't a live handle.
+pub fn resource_drop(handle: u32) {
+    // Destructors may drop other resources, so the entry is removed before
+    // the destructor runs.
+    let entry = HANDLES.with(|handles| handles.borrow_mut().entries.remove(&handle));
+    match entry {
+        Some(entry) => unsafe { (entry.dtor)(entry.rep) },
+        None => panic!("unknown resource handle {handle}"),
+    }
+}
+
+/// Creates a handle for an imported resource which owns `state`, which is
+/// dropped along with the handle.
+pub fn resource_new_state<T: 'static>(state: T) -> u32 {
+    unsafe fn dtor(rep: usize) {
+        drop(Box::from_raw(rep as *mut Box<dyn Any>));
+    }
+
+    let state: Box<dyn Any> = Box::new(state);
+    new_entry(Entry {
+        rep: Box::into_raw(Box::new(state)) as usize,
+        dtor,
+        state: true,
+    })
+}
+
+/// Returns the state of a handle created with [`resource_new_state`].
+///
+/// # Panics
+///
+/// Panics if `handle` isn't a live handle created by
+/// [`resource_new_state`] with a `T`.
+///
+/// # Safety
+///
+/// The returned reference must not outlive the handle.
+pub unsafe fn resource_state<'a, T: 'static>(handle: u32) -> &'a T {
+    let rep = HANDLES.with(|handles| match handles.borrow().entries.get(&handle) {
+        Some(entry) if entry.state => entry.rep,
+        _ => panic!("resource handle {handle} was not created by a mock host"),
+    });
+    let state = &*(rep as *const Box<dyn Any>);
+    match state.downcast_ref() {
+        Some(state) => state,
+        None => panic!(
+            "resource handle {handle} does not hold a `{}`",
+            type_name::<T>()
+        ),
+    }
+}
+
+fn new_entry(entry: Entry) -> u32 {
+    HANDLES.with(|handles| {
+        let mut handles = handles.borrow_mut();
+        // Handles start at 1 and are never reused, so use-after-drop bugs are
+        // caught instead of reaching some other resource.
+        handles.next += 1;
+        let handle = handles.next;
+        handles.entries.insert(handle, entry);
+        handle
+    })
+}
@@ -0,0 +1,160 @@
+//! Representations of exported resources.
+//!
+//! The component model keeps a "rep" in its handle table for each exported
+//! resource, which this crate hands back to [`Representation::get`] to find
+//! the resource's value. Values are boxed by default, while the
+//! `resource_reps` option of generated bindings picks another representation
+//! for each resource, such as the shared pointers here or an index into a
+//! slab which the component implements [`Representation`] for itself.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+use core::mem::ManuallyDrop;
+
+/// How values of an exported resource `T` are stored behind their rep.
+///
+/// # Safety
+///
+/// Until [`Representation::drop`] is called for a rep returned by
+/// [`Representation::into_rep`], `get` must return the value stored for it, and
+/// `get_mut` must only return it while nothing else can reach it.
+pub unsafe trait Representation<T> {
+    /// What `Resource::new` takes to create a resource, which generated
+    /// bindings create from the `T` returned by constructors.
+    type Value: From<T>;
+
+    /// Stores `value` and returns its rep.
+    fn into_rep(value: Self::Value) -> usize;
+
+    /// Returns the value stored for `rep`.
+    ///
+    /// # Safety
+    ///
+    /// `rep` must have been returned by `into_rep` and not yet dropped.
+    unsafe fn get<'a>(rep: usize) -> &'a T;
+
+    /// Returns the value stored for `rep` mutably, or `None` if it's shared.
+    ///
+    /// # Safety
+    ///
+    /// `rep` must have been returned by `into_rep` and not yet dropped.
+    unsafe fn get_mut<'a>(rep: usize) -> Option<&'a mut T>;
+
+    /// Drops the value stored for `rep`, which happens once its resource's
+    /// last handle was dropped.
+    ///
+    /// # Safety
+    ///
+    /// `rep` must have been returned by `into_rep` and not yet dropped.
+    unsafe fn drop(rep: usize);
+}
+
+/// Stores each value in its own `Box`, with the box's pointer as the rep.
+///
+/// This is the default representation.
+pub struct Boxed;
+
+unsafe impl<T> Representation<T> for Boxed {
+    type Value = T;
+
+    fn into_rep(value: T) -> usize {
+        Box::into_raw(Box::new(value)) as usize
+    }
+
+    unsafe fn get<'a>(rep: usize) -> &'a T {
+        &*(rep as *const T)
+    }
+
+    unsafe fn get_mut<'a>(rep: usize) -> Option<&'a mut T> {
+        Some(&mut *(rep as *mut T))
+    }
+
+    unsafe fn drop(rep: usize) {
+        drop(Box::from_raw(rep as *mut T));
+    }
+}
+
+/// Stores an `Rc<T>`, with the pointer from `Rc::into_raw` as the rep.
+///
+/// The component can keep its own clones of the `Rc` passed to
+/// `Resource::new`, and the handle table keeps the value alive as one more
+/// strong reference.
+pub struct RcShared;
+
+impl RcShared {
+    /// Returns another `Rc` of a resource's value.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be the value of a resource represented by `RcShared`,
+    /// such as one borrowed by an export.
+    pub unsafe fn clone_rc<T>(value: &T) -> Rc<T> {
+        let ptr = value as *const T;
+        Rc::increment_strong_count(ptr);
+        Rc::from_raw(ptr)
+    }
+}
+
+unsafe impl<T> Representation<T> for RcShared {
+    type Value = Rc<T>;
+
+    fn into_rep(value: Rc<T>) -> usize {
+        Rc::into_raw(value) as usize
+    }
+
+    unsafe fn get<'a>(rep: usize) -> &'a T {
+        &*(rep as *const T)
+    }
+
+    unsafe fn get_mut<'a>(rep: usize) -> Option<&'a mut T> {
+        let mut rc = ManuallyDrop::new(Rc::from_raw(rep as *const T));
+        Rc::get_mut(&mut rc).map(|value| &mut *(value as *mut T))
+    }
+
+    unsafe fn drop(rep: usize) {
+        drop(Rc::from_raw(rep as *const T));
+    }
+}
+
+/// Like [`RcShared`] but for an `Arc<T>`.
+#[cfg(target_has_atomic = "ptr")]
+pub struct ArcShared;
+
+#[cfg(target_has_atomic = "ptr")]
+impl ArcShared {
+    /// Returns another `Arc` of a resource's value.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be the value of a resource represented by `ArcShared`,
+    /// such as one borrowed by an export.
+    pub unsafe fn clone_arc<T>(value: &T) -> Arc<T> {
+        let ptr = value as *const T;
+        Arc::increment_strong_count(ptr);
+        Arc::from_raw(ptr)
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+unsafe impl<T> Representation<T> for ArcShared {
+    type Value = Arc<T>;
+
+    fn into_rep(value: Arc<T>) -> usize {
+        Arc::into_raw(value) as usize
+    }
+
+    unsafe fn get<'a>(rep: usize) -> &'a T {
+        &*(rep as *const T)
+    }
+
+    unsafe fn get_mut<'a>(rep: usize) -> Option<&'a mut T> {
+        let mut arc = ManuallyDrop::new(Arc::from_raw(rep as *const T));
+        Arc::get_mut(&mut arc).map(|value| &mut *(value as *mut T))
+    }
+
+    unsafe fn drop(rep: usize) {
+        drop(Arc::from_raw(rep as *const T));
+    }
+}
@@ -0,0 +1,119 @@
+//! Tracing of calls through bindings generated with the `trace` option.
+//!
+//! Such bindings report each call of an import and each call of an export
+//! along with its arguments, and then its results once it returns, to the
+//! hook installed with [`set_hook`]. Without a hook nothing is reported, so
+//! a hook is also where events can be forwarded to a logging or `tracing`
+//! setup of the component's choosing.
+//!
+//! Values are formatted with `Debug`, except for borrowed handles of exported
+//! resources whose types needn't implement it, which are shown as `_`.
+
+use core::fmt;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// The hook installed with [`set_hook`], or null.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Installs `hook` to be called with every [`Record`] of generated bindings.
+pub fn set_hook(hook: fn(&Record<'_>)) {
+    HOOK.store(hook as *mut (), Ordering::Relaxed);
+}
+
+/// Whether a function is imported by the component or exported from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Import,
+    Export,
+}
+
+/// Whether a function is being called or is returning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Call,
+    Return,
+}
+
+/// A call of a function, or its return.
+///
+/// `Display` formats this as `import ns:pkg/iface#name("arg", 1)` for a call
+/// and as `import ns:pkg/iface#name -> (42)` for its return.
+#[derive(Clone, Copy)]
+pub struct Record<'a> {
+    pub direction: Direction,
+    pub event: Event,
+    /// The name of the interface, or `None` for a function of the world.
+    pub interface: Option<&'a str>,
+    /// The name of the function in WIT, such as `[method]file.read`.
+    pub function: &'a str,
+    /// The arguments of a call, or the results of a return.
+    pub values: &'a [&'a dyn fmt::Debug],
+}
+
+impl fmt::Debug for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Record")
+            .field("direction", &self.direction)
+            .field("event", &self.event)
+            .field("interface", &self.interface)
+            .field("function", &self.function)
+            .field("values", &self.values)
+            .finish()
+    }
+}
+
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.direction {
+            Direction::Import => f.write_str("import ")?,
+            Direction::Export => f.write_str("export ")?,
+        }
+        if let Some(interface) = self.interface {
+            write!(f, "{interface}#")?;
+        }
+        f.write_str(self.function)?;
+        match self.event {
+            Event::Call => f.write_str("(")?,
+            Event::Return => f.write_str(" -> (")?,
+        }
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{value:?}")?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Stands in for a value which can't be formatted.
+pub struct Opaque;
+
+impl fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("_")
+    }
+}
+
+/// Reports a record to the installed hook, if any.
+#[doc(hidden)]
+pub fn emit(
+    direction: Direction,
+    event: Event,
+    interface: Option<&str>,
+    function: &str,
+    values: &[&dyn fmt::Debug],
+) {
+    let hook = HOOK.load(Ordering::Relaxed);
+    if hook.is_null() {
+        return;
+    }
+    let hook = unsafe { core::mem::transmute::<*mut (), fn(&Record<'_>)>(hook) };
+    hook(&Record {
+        direction,
+        event,
+        interface,
+        function,
+        values,
+    });
+}
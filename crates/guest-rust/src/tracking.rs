@@ -0,0 +1,176 @@
+//! Runtime support for the `track_resources` feature of this crate.
+//!
+//! With the feature enabled every [`Resource`](crate::Resource) records its
+//! handle in a registry when it's created from a handle, and updates it when
+//! the handle is moved out with `into_handle` or dropped. Dropping a handle
+//! twice, or using one after it was moved to the other side, panics with the
+//! resource's type name instead of trapping in the host, and
+//! [`live_resources`] reports the handles which are still alive, such as to
+//! find leaks at the end of a test.
+//!
+//! Without the feature nothing is recorded and [`live_resources`] is always
+//! empty.
+
+// The resource types are only used to name them when the feature is enabled.
+#![cfg_attr(
+    not(feature = "track_resources"),
+    allow(clippy::extra_unused_type_parameters)
+)]
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Whether the `track_resources` feature is enabled.
+pub const ENABLED: bool = cfg!(feature = "track_resources");
+
+/// The handles of each resource type which are alive, as returned by
+/// [`live_resources`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LiveResources {
+    handles: BTreeMap<&'static str, Vec<u32>>,
+}
+
+impl LiveResources {
+    /// Returns whether no handles are alive.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Returns the number of live handles of all types.
+    pub fn len(&self) -> usize {
+        self.handles.values().map(|handles| handles.len()).sum()
+    }
+
+    /// Returns the live handles of the resource type named `type_name`, as
+    /// given by `core::any::type_name`.
+    pub fn get(&self, type_name: &str) -> &[u32] {
+        self.handles.get(type_name).map_or(&[], |handles| handles)
+    }
+
+    /// Iterates over each resource type with live handles along with them.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &[u32])> + '_ {
+        self.handles
+            .iter()
+            .map(|(type_name, handles)| (*type_name, &handles[..]))
+    }
+}
+
+impl fmt::Display for LiveResources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (type_name, handles) in self.iter() {
+            writeln!(f, "{type_name}: {} live {handles:?}", handles.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the handles of every resource which is alive in this component.
+pub fn live_resources() -> LiveResources {
+    #[cfg(feature = "track_resources")]
+    {
+        imp::live()
+    }
+    #[cfg(not(feature = "track_resources"))]
+    {
+        LiveResources::default()
+    }
+}
+
+/// Records that a `T` was created from `handle`.
+#[inline]
+pub(crate) fn acquire<T: ?Sized>(handle: u32) {
+    #[cfg(feature = "track_resources")]
+    imp::update(core::any::type_name::<T>(), handle, imp::Op::Acquire);
+    let _ = handle;
+}
+
+/// Checks that `handle` of a `T` wasn't moved to the other side.
+#[inline]
+pub(crate) fn check_use<T: ?Sized>(handle: u32) {
+    #[cfg(feature = "track_resources")]
+    imp::update(core::any::type_name::<T>(), handle, imp::Op::Use);
+    let _ = handle;
+}
+
+/// Records that `handle` of a `T` was moved to the other side.
+#[inline]
+pub(crate) fn transfer<T: ?Sized>(handle: u32) {
+    #[cfg(feature = "track_resources")]
+    imp::update(core::any::type_name::<T>(), handle, imp::Op::Transfer);
+    let _ = handle;
+}
+
+/// Records that `handle` of a `T` is being dropped.
+#[inline]
+pub(crate) fn release<T: ?Sized>(handle: u32) {
+    #[cfg(feature = "track_resources")]
+    imp::update(core::any::type_name::<T>(), handle, imp::Op::Release);
+    let _ = handle;
+}
+
+#[cfg(feature = "track_resources")]
+mod imp {
+    use super::LiveResources;
+    use alloc::collections::BTreeMap;
+
+    pub enum Op {
+        Acquire,
+        Use,
+        Transfer,
+        Release,
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Live,
+        Transferred,
+    }
+
+    /// The state of each handle which was created, keyed by its type name.
+    /// Dropped handles are removed and transferred ones are kept around to
+    /// catch later uses, until the handle is reused for a new resource.
+    type Registry = BTreeMap<(&'static str, u32), State>;
+
+    static REGISTRY: crate::State<Registry> = crate::State::new(BTreeMap::new);
+
+    pub fn update(type_name: &'static str, handle: u32, op: Op) {
+        // Panic outside of `with` so the registry remains usable afterwards.
+        let misuse = REGISTRY.with(|registry| {
+            let key = (type_name, handle);
+            let state = registry.get(&key).copied();
+            match (op, state) {
+                (Op::Acquire, _) => {
+                    registry.insert(key, State::Live);
+                    None
+                }
+                (_, Some(State::Transferred)) => Some("used after it was transferred"),
+                (Op::Use, _) => None,
+                (Op::Transfer, _) => {
+                    registry.insert(key, State::Transferred);
+                    None
+                }
+                (Op::Release, Some(State::Live)) => {
+                    registry.remove(&key);
+                    None
+                }
+                (Op::Release, None) => Some("dropped twice"),
+            }
+        });
+        if let Some(misuse) = misuse {
+            panic!("handle {handle} of `{type_name}` {misuse}");
+        }
+    }
+
+    pub fn live() -> LiveResources {
+        let mut live = LiveResources::default();
+        REGISTRY.with(|registry| {
+            for ((type_name, handle), state) in registry.iter() {
+                if *state == State::Live {
+                    live.handles.entry(*type_name).or_default().push(*handle);
+                }
+            }
+        });
+        live
+    }
+}
@@ -37,7 +37,7 @@ impl Opts {
 }
 
 impl WorldGenerator for Markdown {
-    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
         self.sizes.fill(resolve);
 
         let world = &resolve.worlds[world];
@@ -109,6 +109,7 @@ impl WorldGenerator for Markdown {
             }
         }
         gen.push_str("\n");
+        Ok(())
     }
 
     fn import_interface(
@@ -666,6 +667,62 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.type_alias(id, name, &Type::Id(id), docs);
     }
 
+    fn type_future(&mut self, _id: TypeId, name: &str, ty: &Option<Type>, docs: &Docs) {
+        self.print_type_header("type", name);
+        match ty {
+            Some(ty) => {
+                self.push_str("future<");
+                self.print_ty(ty);
+                self.push_str(">");
+            }
+            None => self.push_str("future"),
+        }
+        self.push_str("\n<p>");
+        self.docs(docs);
+        self.push_str("\n");
+    }
+
+    fn type_stream(&mut self, _id: TypeId, name: &str, stream: &Stream, docs: &Docs) {
+        self.print_type_header("type", name);
+        match (stream.element, stream.end) {
+            (Some(element), Some(end)) => {
+                self.push_str("stream<");
+                self.print_ty(&element);
+                self.push_str(", ");
+                self.print_ty(&end);
+                self.push_str(">");
+            }
+            (None, Some(end)) => {
+                self.push_str("stream<_, ");
+                self.print_ty(&end);
+                self.push_str(">");
+            }
+            (Some(element), None) => {
+                self.push_str("stream<");
+                self.print_ty(&element);
+                self.push_str(">");
+            }
+            (None, None) => self.push_str("stream"),
+        }
+        self.push_str("\n<p>");
+        self.docs(docs);
+        self.push_str("\n");
+    }
+
+    fn type_handle(&mut self, _id: TypeId, name: &str, handle: &Handle, docs: &Docs) {
+        self.print_type_header("type", name);
+        let (kind, resource) = match handle {
+            Handle::Own(resource) => ("own", resource),
+            Handle::Borrow(resource) => ("borrow", resource),
+        };
+        self.push_str(kind);
+        self.push_str("<");
+        self.print_ty(&Type::Id(*resource));
+        self.push_str(">\n<p>");
+        self.docs(docs);
+        self.push_str("\n");
+    }
+
     fn type_builtin(&mut self, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
         self.type_alias(id, name, ty, docs)
     }
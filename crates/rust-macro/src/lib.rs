@@ -1,12 +1,13 @@
 use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use syn::parse::{Error, Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 use syn::{braced, token, Token};
-use wit_bindgen_core::wit_parser::{PackageId, Resolve, UnresolvedPackage, WorldId};
-use wit_bindgen_rust::{Opts, Ownership};
+use wit_bindgen_core::load::Loader;
+use wit_bindgen_core::wit_parser::{PackageId, Resolve, WorldId};
+use wit_bindgen_rust::{ExportOwnership, Opts, Ownership};
 
 #[proc_macro]
 pub fn generate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -23,9 +24,15 @@ struct Config {
     files: Vec<PathBuf>,
 }
 
-enum Source {
-    Path(String),
-    Inline(String),
+/// Where the WIT to generate bindings for comes from.
+#[derive(Default)]
+struct Source {
+    /// Roots relative to `CARGO_MANIFEST_DIR`, the first of which is the main
+    /// package unless there's inline WIT.
+    paths: Vec<String>,
+    inline: Option<String>,
+    /// Packages by name along with their path.
+    deps: Vec<(String, String)>,
 }
 
 impl Parse for Config {
@@ -33,7 +40,7 @@ impl Parse for Config {
         let call_site = Span::call_site();
         let mut opts = Opts::default();
         let mut world = None;
-        let mut source = None;
+        let mut source = Source::default();
 
         if input.peek(token::Brace) {
             let content;
@@ -41,11 +48,11 @@ impl Parse for Config {
             let fields = Punctuated::<Opt, Token![,]>::parse_terminated(&content)?;
             for field in fields.into_pairs() {
                 match field.into_value() {
-                    Opt::Path(s) => {
-                        if source.is_some() {
-                            return Err(Error::new(s.span(), "cannot specify second source"));
+                    Opt::Path(span, paths) => {
+                        if !source.paths.is_empty() {
+                            return Err(Error::new(span, "cannot specify second path"));
                         }
-                        source = Some(Source::Path(s.value()));
+                        source.paths = paths.iter().map(|p| p.value()).collect();
                     }
                     Opt::World(s) => {
                         if world.is_some() {
@@ -54,14 +61,16 @@ impl Parse for Config {
                         world = Some(s.value());
                     }
                     Opt::Inline(s) => {
-                        if source.is_some() {
-                            return Err(Error::new(s.span(), "cannot specify second source"));
+                        if source.inline.is_some() {
+                            return Err(Error::new(s.span(), "cannot specify second inline"));
                         }
-                        source = Some(Source::Inline(s.value()));
+                        source.inline = Some(s.value());
                     }
+                    Opt::Deps(deps) => source.deps.extend(deps),
                     Opt::UseStdFeature => opts.std_feature = true,
                     Opt::RawStrings => opts.raw_strings = true,
                     Opt::Ownership(ownership) => opts.ownership = ownership,
+                    Opt::ExportOwnership(ownership) => opts.export_ownership = ownership,
                     Opt::Skip(list) => opts.skip.extend(list.iter().map(|i| i.value())),
                     Opt::RuntimePath(path) => opts.runtime_path = Some(path.value()),
                     Opt::BitflagsPath(path) => opts.bitflags_path = Some(path.value()),
@@ -81,12 +90,21 @@ impl Parse for Config {
                             .collect()
                     }
                     Opt::With(with) => opts.with.extend(with),
+                    Opt::Remap(remap) => opts.remap.extend(remap),
+                    Opt::ResourceReps(reps) => opts.resource_reps.extend(reps),
+                    Opt::UnstableAsync => opts.unstable_async = true,
+                    Opt::Mock => opts.mock = true,
+                    Opt::Serde => opts.serde = true,
+                    Opt::ValidateLifts => opts.validate_lifts = true,
+                    Opt::Trace => opts.trace = true,
+                    Opt::TypedHandles => opts.typed_handles = true,
+                    Opt::Arena => opts.arena = true,
                 }
             }
         } else {
             world = input.parse::<Option<syn::LitStr>>()?.map(|s| s.value());
             if input.parse::<Option<syn::token::In>>()?.is_some() {
-                source = Some(Source::Path(input.parse::<syn::LitStr>()?.value()));
+                source.paths = vec![input.parse::<syn::LitStr>()?.value()];
             }
         }
         let (resolve, pkg, files) =
@@ -103,30 +121,26 @@ impl Parse for Config {
     }
 }
 
-fn parse_source(source: &Option<Source>) -> anyhow::Result<(Resolve, PackageId, Vec<PathBuf>)> {
-    let mut resolve = Resolve::default();
-    let mut files = Vec::new();
+fn parse_source(source: &Source) -> anyhow::Result<(Resolve, PackageId, Vec<PathBuf>)> {
     let root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
-    let mut parse = |path: &Path| -> anyhow::Result<_> {
-        if path.is_dir() {
-            let (pkg, sources) = resolve.push_dir(path)?;
-            files = sources;
-            Ok(pkg)
-        } else {
-            let pkg = UnresolvedPackage::parse_file(path)?;
-            files.extend(pkg.source_files().map(|s| s.to_owned()));
-            resolve.push(pkg)
-        }
-    };
-    let pkg = match source {
-        Some(Source::Inline(s)) => {
-            resolve.push(UnresolvedPackage::parse("macro-input".as_ref(), s)?)?
-        }
-        Some(Source::Path(s)) => parse(&root.join(s))?,
-        None => parse(&root.join("wit"))?,
+    let mut loader = Loader::new();
+    for (name, path) in source.deps.iter() {
+        loader.push_dep(name, &root.join(path))?;
+    }
+    if let Some(inline) = &source.inline {
+        loader.push_str("macro-input".as_ref(), inline)?;
+    }
+    // Without any source the `wit` directory is the main package, while
+    // inline WIT only reads the directories it's given.
+    let default_path = ["wit".to_string()];
+    let paths = match &source.paths[..] {
+        [] if source.inline.is_none() => &default_path[..],
+        paths => paths,
     };
-
-    Ok((resolve, pkg, files))
+    for path in paths {
+        loader.push_path(&root.join(path))?;
+    }
+    loader.finish()
 }
 
 impl Config {
@@ -140,13 +154,16 @@ impl Config {
         let src = std::str::from_utf8(src).unwrap();
         let mut contents = src.parse::<TokenStream>().unwrap();
 
-        // Include a dummy `include_str!` for any files we read so rustc knows that
-        // we depend on the contents of those files.
+        // Include a dummy `include_bytes!` for any files we read so rustc knows
+        // that we depend on the contents of those files, which may be binary.
         for file in self.files.iter() {
             contents.extend(
-                format!("const _: &str = include_str!(r#\"{}\"#);\n", file.display())
-                    .parse::<TokenStream>()
-                    .unwrap(),
+                format!(
+                    "const _: &[u8] = include_bytes!(r#\"{}\"#);\n",
+                    file.display()
+                )
+                .parse::<TokenStream>()
+                .unwrap(),
             );
         }
 
@@ -162,6 +179,7 @@ mod kw {
     syn::custom_keyword!(path);
     syn::custom_keyword!(inline);
     syn::custom_keyword!(ownership);
+    syn::custom_keyword!(export_ownership);
     syn::custom_keyword!(runtime_path);
     syn::custom_keyword!(bitflags_path);
     syn::custom_keyword!(exports);
@@ -169,6 +187,16 @@ mod kw {
     syn::custom_keyword!(export_prefix);
     syn::custom_keyword!(additional_derives);
     syn::custom_keyword!(with);
+    syn::custom_keyword!(remap);
+    syn::custom_keyword!(resource_reps);
+    syn::custom_keyword!(mock);
+    syn::custom_keyword!(serde);
+    syn::custom_keyword!(validate_lifts);
+    syn::custom_keyword!(trace);
+    syn::custom_keyword!(typed_handles);
+    syn::custom_keyword!(arena);
+    syn::custom_keyword!(unstable_async);
+    syn::custom_keyword!(deps);
 }
 
 #[derive(Clone)]
@@ -215,12 +243,14 @@ impl Parse for Export {
 
 enum Opt {
     World(syn::LitStr),
-    Path(syn::LitStr),
+    Path(Span, Vec<syn::LitStr>),
     Inline(syn::LitStr),
+    Deps(Vec<(String, String)>),
     UseStdFeature,
     RawStrings,
     Skip(Vec<syn::LitStr>),
     Ownership(Ownership),
+    ExportOwnership(ExportOwnership),
     RuntimePath(syn::LitStr),
     BitflagsPath(syn::LitStr),
     Exports(Vec<Export>),
@@ -229,19 +259,43 @@ enum Opt {
     // Parse as paths so we can take the concrete types/macro names rather than raw strings
     AdditionalDerives(Vec<syn::Path>),
     With(HashMap<String, String>),
+    Remap(HashMap<String, String>),
+    ResourceReps(HashMap<String, String>),
+    UnstableAsync,
+    Mock,
+    Serde,
+    ValidateLifts,
+    Trace,
+    TypedHandles,
+    Arena,
 }
 
 impl Parse for Opt {
     fn parse(input: ParseStream<'_>) -> Result<Self> {
         let l = input.lookahead1();
         if l.peek(kw::path) {
-            input.parse::<kw::path>()?;
+            let span = input.parse::<kw::path>()?.span;
             input.parse::<Token![:]>()?;
-            Ok(Opt::Path(input.parse()?))
+            if input.peek(token::Bracket) {
+                let contents;
+                syn::bracketed!(contents in input);
+                let list = Punctuated::<_, Token![,]>::parse_terminated(&contents)?;
+                Ok(Opt::Path(span, list.into_iter().collect()))
+            } else {
+                Ok(Opt::Path(span, vec![input.parse()?]))
+            }
         } else if l.peek(kw::inline) {
             input.parse::<kw::inline>()?;
             input.parse::<Token![:]>()?;
             Ok(Opt::Inline(input.parse()?))
+        } else if l.peek(kw::deps) {
+            input.parse::<kw::deps>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(deps_field_parse, Token![,])?;
+            Ok(Opt::Deps(fields.into_iter().collect()))
         } else if l.peek(kw::world) {
             input.parse::<kw::world>()?;
             input.parse::<Token![:]>()?;
@@ -290,6 +344,23 @@ impl Parse for Opt {
                     ));
                 }
             }))
+        } else if l.peek(kw::export_ownership) {
+            input.parse::<kw::export_ownership>()?;
+            input.parse::<Token![:]>()?;
+            let ownership = input.parse::<syn::Ident>()?;
+            Ok(Opt::ExportOwnership(match ownership.to_string().as_str() {
+                "Owning" => ExportOwnership::Owning,
+                "Borrowing" => ExportOwnership::Borrowing,
+                name => {
+                    return Err(Error::new(
+                        ownership.span(),
+                        format!(
+                            "unrecognized export ownership: `{name}`; \
+                             expected `Owning` or `Borrowing`"
+                        ),
+                    ));
+                }
+            }))
         } else if l.peek(kw::exports) {
             input.parse::<kw::exports>()?;
             input.parse::<Token![:]>()?;
@@ -333,7 +404,44 @@ impl Parse for Opt {
             let _lbrace = braced!(contents in input);
             let fields: Punctuated<_, Token![,]> =
                 contents.parse_terminated(with_field_parse, Token![,])?;
-            Ok(Opt::With(HashMap::from_iter(fields.into_iter())))
+            Ok(Opt::With(HashMap::from_iter(fields)))
+        } else if l.peek(kw::remap) {
+            input.parse::<kw::remap>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(remap_field_parse, Token![,])?;
+            Ok(Opt::Remap(HashMap::from_iter(fields)))
+        } else if l.peek(kw::resource_reps) {
+            input.parse::<kw::resource_reps>()?;
+            input.parse::<Token![:]>()?;
+            let contents;
+            let _lbrace = braced!(contents in input);
+            let fields: Punctuated<_, Token![,]> =
+                contents.parse_terminated(remap_field_parse, Token![,])?;
+            Ok(Opt::ResourceReps(HashMap::from_iter(fields)))
+        } else if l.peek(kw::unstable_async) {
+            input.parse::<kw::unstable_async>()?;
+            Ok(Opt::UnstableAsync)
+        } else if l.peek(kw::mock) {
+            input.parse::<kw::mock>()?;
+            Ok(Opt::Mock)
+        } else if l.peek(kw::serde) {
+            input.parse::<kw::serde>()?;
+            Ok(Opt::Serde)
+        } else if l.peek(kw::validate_lifts) {
+            input.parse::<kw::validate_lifts>()?;
+            Ok(Opt::ValidateLifts)
+        } else if l.peek(kw::trace) {
+            input.parse::<kw::trace>()?;
+            Ok(Opt::Trace)
+        } else if l.peek(kw::typed_handles) {
+            input.parse::<kw::typed_handles>()?;
+            Ok(Opt::TypedHandles)
+        } else if l.peek(kw::arena) {
+            input.parse::<kw::arena>()?;
+            Ok(Opt::Arena)
         } else {
             Err(l.error())
         }
@@ -360,6 +468,25 @@ fn serialize(path: syn::Path) -> String {
     }
 }
 
+fn remap_field_parse(input: ParseStream<'_>) -> Result<(String, String)> {
+    let ty = input.parse::<syn::LitStr>()?.value();
+    input.parse::<Token![:]>()?;
+    let rust = input.parse::<syn::Type>()?;
+    Ok((ty, rust.into_token_stream().to_string()))
+}
+
+/// Parses `"ns:pkg": "path"`, also accepting `=>` in place of the colon.
+fn deps_field_parse(input: ParseStream<'_>) -> Result<(String, String)> {
+    let name = input.parse::<syn::LitStr>()?.value();
+    if input.peek(Token![=>]) {
+        input.parse::<Token![=>]>()?;
+    } else {
+        input.parse::<Token![:]>()?;
+    }
+    let path = input.parse::<syn::LitStr>()?.value();
+    Ok((name, path))
+}
+
 fn with_field_parse(input: ParseStream<'_>) -> Result<(String, String)> {
     let interface = input.parse::<syn::LitStr>()?.value();
     input.parse::<Token![:]>()?;
@@ -402,3 +529,77 @@ fn with_field_parse(input: ParseStream<'_>) -> Result<(String, String)> {
 
     Ok((interface, buf))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use wit_bindgen_core::wit_parser::UnresolvedPackage;
+    use wit_component::{ComponentEncoder, StringEncoding};
+
+    fn source(path: &Path) -> Source {
+        Source {
+            paths: vec![path.to_str().unwrap().to_string()],
+            inline: None,
+            deps: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parse_wasm_sources() {
+        let dir = std::env::temp_dir().join(format!("wit-bindgen-macro-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut resolve = Resolve::default();
+        let wit = "
+            package my:test;
+
+            world guest {
+                import f: func();
+                export g: func() -> string;
+            }
+        ";
+        let pkg = resolve
+            .push(UnresolvedPackage::parse("test.wit".as_ref(), wit).unwrap())
+            .unwrap();
+        let world = resolve.select_world(pkg, None).unwrap();
+
+        let package = dir.join("package.wasm");
+        std::fs::write(
+            &package,
+            wit_component::encode(None, &resolve, pkg).unwrap(),
+        )
+        .unwrap();
+        let mut module = wit_component::dummy_module(&resolve, world);
+        wit_component::embed_component_metadata(&mut module, &resolve, world, StringEncoding::UTF8)
+            .unwrap();
+        let component = dir.join("component.wasm");
+        let encoded = ComponentEncoder::default()
+            .module(&module)
+            .unwrap()
+            .encode()
+            .unwrap();
+        std::fs::write(&component, encoded).unwrap();
+
+        for path in [&package, &component] {
+            let (decoded, main, files) = parse_source(&source(path)).unwrap();
+            assert_eq!(files, [path.clone()]);
+            let world = decoded.select_world(main, None).unwrap();
+            let mut files = Default::default();
+            Opts {
+                exports: [(wit_bindgen_rust::ExportKey::World, "Guest".to_string())].into(),
+                ..Default::default()
+            }
+            .build()
+            .generate(&decoded, world, &mut files)
+            .unwrap();
+            let (_, src) = files.iter().next().unwrap();
+            let src = std::str::from_utf8(src).unwrap();
+            assert!(src.contains("pub fn f()"), "{path:?}");
+            assert!(
+                src.contains("fn g() -> wit_bindgen::rt::string::String"),
+                "{path:?}"
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
-    dealias, int_repr, to_rust_ident, wasm_type, Direction, InterfaceGenerator, RustFlagsRepr,
+    dealias, int_repr, to_rust_ident, wasm_type, Direction, Identifier, InterfaceGenerator,
+    RustFlagsRepr,
 };
 use heck::*;
 use std::fmt::Write as _;
 use std::mem;
-use wit_bindgen_core::abi::{Bindgen, Instruction, LiftLower, WasmType};
+use wit_bindgen_core::abi::{self, AbiVariant, Bindgen, Instruction, LiftLower, WasmType};
 use wit_bindgen_core::{uwrite, uwriteln, wit_parser::*, Source};
 
 pub(super) struct FunctionBindgen<'a, 'b> {
@@ -15,9 +16,15 @@ pub(super) struct FunctionBindgen<'a, 'b> {
     block_storage: Vec<(Source, Vec<(String, String)>)>,
     tmp: usize,
     pub needs_cleanup_list: bool,
+    /// Whether conversions from remapped types are kept alive in
+    /// `repr_list` until the import returns.
+    pub needs_repr_list: bool,
+    /// Frees the strings and lists lent to an export once it returns.
+    borrowed_frees: Vec<String>,
     cleanup: Vec<(String, String)>,
     pub import_return_pointer_area_size: usize,
     pub import_return_pointer_area_align: usize,
+    pub async_: bool,
 }
 
 impl<'a, 'b> FunctionBindgen<'a, 'b> {
@@ -33,12 +40,61 @@ impl<'a, 'b> FunctionBindgen<'a, 'b> {
             block_storage: Vec::new(),
             tmp: 0,
             needs_cleanup_list: false,
+            needs_repr_list: false,
+            borrowed_frees: Vec::new(),
             cleanup: Vec::new(),
             import_return_pointer_area_size: 0,
             import_return_pointer_area_align: 0,
+            async_: false,
         }
     }
 
+    /// Exports with a post-return reset the arena of the `arena` option and
+    /// report their allocation counts there, and all others once their results are
+    /// lowered since those can't refer to the arena.
+    fn emit_call_end(&mut self, func: &Function) {
+        if !abi::guest_export_needs_post_return(self.gen.resolve, func) {
+            if self.gen.gen.opts.arena {
+                uwriteln!(self.src, "{}::arena::reset();", self.gen.gen.runtime_path());
+            }
+            let end_call = self.gen.alloc_stats_end_call(func);
+            self.push_str(&end_call);
+        }
+    }
+
+    /// The path to the functions which lift values from the host, which
+    /// always validate them with the `validate_lifts` option.
+    fn lift_rt(&self) -> String {
+        let rt = self.gen.gen.runtime_path();
+        if self.gen.gen.opts.validate_lifts {
+            format!("{rt}::checked")
+        } else {
+            rt.to_string()
+        }
+    }
+
+    /// The path to the functions which allocate lists for the host and take
+    /// ownership of those it allocated, which are those of the arena with
+    /// the `arena` option.
+    fn alloc_rt(&self) -> String {
+        let rt = self.gen.gen.runtime_path();
+        if self.gen.gen.opts.arena {
+            format!("{rt}::arena")
+        } else {
+            rt.to_string()
+        }
+    }
+
+    /// Frees memory given to an export by the host once it's been lifted,
+    /// unless it's in the arena which is reset at once instead.
+    fn dealloc(&self, ptr: &str, size: &str, align: usize) -> Option<String> {
+        if self.gen.gen.opts.arena {
+            return None;
+        }
+        let rt = self.gen.gen.runtime_path();
+        Some(format!("{rt}::dealloc({ptr}, {size}, {align});\n"))
+    }
+
     fn emit_cleanup(&mut self) {
         for (ptr, layout) in mem::take(&mut self.cleanup) {
             self.push_str(&format!(
@@ -279,14 +335,16 @@ impl Bindgen for FunctionBindgen<'_, '_> {
         &self.gen.sizes
     }
 
-    fn is_list_canonical(&self, resolve: &Resolve, ty: &Type) -> bool {
-        if !resolve.all_bits_valid(ty) {
-            return false;
-        }
-        match ty {
-            Type::Id(id) => !self.gen.gen.types.get(*id).has_resource,
-            _ => true,
-        }
+    fn is_list_canonical(&self, _resolve: &Resolve, ty: &Type) -> bool {
+        self.gen.is_list_canonical(ty)
+    }
+
+    fn is_remapped(&self, ty: TypeId) -> bool {
+        self.gen.gen.remapped_types.contains_key(&ty)
+    }
+
+    fn is_borrowed_param(&self, ty: &Type) -> bool {
+        self.gen.is_borrowed_export_param(ty)
     }
 
     fn emit(
@@ -367,7 +425,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             Instruction::CharFromI32 => {
                 results.push(format!(
                     "{}::char_lift({} as u32)",
-                    self.gen.gen.runtime_path(),
+                    self.lift_rt(),
                     operands[0]
                 ));
             }
@@ -380,7 +438,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             Instruction::BoolFromI32 => {
                 results.push(format!(
                     "{}::bool_lift({} as u8)",
-                    self.gen.gen.runtime_path(),
+                    self.lift_rt(),
                     operands[0]
                 ));
             }
@@ -402,7 +460,23 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                         i * 32
                     ));
                 }
-                results.push(result);
+                let tmp = self.tmp();
+                uwriteln!(self.src, "let flags{tmp} = {result};");
+                let rt = self.gen.gen.runtime_path();
+                // Unknown bits are kept unless lifts are validated, since
+                // they may be flags added in a later version of the WIT.
+                let check = if self.gen.gen.opts.validate_lifts {
+                    String::new()
+                } else {
+                    format!("{rt}::checked::ENABLED && ")
+                };
+                uwriteln!(
+                    self.src,
+                    "if {check}!{name}::all().contains(flags{tmp}) {{
+                        {rt}::checked::invalid_flags::<()>();
+                    }}"
+                );
+                results.push(format!("flags{tmp}"));
             }
 
             Instruction::HandleLower {
@@ -412,7 +486,11 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 let op = &operands[0];
                 let rt = self.gen.gen.runtime_path();
                 let resource = dealias(self.gen.resolve, *resource);
+                let typed = self.gen.gen.opts.typed_handles;
                 results.push(match self.gen.gen.resources[&resource].direction {
+                    Direction::Import if typed => {
+                        format!("({op}).into_inner().into_handle() as i32")
+                    }
                     Direction::Import => format!("({op}).into_handle() as i32"),
                     Direction::Export => format!("{rt}::Resource::into_handle({op}) as i32"),
                 });
@@ -433,6 +511,8 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                     Handle::Own(resource) => ("", resource),
                 };
                 let resource = dealias(resolve, *resource);
+                let rt = self.gen.gen.runtime_path();
+                let typed = self.gen.gen.opts.typed_handles;
 
                 results.push(
                     if let Direction::Export = self.gen.gen.resources[&resource].direction {
@@ -443,7 +523,14 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                                     .as_deref()
                                     .unwrap()
                                     .to_upper_camel_case();
-                                format!("&*({op} as u32 as usize as *const {name})")
+                                let rep = format!(
+                                    "{rt}::Resource::<{name}>::lift_borrow({op} as u32 as usize)"
+                                );
+                                if typed {
+                                    format!("{rt}::Borrow::new({rep})")
+                                } else {
+                                    rep
+                                }
                             }
                             Handle::Own(_) => {
                                 let name = self.gen.type_path(resource, true);
@@ -452,11 +539,80 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                         }
                     } else {
                         let name = self.gen.type_path(resource, true);
-                        format!("{prefix}{name}::from_handle({op} as u32)")
+                        let value = format!("{prefix}{name}::from_handle({op} as u32)");
+                        match handle {
+                            // A struct expression, unlike a call, extends
+                            // the lifetime of the borrowed temporary handle,
+                            // which is dropped once the export returns.
+                            Handle::Borrow(_) if typed => {
+                                format!("{rt}::Borrow {{ rep: {value} }}")
+                            }
+                            Handle::Own(_) if typed => format!("{rt}::Own::new({value})"),
+                            _ => value,
+                        }
                     },
                 );
             }
 
+            // Futures and streams are only handles which the runtime doesn't
+            // own, so lowering one just passes its index along.
+            Instruction::FutureLower { .. } | Instruction::StreamLower { .. } => {
+                let op = &operands[0];
+                results.push(format!("({op}).handle() as i32"));
+            }
+
+            Instruction::FutureLift { .. } => {
+                let op = &operands[0];
+                let rt = self.gen.gen.runtime_path();
+                results.push(format!("{rt}::FutureHandle::from_handle({op} as u32)"));
+            }
+
+            Instruction::StreamLift { .. } => {
+                let op = &operands[0];
+                let rt = self.gen.gen.runtime_path();
+                results.push(format!("{rt}::StreamHandle::from_handle({op} as u32)"));
+            }
+
+            Instruction::LowerRemapped { ty, realloc } => {
+                let op = &operands[0];
+                let repr = self.gen.type_path(*ty, true);
+                let root = self.gen.path_to_root();
+                let tmp = self.tmp();
+                let result = format!("repr{tmp}");
+                let lower = format!("{root}Lower::<{repr}>::lower(&{op})");
+                if realloc.is_none()
+                    && !self.block_storage.is_empty()
+                    && self.gen.gen.types.get(*ty).has_list
+                {
+                    // Lowering lists without `realloc` borrows from the
+                    // converted value, which would otherwise be dropped at the
+                    // end of this block before the import is called.
+                    self.needs_repr_list = true;
+                    uwriteln!(
+                        self.src,
+                        "
+                            let {result} = {rt}::boxed::Box::new({lower});
+                            let {result}_ptr: *const {repr} = &*{result};
+                            repr_list.push({result});
+                            let {result} = &*{result}_ptr;
+                        ",
+                        rt = self.gen.gen.runtime_path(),
+                    );
+                } else {
+                    uwriteln!(self.src, "let {result} = {lower};");
+                }
+                results.push(result);
+            }
+            Instruction::LiftRemapped { ty } => {
+                let path = &self.gen.gen.remapped_types[ty];
+                let repr = self.gen.type_path(*ty, true);
+                let root = self.gen.path_to_root();
+                results.push(format!(
+                    "<{path} as {root}Lift<{repr}>>::lift({})",
+                    operands[0]
+                ));
+            }
+
             Instruction::RecordLower { ty, record, .. } => {
                 self.record_lower(*ty, record, &operands[0], results);
             }
@@ -526,16 +682,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 };
                 uwriteln!(self.src, "let v{tmp} = match {op0} {{");
                 for (i, (case, block)) in variant.cases.iter().zip(blocks).enumerate() {
-                    if i == variant.cases.len() - 1 {
-                        uwriteln!(
-                            self.src,
-                            "n => {{
-                                debug_assert_eq!(n, {i}, \"invalid enum discriminant\");\
-                            "
-                        );
-                    } else {
-                        uwriteln!(self.src, "{i} => {{");
-                    }
+                    uwriteln!(self.src, "{i} => {{");
                     let case_name = case.name.to_upper_camel_case();
                     if case.ty.is_none() {
                         uwriteln!(self.src, "{name}::{case_name}");
@@ -545,6 +692,13 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                     }
                     uwriteln!(self.src, "}}");
                 }
+                // Other discriminants are rejected even without validation,
+                // which costs nothing over matching on them.
+                uwriteln!(
+                    self.src,
+                    "_ => {}::checked::invalid_enum_discriminant(),",
+                    self.gen.gen.runtime_path()
+                );
                 uwriteln!(self.src, "}};");
                 results.push(format!("v{tmp}"));
             }
@@ -579,7 +733,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                         }}
                         _ => {rt}::invalid_enum_discriminant(),
                     }}",
-                    rt = self.gen.gen.runtime_path(),
+                    rt = self.lift_rt(),
                 ));
             }
 
@@ -618,7 +772,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                         }}
                         _ => {rt}::invalid_enum_discriminant(),
                     }}",
-                    rt = self.gen.gen.runtime_path(),
+                    rt = self.lift_rt(),
                 ));
             }
 
@@ -641,28 +795,38 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 let len = format!("len{}", tmp);
                 if realloc.is_none() {
                     self.push_str(&format!("let {} = {};\n", val, operands[0]));
+                    self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
+                    self.push_str(&format!("let {} = {}.len() as i32;\n", len, val));
                 } else {
                     let op0 = operands.pop().unwrap();
-                    self.push_str(&format!("let {} = ({}).into_boxed_slice();\n", val, op0));
-                }
-                self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
-                self.push_str(&format!("let {} = {}.len() as i32;\n", len, val));
-                if realloc.is_some() {
-                    self.push_str(&format!("::core::mem::forget({});\n", val));
+                    uwriteln!(
+                        self.src,
+                        "let ({ptr}, {len}) = {}::list_lower({op0});",
+                        self.alloc_rt()
+                    );
                 }
                 results.push(ptr);
                 results.push(len);
             }
 
-            Instruction::ListCanonLift { .. } => {
+            Instruction::ListCanonLift {
+                element, borrowed, ..
+            } => {
                 let tmp = self.tmp();
                 let len = format!("len{}", tmp);
                 self.push_str(&format!("let {} = {} as usize;\n", len, operands[1]));
-                let result = format!(
-                    "Vec::from_raw_parts({} as *mut _, {1}, {1})",
-                    operands[0], len
-                );
-                results.push(result);
+                let ptr = &operands[0];
+                if *borrowed {
+                    let size = self.gen.sizes.size(element);
+                    let align = self.gen.sizes.align(element);
+                    let dealloc = self.dealloc(ptr, &format!("{len} * {size}"), align);
+                    self.borrowed_frees.extend(dealloc);
+                    results.push(format!(
+                        "::core::slice::from_raw_parts({ptr} as *const _, {len})"
+                    ));
+                } else {
+                    results.push(format!("{}::list_lift({ptr}, {len})", self.alloc_rt()));
+                }
             }
 
             Instruction::StringLower { realloc } => {
@@ -672,36 +836,43 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 let len = format!("len{}", tmp);
                 if realloc.is_none() {
                     self.push_str(&format!("let {} = {};\n", val, operands[0]));
+                    self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
+                    self.push_str(&format!("let {} = {}.len() as i32;\n", len, val));
                 } else {
-                    let op0 = format!("{}.into_bytes()", operands[0]);
-                    self.push_str(&format!("let {} = ({}).into_boxed_slice();\n", val, op0));
-                }
-                self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
-                self.push_str(&format!("let {} = {}.len() as i32;\n", len, val));
-                if realloc.is_some() {
-                    self.push_str(&format!("::core::mem::forget({});\n", val));
+                    uwriteln!(
+                        self.src,
+                        "let ({ptr}, {len}) = {}::list_lower({}.into_bytes());",
+                        self.alloc_rt(),
+                        operands[0]
+                    );
                 }
                 results.push(ptr);
                 results.push(len);
             }
 
-            Instruction::StringLift => {
+            Instruction::StringLift { borrowed } => {
                 let tmp = self.tmp();
                 let len = format!("len{}", tmp);
                 uwriteln!(self.src, "let {len} = {} as usize;", operands[1]);
-                uwriteln!(
-                    self.src,
-                    "let bytes{tmp} = Vec::from_raw_parts({} as *mut _, {len}, {len});",
-                    operands[0],
-                );
-                if self.gen.gen.opts.raw_strings {
-                    results.push(format!("bytes{tmp}"));
+                let ptr = &operands[0];
+                let result = if *borrowed {
+                    let dealloc = self.dealloc(ptr, &len, 1);
+                    self.borrowed_frees.extend(dealloc);
+                    let bytes = format!("::core::slice::from_raw_parts({ptr} as *const u8, {len})");
+                    if self.gen.gen.opts.raw_strings {
+                        bytes
+                    } else {
+                        format!("{}::str_lift({bytes})", self.lift_rt())
+                    }
                 } else {
-                    results.push(format!(
-                        "{}::string_lift(bytes{tmp})",
-                        self.gen.gen.runtime_path()
-                    ));
-                }
+                    let bytes = format!("{}::list_lift({ptr}, {len})", self.alloc_rt());
+                    if self.gen.gen.opts.raw_strings {
+                        bytes
+                    } else {
+                        format!("{}::string_lift({bytes})", self.lift_rt())
+                    }
+                };
+                results.push(result);
             }
 
             Instruction::ListLower { element, realloc } => {
@@ -721,8 +892,15 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 self.push_str(&format!(
                     "let {layout} = alloc::Layout::from_size_align_unchecked({vec}.len() * {size}, {align});\n",
                 ));
+                // Lists handed to the caller are freed in post-return, so
+                // they're allocated with the runtime, or in the arena which
+                // is reset then.
+                let alloc = match realloc {
+                    Some(_) => format!("{}::list_alloc", self.alloc_rt()),
+                    None => "alloc::alloc".to_string(),
+                };
                 self.push_str(&format!(
-                    "let {result} = if {layout}.size() != 0\n{{\nlet ptr = alloc::alloc({layout});\n",
+                    "let {result} = if {layout}.size() != 0\n{{\nlet ptr = {alloc}({layout});\n",
                 ));
                 self.push_str(&format!(
                     "if ptr.is_null()\n{{\nalloc::handle_alloc_error({layout});\n}}\nptr\n}}",
@@ -771,16 +949,53 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 uwriteln!(self.src, "{result}.push(e{tmp});");
                 uwriteln!(self.src, "}}");
                 results.push(result);
-                self.push_str(&format!(
-                    "{rt}::dealloc({base}, ({len} as usize) * {size}, {align});\n",
-                    rt = self.gen.gen.runtime_path(),
-                ));
+                if let Some(dealloc) =
+                    self.dealloc(&base, &format!("({len} as usize) * {size}"), align)
+                {
+                    self.push_str(&dealloc);
+                }
             }
 
             Instruction::IterElem { .. } => results.push("e".to_string()),
 
             Instruction::IterBasePointer => results.push("base".to_string()),
 
+            Instruction::CallWasm { name, sig, .. } if self.async_ => {
+                // Async imports take an extra pointer for the direct result,
+                // if any, and return the status of the call which is then
+                // awaited before results are read.
+                let mut params = sig.params.clone();
+                if !sig.results.is_empty() {
+                    params.push(WasmType::I32);
+                }
+                let func = self.declare_import(
+                    self.gen.wasm_import_module.unwrap(),
+                    &format!("[async]{name}"),
+                    &params,
+                    &[WasmType::I32],
+                );
+                let mut operands = operands.clone();
+                if let Some(result) = sig.results.first() {
+                    uwriteln!(
+                        self.src,
+                        "let mut ret = ::core::mem::MaybeUninit::<{}>::uninit();",
+                        wasm_type(*result)
+                    );
+                    operands.push("ret.as_mut_ptr() as i32".to_string());
+                }
+                uwriteln!(
+                    self.src,
+                    "let status = {func}({});\n\
+                     {}::async_support::await_result(status).await;",
+                    operands.join(", "),
+                    self.gen.gen.runtime_path(),
+                );
+                if !sig.results.is_empty() {
+                    self.push_str("let ret = ret.assume_init();\n");
+                    results.push("ret".to_string());
+                }
+            }
+
             Instruction::CallWasm { name, sig, .. } => {
                 let func = self.declare_import(
                     self.gen.wasm_import_module.unwrap(),
@@ -801,6 +1016,29 @@ impl Bindgen for FunctionBindgen<'_, '_> {
             }
 
             Instruction::CallInterface { func, .. } => {
+                // Strings and lists which are borrowed are freed once the
+                // call returns, rather than by their owner.
+                let borrowed = mem::take(&mut self.borrowed_frees);
+                // Methods take `&self` rather than a typed borrow.
+                if self.gen.gen.opts.typed_handles && matches!(func.kind, FunctionKind::Method(_)) {
+                    operands[0] = format!("({}).get()", operands[0]);
+                }
+                let trace = self.gen.gen.opts.trace;
+                if trace {
+                    let mut values = Vec::new();
+                    for ((_, ty), op) in func.params.iter().zip(operands.iter_mut()) {
+                        let tmp = self.tmp();
+                        uwriteln!(self.src, "let param{tmp} = {op};");
+                        *op = format!("param{tmp}");
+                        if self.gen.is_opaque_to_trace(ty) {
+                            values.push(format!("&{}::trace::Opaque", self.gen.gen.runtime_path()));
+                        } else {
+                            values.push(format!("&{op}"));
+                        }
+                    }
+                    let call = self.gen.trace_event(func, "Call", &values);
+                    self.push_str(&call);
+                }
                 self.let_results(func.results.len(), results);
                 match &func.kind {
                     FunctionKind::Freestanding => {
@@ -823,7 +1061,7 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                     FunctionKind::Constructor(ty) => {
                         self.gen.mark_resource_owned(*ty);
                         self.push_str(&format!(
-                            "Own{0}::new(<_{0}Impl as Guest{0}>::new",
+                            "Own{0}::new(::core::convert::From::from(<_{0}Impl as Guest{0}>::new",
                             resolve.types[*ty]
                                 .name
                                 .as_deref()
@@ -835,16 +1073,68 @@ impl Bindgen for FunctionBindgen<'_, '_> {
                 self.push_str("(");
                 self.push_str(&operands.join(", "));
                 self.push_str(")");
+                if self.async_ {
+                    self.push_str(".await");
+                }
                 if let FunctionKind::Constructor(_) = &func.kind {
-                    self.push_str(")");
+                    self.push_str("))");
                 }
                 self.push_str(";\n");
+                for dealloc in borrowed {
+                    self.push_str(&dealloc);
+                }
+                if trace {
+                    let values = results.iter().map(|r| format!("&{r}")).collect::<Vec<_>>();
+                    let ret = self.gen.trace_event(func, "Return", &values);
+                    self.push_str(&ret);
+                }
             }
 
-            Instruction::Return { amt, .. } => {
+            // Async exports hand their results to the `[task-return]`
+            // intrinsic rather than returning them, and then immediately
+            // clean up as the host has copied them out by then.
+            Instruction::Return { amt, func } if self.async_ && !self.gen.in_import => {
                 self.emit_cleanup();
+                let module = match self.gen.identifier {
+                    Identifier::Interface(_, key) => resolve.name_world_key(key),
+                    Identifier::World(_) => "$root".to_string(),
+                };
+                let sig = resolve.wasm_signature(AbiVariant::GuestExport, func);
+                let task_return = self.declare_import(
+                    &format!("[export]{module}"),
+                    &format!("[task-return]{}", func.name),
+                    &sig.results,
+                    &[],
+                );
+                match amt {
+                    0 => uwriteln!(self.src, "{task_return}();"),
+                    _ => {
+                        uwriteln!(self.src, "let ret = {};", operands[0]);
+                        uwriteln!(self.src, "{task_return}(ret);");
+                        if abi::guest_export_needs_post_return(resolve, func) {
+                            let name_snake = func.name.to_snake_case().replace('.', "_");
+                            uwriteln!(self.src, "__post_return_{name_snake}(ret);");
+                        }
+                    }
+                }
+                self.emit_call_end(func);
+            }
+
+            Instruction::Return { amt, func } => {
+                self.emit_cleanup();
+                if !self.gen.in_import {
+                    self.emit_call_end(func);
+                }
                 match amt {
                     0 => {}
+                    // Imported constructors return `Self` rather than an
+                    // `Own` of it.
+                    1 if self.gen.gen.opts.typed_handles
+                        && self.gen.in_import
+                        && matches!(func.kind, FunctionKind::Constructor(_)) =>
+                    {
+                        uwriteln!(self.src, "({}).into_inner()", operands[0]);
+                    }
                     1 => {
                         self.push_str(&operands[0]);
                         self.push_str("\n");
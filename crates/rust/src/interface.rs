@@ -1,7 +1,8 @@
 use crate::bindgen::FunctionBindgen;
 use crate::{
-    dealias, int_repr, to_rust_ident, to_upper_camel_case, wasm_type, Direction, ExportKey, FnSig,
-    Identifier, InterfaceName, Ownership, RustFlagsRepr, RustWasm, TypeMode,
+    dealias, int_repr, to_rust_ident, to_upper_camel_case, wasm_type, Direction, ExportKey,
+    ExportOwnership, FnSig, Identifier, InterfaceName, Ownership, RustFlagsRepr, RustWasm,
+    TypeMode,
 };
 use anyhow::Result;
 use heck::*;
@@ -24,7 +25,7 @@ pub struct InterfaceGenerator<'a> {
 }
 
 impl InterfaceGenerator<'_> {
-    fn export_key(&self, item: Option<&str>) -> ExportKey {
+    pub(super) fn export_key(&self, item: Option<&str>) -> ExportKey {
         let base = match self.identifier {
             Identifier::World(_) => ExportKey::World,
             Identifier::Interface(_, WorldKey::Name(n)) => ExportKey::Name(n.to_string()),
@@ -120,6 +121,7 @@ impl InterfaceGenerator<'_> {
             let mut sig = FnSig {
                 use_item_name: true,
                 private: true,
+                async_: self.gen.opts.unstable_async,
                 ..Default::default()
             };
             if let FunctionKind::Method(_) = &func.kind {
@@ -145,6 +147,9 @@ impl InterfaceGenerator<'_> {
                 "use {path_to_root}{impl_name} as {local_impl_name};"
             );
 
+            if self.gen.opts.unstable_async {
+                uwriteln!(self.src, "#[allow(async_fn_in_trait)]");
+            }
             uwriteln!(self.src, "pub trait {trait_name} {{");
             for method in methods {
                 self.src.push_str(&method);
@@ -155,10 +160,67 @@ impl InterfaceGenerator<'_> {
         Ok(())
     }
 
-    pub fn generate_imports<'a>(&mut self, funcs: impl Iterator<Item = &'a Function>) {
-        for func in funcs {
+    pub fn generate_imports<'a>(&mut self, funcs: impl Iterator<Item = &'a Function> + Clone) {
+        for func in funcs.clone() {
             self.generate_guest_import(func);
         }
+        if self.gen.opts.mock {
+            self.generate_mock_host(funcs);
+        }
+    }
+
+    /// Generates the `Host` trait which imports call on non-wasm targets in
+    /// `mock` mode, along with `set_host` to install an implementation.
+    fn generate_mock_host<'a>(&mut self, funcs: impl Iterator<Item = &'a Function>) {
+        let funcs = funcs
+            .filter(|func| !self.gen.skip.contains(&func.name))
+            .collect::<Vec<_>>();
+        if funcs.is_empty() {
+            return;
+        }
+
+        self.src.push_str(
+            "/// The imports of this interface, which are called on non-wasm\n\
+             /// targets instead of the host.\n\
+             #[cfg(not(target_arch = \"wasm32\"))]\n\
+             pub trait Host {\n",
+        );
+        for func in funcs {
+            let sig = FnSig {
+                private: true,
+                self_arg: Some("&self".into()),
+                ..Default::default()
+            };
+            // Methods are named after the full name of the function, such as
+            // `method_blob_read`, to keep those of resources apart.
+            let func = &Function {
+                name: mock_host_method(func),
+                ..func.clone()
+            };
+            self.print_docs_and_params(func, TypeMode::AllBorrowed("'_"), &sig);
+            match func.kind {
+                FunctionKind::Constructor(id) => {
+                    let name = self.resolve.types[id].name.as_ref().unwrap();
+                    uwrite!(self.src, " -> {}", to_upper_camel_case(name));
+                }
+                _ => self.print_results(&func.results, TypeMode::Owned),
+            }
+            self.src.push_str(";\n");
+        }
+        self.src.push_str("}\n");
+
+        let rt = self.gen.runtime_path();
+        uwriteln!(
+            self.src,
+            "
+                /// Installs the implementation of this interface's imports
+                /// for the current thread.
+                #[cfg(not(target_arch = \"wasm32\"))]
+                pub fn set_host(host: {rt}::boxed::Box<dyn Host>) {{
+                    {rt}::mock::set_host(host)
+                }}
+            "
+        );
     }
 
     pub fn finish(&mut self) -> String {
@@ -182,7 +244,7 @@ impl InterfaceGenerator<'_> {
         mem::take(&mut self.src).into()
     }
 
-    fn path_to_root(&self) -> String {
+    pub(super) fn path_to_root(&self) -> String {
         let mut path_to_root = String::new();
 
         if let Identifier::Interface(_, key) = self.identifier {
@@ -244,7 +306,10 @@ impl InterfaceGenerator<'_> {
             return;
         }
 
-        let mut sig = FnSig::default();
+        let mut sig = FnSig {
+            async_: self.gen.opts.unstable_async,
+            ..Default::default()
+        };
         let param_mode = TypeMode::AllBorrowed("'_");
         match func.kind {
             FunctionKind::Freestanding => {}
@@ -269,9 +334,39 @@ impl InterfaceGenerator<'_> {
             ",
             rt = self.gen.runtime_path()
         ));
+        let trace = self.gen.opts.trace;
+        if trace {
+            let values = params.iter().map(|p| format!("&{p}")).collect::<Vec<_>>();
+            let call = self.trace_event(func, "Call", &values);
+            self.src.push_str(&call);
+        }
+        if self.gen.opts.mock {
+            let rt = self.gen.runtime_path();
+            let mut params = params.clone();
+            // The `Host` takes the receiver of a method as any other borrow.
+            if self.gen.opts.typed_handles && matches!(func.kind, FunctionKind::Method(_)) {
+                params[0] = format!("{rt}::Borrow::new({})", params[0]);
+            }
+            uwriteln!(
+                self.src,
+                "
+                    #[cfg(not(target_arch = \"wasm32\"))]
+                    {ret} {rt}::mock::host::<dyn Host>().{method}({params});
+                    #[cfg(target_arch = \"wasm32\")]
+                ",
+                // Traced calls go on to report the host's results.
+                ret = if trace { "let ret =" } else { "return" },
+                method = to_rust_ident(&mock_host_method(func)),
+                params = params.join(", "),
+            );
+        }
+        if trace {
+            self.src.push_str("let ret = ");
+        }
         self.src.push_str("unsafe {\n");
 
         let mut f = FunctionBindgen::new(self, params);
+        f.async_ = f.gen.gen.opts.unstable_async;
         abi::call(
             f.gen.resolve,
             AbiVariant::GuestImport,
@@ -281,6 +376,7 @@ impl InterfaceGenerator<'_> {
         );
         let FunctionBindgen {
             needs_cleanup_list,
+            needs_repr_list,
             src,
             import_return_pointer_area_size,
             import_return_pointer_area_align,
@@ -290,6 +386,13 @@ impl InterfaceGenerator<'_> {
         if needs_cleanup_list {
             self.src.push_str("let mut cleanup_list = Vec::new();\n");
         }
+        if needs_repr_list {
+            uwriteln!(
+                self.src,
+                "let mut repr_list: Vec<{rt}::boxed::Box<dyn ::core::any::Any>> = Vec::new();",
+                rt = self.gen.runtime_path()
+            );
+        }
         if import_return_pointer_area_size > 0 {
             uwrite!(
                 self.src,
@@ -303,6 +406,17 @@ impl InterfaceGenerator<'_> {
         self.src.push_str(&String::from(src));
 
         self.src.push_str("}\n");
+        if trace {
+            let values = match func.results.len() {
+                0 => Vec::new(),
+                1 => vec!["&ret".to_string()],
+                n => (0..n).map(|i| format!("&ret.{i}")).collect(),
+            };
+            self.src.push_str(";\n");
+            let ret = self.trace_event(func, "Return", &values);
+            self.src.push_str(&ret);
+            self.src.push_str("ret\n");
+        }
         self.src.push_str("}\n");
 
         match func.kind {
@@ -324,7 +438,13 @@ impl InterfaceGenerator<'_> {
             Identifier::World(_) => None,
         };
         let export_prefix = self.gen.opts.export_prefix.as_deref().unwrap_or("");
-        let export_name = func.core_export_name(wasm_module_export_name.as_deref());
+        let async_ = self.gen.opts.unstable_async;
+        let export_name = if async_ {
+            async_export_name(func, wasm_module_export_name.as_deref(), "[async]")
+        } else {
+            func.core_export_name(wasm_module_export_name.as_deref())
+                .into_owned()
+        };
         uwrite!(
             self.src,
             "
@@ -345,6 +465,7 @@ impl InterfaceGenerator<'_> {
         self.src.push_str(")");
 
         match sig.results.len() {
+            _ if async_ => self.src.push_str(" -> i32"),
             0 => {}
             1 => {
                 uwrite!(self.src, " -> {}", wasm_type(sig.results[0]));
@@ -379,6 +500,7 @@ impl InterfaceGenerator<'_> {
         );
 
         let mut f = FunctionBindgen::new(self, params);
+        f.async_ = async_;
         abi::call(
             f.gen.resolve,
             AbiVariant::GuestExport,
@@ -392,40 +514,95 @@ impl InterfaceGenerator<'_> {
             ..
         } = f;
         assert!(!needs_cleanup_list);
-        self.src.push_str(&String::from(src));
-        self.src.push_str("}\n");
+        if async_ {
+            // The body of an async export runs as a task on the executor in
+            // the runtime, with further progress driven by the host invoking
+            // the `[callback]` export below.
+            uwriteln!(
+                self.src,
+                "{rt}::async_support::first_poll(async move {{\n{src}}})",
+                rt = self.gen.runtime_path(),
+                src = String::from(src),
+            );
+            self.src.push_str("}\n");
+            let export_prefix = self.gen.opts.export_prefix.as_deref().unwrap_or("");
+            let callback_name =
+                async_export_name(func, wasm_module_export_name.as_deref(), "[callback]");
+            uwrite!(
+                self.src,
+                "
+                    #[doc(hidden)]
+                    #[export_name = \"{export_prefix}{callback_name}\"]
+                    #[allow(non_snake_case)]
+                    unsafe extern \"C\" fn __callback_{name_snake}(\
+                        ctx: i32, event: i32, handle: i32, status: i32,\
+                    ) -> i32 {{
+                        {rt}::async_support::callback(ctx, event, handle, status)
+                    }}
+                ",
+                rt = self.gen.runtime_path(),
+            );
+        } else {
+            self.src.push_str(&String::from(src));
+            self.src.push_str("}\n");
+        }
 
         if abi::guest_export_needs_post_return(self.resolve, func) {
             let export_prefix = self.gen.opts.export_prefix.as_deref().unwrap_or("");
+            // Async exports invoke post-return themselves after
+            // `[task-return]`, so it's not exported in that case and must be
+            // visible to the export above.
+            if !async_ {
+                uwrite!(
+                    self.src,
+                    "
+                        const _: () = {{
+                        #[doc(hidden)]
+                        #[export_name = \"{export_prefix}cabi_post_{export_name}\"]
+                    "
+                );
+            }
             uwrite!(
                 self.src,
                 "
-                    const _: () = {{
-                    #[doc(hidden)]
-                    #[export_name = \"{export_prefix}cabi_post_{export_name}\"]
                     #[allow(non_snake_case)]
                     unsafe extern \"C\" fn __post_return_{name_snake}(\
                 "
             );
             let mut params = Vec::new();
             for (i, result) in sig.results.iter().enumerate() {
-                let name = format!("arg{}", i);
+                // The results aren't looked at when resetting an arena.
+                let name = if self.gen.opts.arena {
+                    format!("_arg{i}")
+                } else {
+                    format!("arg{i}")
+                };
                 uwrite!(self.src, "{name}: {},", wasm_type(*result));
                 params.push(name);
             }
             self.src.push_str(") {\n");
 
-            let mut f = FunctionBindgen::new(self, params);
-            abi::post_return(f.gen.resolve, func, &mut f);
-            let FunctionBindgen {
-                needs_cleanup_list,
-                src,
-                ..
-            } = f;
-            assert!(!needs_cleanup_list);
-            self.src.push_str(&String::from(src));
+            // With an arena everything the results point to is freed at once
+            // instead of walking them.
+            if self.gen.opts.arena {
+                uwriteln!(self.src, "{}::arena::reset();", self.gen.runtime_path());
+            } else {
+                let mut f = FunctionBindgen::new(self, params);
+                abi::post_return(f.gen.resolve, func, &mut f);
+                let FunctionBindgen {
+                    needs_cleanup_list,
+                    src,
+                    ..
+                } = f;
+                assert!(!needs_cleanup_list);
+                self.src.push_str(&src);
+            }
+            let end_call = self.alloc_stats_end_call(func);
+            self.src.push_str(&end_call);
             self.src.push_str("}\n");
-            self.src.push_str("};\n");
+            if !async_ {
+                self.src.push_str("};\n");
+            }
         }
     }
 
@@ -476,6 +653,7 @@ impl InterfaceGenerator<'_> {
             let mut sig = FnSig {
                 use_item_name: true,
                 private: true,
+                async_: self.gen.opts.unstable_async,
                 ..Default::default()
             };
             if let FunctionKind::Method(_) = &func.kind {
@@ -489,6 +667,65 @@ impl InterfaceGenerator<'_> {
         self.src.push_str("}\n");
     }
 
+    /// Returns a skeleton implementation of the trait for `resource`, or of
+    /// `Guest` for freestanding functions, on a new struct `ty`.
+    ///
+    /// This is used for `wit-bindgen rust --scaffold`, where the skeleton is
+    /// placed in a module which imports everything in this interface.
+    pub fn generate_scaffold(
+        &mut self,
+        resource: Option<TypeId>,
+        ty: &str,
+        funcs: &[&Function],
+    ) -> String {
+        let trait_name = match resource {
+            Some(id) => format!(
+                "Guest{}",
+                self.resolve.types[id]
+                    .name
+                    .as_deref()
+                    .unwrap()
+                    .to_upper_camel_case()
+            ),
+            None => "Guest".to_string(),
+        };
+        let mut methods = Vec::new();
+        for &func in funcs {
+            if self.gen.skip.contains(&func.name) {
+                continue;
+            }
+            let mut sig = FnSig {
+                use_item_name: true,
+                private: true,
+                async_: self.gen.opts.unstable_async,
+                ..Default::default()
+            };
+            if let FunctionKind::Method(_) = &func.kind {
+                sig.self_arg = Some("&self".into());
+                sig.self_is_first_param = true;
+            }
+            let prev = mem::take(&mut self.src);
+            self.print_signature(func, TypeMode::Owned, &sig);
+            let printed = String::from(mem::replace(&mut self.src, prev));
+            let (docs, sig) = printed
+                .trim_end()
+                .rsplit_once('\n')
+                .unwrap_or(("", &printed));
+            let mut method = String::new();
+            for line in docs.lines() {
+                uwriteln!(method, "    {}", line.trim());
+            }
+            uwriteln!(method, "    {} {{", tidy_signature(sig.trim()));
+            uwriteln!(method, "        unimplemented!()");
+            uwriteln!(method, "    }}");
+            methods.push(method);
+        }
+        format!(
+            "pub struct {ty};\n\nimpl {trait_name} for {ty} {{\n{}}}\n",
+            methods.join("\n")
+        )
+    }
+
     fn rustdoc(&mut self, docs: &Docs) {
         let docs = match &docs.contents {
             Some(docs) => docs,
@@ -601,7 +838,11 @@ impl InterfaceGenerator<'_> {
             self.push_str(&name);
             params.push(name);
             self.push_str(": ");
-            self.print_ty(param, param_mode);
+            if self.is_borrowed_export_param(param) {
+                self.print_borrowed_export_param(param);
+            } else {
+                self.print_ty(param, param_mode);
+            }
             self.push_str(",");
         }
         self.push_str(")");
@@ -685,6 +926,22 @@ impl InterfaceGenerator<'_> {
     fn print_tyid(&mut self, id: TypeId, mode: TypeMode) {
         let info = self.info(id);
         let lt = self.lifetime_for(&info, mode);
+        if let Some(path) = self.gen.remapped_types.get(&id).cloned() {
+            // Remapped types are never generic, so they're borrowed wherever
+            // the WIT type would have been, which also keeps the lifetime of
+            // any types containing them in use.
+            if info.has_list {
+                if let TypeMode::AllBorrowed(lt) | TypeMode::HandlesBorrowed(lt) = mode {
+                    self.push_str("&");
+                    if lt != "'_" {
+                        self.push_str(lt);
+                        self.push_str(" ");
+                    }
+                }
+            }
+            self.push_str(&path);
+            return;
+        }
         let ty = &self.resolve.types[id];
         if ty.name.is_some() {
             // If `mode` is borrowed then that means literal ownership of the
@@ -719,11 +976,16 @@ impl InterfaceGenerator<'_> {
             // If the type recursively owns data and it's a
             // variant/record/list, then we need to place the
             // lifetime parameter on the type as well.
-            if (info.has_list || info.has_borrow_handle)
-                && !info.has_own_handle
-                && needs_generics(self.resolve, &ty.kind)
-            {
-                self.print_generics(lt);
+            //
+            // Types containing borrowed handles always carry a lifetime, so if
+            // one isn't otherwise available the elided lifetime is spelled
+            // out, which `async fn` signatures require.
+            if needs_generics(self.resolve, &ty.kind) {
+                if info.has_borrow_handle {
+                    self.print_generics(lt.or(Some("'_")));
+                } else if info.has_list && !info.has_own_handle {
+                    self.print_generics(lt);
+                }
             }
 
             return;
@@ -751,7 +1013,11 @@ impl InterfaceGenerator<'_> {
             }
         }
 
-        match &ty.kind {
+        self.print_anonymous_type(&ty.kind, mode);
+    }
+
+    fn print_anonymous_type(&mut self, kind: &TypeDefKind, mode: TypeMode) {
+        match kind {
             TypeDefKind::List(t) => self.print_list(t, mode),
 
             TypeDefKind::Option(t) => {
@@ -793,27 +1059,41 @@ impl InterfaceGenerator<'_> {
             TypeDefKind::Enum(_) => {
                 panic!("unsupported anonymous type reference: enum")
             }
+            // The payloads of futures and streams are never borrowed, since
+            // only a handle is passed along with the call.
             TypeDefKind::Future(ty) => {
-                self.push_str("Future<");
-                self.print_optional_ty(ty.as_ref(), mode);
+                self.push_str(&format!("{}::FutureHandle<", self.gen.runtime_path()));
+                self.print_optional_ty(ty.as_ref(), TypeMode::Owned);
                 self.push_str(">");
             }
             TypeDefKind::Stream(stream) => {
-                self.push_str("Stream<");
-                self.print_optional_ty(stream.element.as_ref(), mode);
+                self.push_str(&format!("{}::StreamHandle<", self.gen.runtime_path()));
+                self.print_optional_ty(stream.element.as_ref(), TypeMode::Owned);
                 self.push_str(",");
-                self.print_optional_ty(stream.end.as_ref(), mode);
+                self.print_optional_ty(stream.end.as_ref(), TypeMode::Owned);
                 self.push_str(">");
             }
 
             TypeDefKind::Handle(Handle::Own(ty)) => {
                 self.mark_resource_owned(*ty);
-                self.print_ty(&Type::Id(*ty), mode);
+                if self.gen.opts.typed_handles && !self.is_exported_resource(*ty) {
+                    self.push_str(&format!("{}::Own<", self.gen.runtime_path()));
+                    self.print_ty(&Type::Id(*ty), mode);
+                    self.push_str(">");
+                } else {
+                    self.print_ty(&Type::Id(*ty), mode);
+                }
             }
 
             TypeDefKind::Handle(Handle::Borrow(ty)) => {
-                self.push_str("&");
-                if let TypeMode::AllBorrowed(lt) | TypeMode::HandlesBorrowed(lt) = mode {
+                let lt = match mode {
+                    TypeMode::AllBorrowed(lt) | TypeMode::HandlesBorrowed(lt) => lt,
+                    TypeMode::Owned => "'_",
+                };
+                if self.gen.opts.typed_handles {
+                    self.push_str(&format!("{}::Borrow<{lt}, ", self.gen.runtime_path()));
+                } else {
+                    self.push_str("&");
                     if lt != "'_" {
                         self.push_str(lt);
                         self.push_str(" ");
@@ -833,6 +1113,9 @@ impl InterfaceGenerator<'_> {
                 } else {
                     self.print_ty(&Type::Id(*ty), mode);
                 }
+                if self.gen.opts.typed_handles {
+                    self.push_str(">");
+                }
             }
 
             TypeDefKind::Type(t) => self.print_ty(t, mode),
@@ -905,6 +1188,11 @@ impl InterfaceGenerator<'_> {
         if !info.owned && !info.borrowed {
             return Vec::new();
         }
+        // The representation of a remapped type is only ever converted to or
+        // from an owned value.
+        if self.gen.remapped_types.contains_key(&ty) {
+            return vec![(self.result_name(ty), TypeMode::Owned)];
+        }
         let mut result = Vec::new();
 
         // Prioritize generating an "owned" type. This is done to simplify
@@ -931,6 +1219,47 @@ impl InterfaceGenerator<'_> {
         result
     }
 
+    /// Returns whether the Rust type for `ty` implements `Default`.
+    ///
+    /// This is the case for records made up of such types, but not for
+    /// variants or results which have no natural default case, nor for
+    /// remapped or `with` types, which might not implement it.
+    fn has_default(&self, ty: &Type) -> bool {
+        let id = match ty {
+            Type::Id(id) => *id,
+            _ => return true,
+        };
+        if self.info(id).has_foreign {
+            return false;
+        }
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Record(record) => record.fields.iter().all(|f| self.has_default(&f.ty)),
+            // The standard library only implements `Default` for tuples of up
+            // to 12 elements.
+            TypeDefKind::Tuple(tuple) => {
+                tuple.types.len() <= 12 && tuple.types.iter().all(|t| self.has_default(t))
+            }
+            TypeDefKind::Flags(_) | TypeDefKind::List(_) | TypeDefKind::Option(_) => true,
+            TypeDefKind::Type(ty) => self.has_default(ty),
+            _ => false,
+        }
+    }
+
+    /// Returns the serde derives for a type if the `serde` option is enabled.
+    ///
+    /// Types which contain handles can't be serialized and are skipped, and
+    /// types borrowing their contents can only be serialized.
+    fn serde_derives(&self, info: &TypeInfo, lt: Option<&str>) -> Vec<String> {
+        if !self.gen.opts.serde || info.has_resource {
+            return Vec::new();
+        }
+        let mut derives = vec!["::serde::Serialize".to_string()];
+        if lt.is_none() {
+            derives.push("::serde::Deserialize".to_string());
+        }
+        derives
+    }
+
     fn print_typedef_record(
         &mut self,
         id: TypeId,
@@ -960,12 +1289,20 @@ impl InterfaceGenerator<'_> {
                 self.push_str("#[component(record)]\n");
             }
             let mut derives = additional_derives.clone();
+            let serde = self.serde_derives(&info, lt);
+            let renames = !serde.is_empty();
+            derives.extend(serde);
             if info.is_copy() {
                 self.push_str("#[repr(C)]\n");
                 derives.extend(["Copy", "Clone"].into_iter().map(|s| s.to_string()));
             } else if info.is_clone() {
                 derives.insert("Clone".to_string());
             }
+            let mut builtin = comparison_derives(&info);
+            if record.fields.iter().all(|f| self.has_default(&f.ty)) {
+                builtin.push("Default");
+            }
+            extend_derives(&mut derives, builtin);
             if !derives.is_empty() {
                 self.push_str("#[derive(");
                 self.push_str(&derives.into_iter().collect::<Vec<_>>().join(", "));
@@ -979,6 +1316,9 @@ impl InterfaceGenerator<'_> {
                 if derive_component {
                     self.push_str(&format!("#[component(name = \"{}\")]\n", field.name));
                 }
+                if renames && to_rust_ident(&field.name) != field.name {
+                    self.push_str(&format!("#[serde(rename = \"{}\")]\n", field.name));
+                }
                 self.push_str("pub ");
                 self.push_str(&to_rust_ident(&field.name));
                 self.push_str(": ");
@@ -1105,11 +1445,15 @@ impl InterfaceGenerator<'_> {
                 self.push_str(&format!("#[component({})]\n", derive_component));
             }
             let mut derives = additional_derives.clone();
+            let serde = self.serde_derives(&info, lt);
+            let renames = !serde.is_empty();
+            derives.extend(serde);
             if info.is_copy() {
                 derives.extend(["Copy", "Clone"].into_iter().map(|s| s.to_string()));
             } else if info.is_clone() {
                 derives.insert("Clone".to_string());
             }
+            extend_derives(&mut derives, comparison_derives(&info));
             if !derives.is_empty() {
                 self.push_str("#[derive(");
                 self.push_str(&derives.into_iter().collect::<Vec<_>>().join(", "));
@@ -1121,10 +1465,15 @@ impl InterfaceGenerator<'_> {
             for (case_name, component_name, docs, payload) in cases.clone() {
                 self.rustdoc(docs);
                 if derive_component.is_some() {
-                    if let Some(n) = component_name {
+                    if let Some(n) = &component_name {
                         self.push_str(&format!("#[component(name = \"{}\")] ", n));
                     }
                 }
+                if renames {
+                    if let Some(n) = &component_name {
+                        self.push_str(&format!("#[serde(rename = \"{}\")] ", n));
+                    }
+                }
                 self.push_str(&case_name);
                 if let Some(ty) = payload {
                     self.push_str("(");
@@ -1273,11 +1622,11 @@ impl InterfaceGenerator<'_> {
             .iter()
             .cloned()
             .collect();
-        derives.extend(
-            ["Clone", "Copy", "PartialEq", "Eq"]
-                .into_iter()
-                .map(|s| s.to_string()),
-        );
+        derives.extend(["Clone", "Copy"].into_iter().map(|s| s.to_string()));
+        extend_derives(&mut derives, comparison_derives(&info));
+        let serde = self.serde_derives(&info, None);
+        let renames = !serde.is_empty();
+        derives.extend(serde);
         self.push_str("#[derive(");
         self.push_str(&derives.into_iter().collect::<Vec<_>>().join(", "));
         self.push_str(")]\n");
@@ -1285,11 +1634,16 @@ impl InterfaceGenerator<'_> {
         for case in enum_.cases.iter() {
             self.rustdoc(&case.docs);
             self.push_str(&case_attr(case));
+            if renames {
+                self.push_str(&format!("#[serde(rename = \"{}\")] ", case.name));
+            }
             self.push_str(&case.name.to_upper_camel_case());
             self.push_str(",\n");
         }
         self.push_str("}\n");
 
+        self.print_enum_conversions(id, &name, enum_);
+
         // Auto-synthesize an implementation of the standard `Error` trait for
         // error-looking types based on their name.
         if info.error {
@@ -1297,19 +1651,6 @@ impl InterfaceGenerator<'_> {
             self.push_str(&name);
             self.push_str("{\n");
 
-            self.push_str("pub fn name(&self) -> &'static str {\n");
-            self.push_str("match self {\n");
-            for case in enum_.cases.iter() {
-                self.push_str(&name);
-                self.push_str("::");
-                self.push_str(&case.name.to_upper_camel_case());
-                self.push_str(" => \"");
-                self.push_str(case.name.as_str());
-                self.push_str("\",\n");
-            }
-            self.push_str("}\n");
-            self.push_str("}\n");
-
             self.push_str("pub fn message(&self) -> &'static str {\n");
             self.push_str("match self {\n");
             for case in enum_.cases.iter() {
@@ -1366,8 +1707,210 @@ impl InterfaceGenerator<'_> {
                     .cases
                     .iter()
                     .map(|c| (c.name.to_upper_camel_case(), None)),
-            )
+            );
+            uwriteln!(
+                self.src,
+                "
+                    impl ::core::fmt::Display for {name} {{
+                        fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {{
+                            f.write_str(self.name())
+                        }}
+                    }}
+                "
+            );
+        }
+    }
+
+    /// Enums are converted to and from the WIT names of their cases and their
+    /// discriminants. Error enums keep their own `Display` which includes the
+    /// discriminant, so that's printed by the caller.
+    fn print_enum_conversions(&mut self, id: TypeId, name: &str, enum_: &Enum) {
+        let rt = self.gen.runtime_path();
+        let wit_name = self.resolve.types[id].name.as_deref().unwrap_or(name);
+        let mut all = String::new();
+        let mut names = String::new();
+        let mut parse = String::new();
+        let mut discriminants = String::new();
+        for (i, case) in enum_.cases.iter().enumerate() {
+            let case_name = case.name.to_upper_camel_case();
+            let wit = &case.name;
+            uwrite!(all, "{name}::{case_name}, ");
+            uwriteln!(names, "{name}::{case_name} => \"{wit}\",");
+            uwriteln!(parse, "\"{wit}\" => Ok({name}::{case_name}),");
+            uwriteln!(discriminants, "{i} => Ok({name}::{case_name}),");
+        }
+        let count = enum_.cases.len();
+        uwriteln!(
+            self.src,
+            r#"
+                impl {name} {{
+                    /// All cases, in the order they're declared in WIT.
+                    pub const ALL: [{name}; {count}] = [{all}];
+
+                    /// Returns an iterator over all cases.
+                    pub fn iter() -> impl Iterator<Item = {name}> {{
+                        Self::ALL.into_iter()
+                    }}
+
+                    /// Returns the WIT name of this case.
+                    pub fn name(&self) -> &'static str {{
+                        match self {{
+                            {names}
+                        }}
+                    }}
+                }}
+
+                impl ::core::str::FromStr for {name} {{
+                    type Err = {rt}::UnknownName;
+
+                    fn from_str(s: &str) -> Result<{name}, {rt}::UnknownName> {{
+                        match s {{
+                            {parse}
+                            _ => Err({rt}::UnknownName::new("{wit_name}", s)),
+                        }}
+                    }}
+                }}
+
+                impl ::core::convert::TryFrom<u32> for {name} {{
+                    type Error = {rt}::InvalidValue;
+
+                    fn try_from(value: u32) -> Result<{name}, {rt}::InvalidValue> {{
+                        match value {{
+                            {discriminants}
+                            _ => Err({rt}::InvalidValue::new("{wit_name}")),
+                        }}
+                    }}
+                }}
+
+                impl From<{name}> for u32 {{
+                    fn from(value: {name}) -> u32 {{
+                        value as u32
+                    }}
+                }}
+            "#
+        );
+    }
+
+    /// Flags are converted to and from the WIT names of the flags which are
+    /// set, separated by `|`, and their bits. Iterating them is already
+    /// provided by `bitflags`.
+    fn print_flags_conversions(&mut self, id: TypeId, name: &str, flags: &Flags) {
+        let rt = self.gen.runtime_path();
+        let wit_name = self.resolve.types[id].name.as_deref().unwrap_or(name);
+        let name = name.to_upper_camel_case();
+        let repr = RustFlagsRepr::new(flags);
+        let mut names = String::new();
+        let mut parse = String::new();
+        for flag in flags.flags.iter() {
+            let konst = flag.name.to_shouty_snake_case();
+            let wit = &flag.name;
+            uwrite!(names, "(\"{wit}\", {name}::{konst}), ");
+            uwriteln!(parse, "\"{wit}\" => {name}::{konst},");
         }
+        uwriteln!(
+            self.src,
+            r#"
+                impl {name} {{
+                    /// Returns an iterator over the WIT names of the flags
+                    /// which are set.
+                    pub fn names(&self) -> impl Iterator<Item = &'static str> {{
+                        let flags = *self;
+                        [{names}]
+                            .into_iter()
+                            .filter(move |(_, flag)| flags.contains(*flag))
+                            .map(|(name, _)| name)
+                    }}
+                }}
+
+                impl ::core::fmt::Display for {name} {{
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {{
+                        for (i, name) in self.names().enumerate() {{
+                            if i != 0 {{
+                                f.write_str(" | ")?;
+                            }}
+                            f.write_str(name)?;
+                        }}
+                        Ok(())
+                    }}
+                }}
+
+                impl ::core::str::FromStr for {name} {{
+                    type Err = {rt}::UnknownName;
+
+                    fn from_str(s: &str) -> Result<{name}, {rt}::UnknownName> {{
+                        let mut flags = {name}::empty();
+                        for name in s.split('|').map(str::trim).filter(|name| !name.is_empty()) {{
+                            flags |= match name {{
+                                {parse}
+                                _ => return Err({rt}::UnknownName::new("{wit_name}", name)),
+                            }};
+                        }}
+                        Ok(flags)
+                    }}
+                }}
+
+                impl ::core::convert::TryFrom<{repr}> for {name} {{
+                    type Error = {rt}::InvalidValue;
+
+                    fn try_from(bits: {repr}) -> Result<{name}, {rt}::InvalidValue> {{
+                        {name}::from_bits(bits).ok_or_else(|| {rt}::InvalidValue::new("{wit_name}"))
+                    }}
+                }}
+
+                impl From<{name}> for {repr} {{
+                    fn from(flags: {name}) -> {repr} {{
+                        flags.bits()
+                    }}
+                }}
+            "#
+        );
+    }
+
+    /// Flags are serialized as a list of the names of the flags which are set.
+    fn print_flags_serde(&mut self, name: &str, flags: &Flags) {
+        let name = name.to_upper_camel_case();
+        let rt = self.gen.runtime_path();
+        let mut serialize = String::new();
+        let mut deserialize = String::new();
+        let mut names = String::new();
+        for flag in flags.flags.iter() {
+            let konst = flag.name.to_shouty_snake_case();
+            let wit = &flag.name;
+            uwriteln!(
+                serialize,
+                "if self.contains(Self::{konst}) {{ seq.serialize_element(\"{wit}\")?; }}"
+            );
+            uwriteln!(deserialize, "\"{wit}\" => Self::{konst},");
+            uwrite!(names, "\"{wit}\", ");
+        }
+        uwriteln!(
+            self.src,
+            r#"
+                impl ::serde::Serialize for {name} {{
+                    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {{
+                        use ::serde::ser::SerializeSeq;
+                        let len = (self.bits() & Self::all().bits()).count_ones() as usize;
+                        let mut seq = serializer.serialize_seq(Some(len))?;
+                        {serialize}
+                        seq.end()
+                    }}
+                }}
+
+                impl<'de> ::serde::Deserialize<'de> for {name} {{
+                    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {{
+                        let names = <{rt}::vec::Vec<{rt}::string::String> as ::serde::Deserialize>::deserialize(deserializer)?;
+                        let mut flags = Self::empty();
+                        for name in names {{
+                            flags |= match name.as_str() {{
+                                {deserialize}
+                                _ => return Err(::serde::de::Error::unknown_variant(&name, &[{names}])),
+                            }};
+                        }}
+                        Ok(flags)
+                    }}
+                }}
+            "#
+        );
     }
 
     fn print_typedef_alias(&mut self, id: TypeId, ty: &Type, docs: &Docs) {
@@ -1411,6 +1954,22 @@ impl InterfaceGenerator<'_> {
         }
     }
 
+    /// Prints a named alias of a type which is otherwise only ever used
+    /// anonymously, such as a handle, future, or stream.
+    fn print_typedef_anonymous(&mut self, id: TypeId, docs: &Docs) {
+        let info = self.info(id);
+        let kind = &self.resolve.types[id].kind;
+        for (name, mode) in self.modes_of(id) {
+            self.rustdoc(docs);
+            self.push_str(&format!("pub type {name}"));
+            let lt = self.lifetime_for(&info, mode);
+            self.print_generics(lt);
+            self.push_str(" = ");
+            self.print_anonymous_type(kind, mode);
+            self.push_str(";\n");
+        }
+    }
+
     fn print_type_list(&mut self, id: TypeId, ty: &Type, docs: &Docs) {
         let info = self.info(id);
         for (name, mode) in self.modes_of(id) {
@@ -1427,7 +1986,9 @@ impl InterfaceGenerator<'_> {
     fn param_name(&self, ty: TypeId) -> String {
         let info = self.info(ty);
         let name = to_upper_camel_case(self.resolve.types[ty].name.as_ref().unwrap());
-        if self.uses_two_names(&info) {
+        if self.gen.remapped_types.contains_key(&ty) {
+            format!("{name}Repr")
+        } else if self.uses_two_names(&info) {
             format!("{}Param", name)
         } else {
             name
@@ -1437,7 +1998,9 @@ impl InterfaceGenerator<'_> {
     fn result_name(&self, ty: TypeId) -> String {
         let info = self.info(ty);
         let name = to_upper_camel_case(self.resolve.types[ty].name.as_ref().unwrap());
-        if self.uses_two_names(&info) {
+        if self.gen.remapped_types.contains_key(&ty) {
+            format!("{name}Repr")
+        } else if self.uses_two_names(&info) {
             format!("{}Result", name)
         } else if self.is_exported_resource(ty) {
             format!("Own{name}")
@@ -1567,7 +2130,33 @@ impl InterfaceGenerator<'_> {
     }
 
     fn info(&self, ty: TypeId) -> TypeInfo {
-        self.gen.types.get(ty)
+        let mut info = self.gen.types.get(ty);
+        if self.gen.remapped_reprs.contains(&ty) {
+            info.owned = true;
+        }
+        info
+    }
+
+    /// Returns the WIT name that the type `id`, named `name`, is defined
+    /// under, which for remapped types is that of their representation.
+    fn repr_name(&self, id: TypeId, name: &str) -> String {
+        if self.gen.remapped_types.contains_key(&id) {
+            format!("{name}-repr")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Defines `pub type {Name}` for the remapped type `id`, if it is one,
+    /// so its WIT name refers to the Rust type used in its place.
+    pub(super) fn print_remapped_alias(&mut self, id: TypeId) {
+        let Some(path) = self.gen.remapped_types.get(&id).cloned() else {
+            return;
+        };
+        let ty = &self.resolve.types[id];
+        let name = to_upper_camel_case(ty.name.as_ref().unwrap());
+        self.rustdoc(&ty.docs);
+        uwriteln!(self.src, "pub type {name} = {path};");
     }
 
     fn print_borrowed_slice(
@@ -1580,6 +2169,116 @@ impl InterfaceGenerator<'_> {
         self.print_rust_slice(mutbl, ty, lifetime, mode);
     }
 
+    /// Returns a statement reporting a call of `func`, or its return, to the
+    /// runtime's `trace` hook along with the given `&value` expressions.
+    pub(super) fn trace_event(&self, func: &Function, event: &str, values: &[String]) -> String {
+        let rt = self.gen.runtime_path();
+        let direction = if self.in_import { "Import" } else { "Export" };
+        let interface = self.interface_name_literal();
+        format!(
+            "{rt}::trace::emit({rt}::trace::Direction::{direction}, {rt}::trace::Event::{event}, {interface}, \"{}\", &[{}]);\n",
+            func.name,
+            values.join(", "),
+        )
+    }
+
+    /// Returns the call reporting the allocation counts of the export `func`
+    /// once it's over, which does nothing without the `alloc_stats` feature.
+    pub(super) fn alloc_stats_end_call(&self, func: &Function) -> String {
+        format!(
+            "{}::alloc_stats::end_call({}, \"{}\");\n",
+            self.gen.runtime_path(),
+            self.interface_name_literal(),
+            func.name,
+        )
+    }
+
+    /// The name of this interface as an `Option<&str>` expression, or `None`
+    /// for functions of the world.
+    fn interface_name_literal(&self) -> String {
+        match self.identifier {
+            Identifier::Interface(_, key) => {
+                format!("Some(\"{}\")", self.resolve.name_world_key(key))
+            }
+            Identifier::World(_) => "None".to_string(),
+        }
+    }
+
+    /// Returns whether an argument of type `ty` is traced as opaque, which is
+    /// the case for borrowed handles of exported resources since those are
+    /// the implementation's own types.
+    pub(super) fn is_opaque_to_trace(&self, ty: &Type) -> bool {
+        let id = match ty {
+            Type::Id(id) if !self.in_import => *id,
+            _ => return false,
+        };
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Type(t) => self.is_opaque_to_trace(t),
+            TypeDefKind::Handle(Handle::Borrow(resource)) => {
+                let resource = dealias(self.resolve, *resource);
+                self.gen.resources[&resource].direction == Direction::Export
+            }
+            _ => self.gen.types.get(id).has_borrow_handle,
+        }
+    }
+
+    /// Returns whether `ty`, the type of a parameter of an export, is passed
+    /// as a `&str` or `&[T]` borrowing from the caller's buffer.
+    pub(super) fn is_borrowed_export_param(&self, ty: &Type) -> bool {
+        if self.in_import
+            || self.gen.opts.unstable_async
+            || (self.gen.opts.export_ownership != ExportOwnership::Borrowing
+                && !self.gen.opts.arena)
+        {
+            return false;
+        }
+        match ty {
+            Type::String => true,
+            Type::Id(id) if self.gen.remapped_types.contains_key(id) => false,
+            Type::Id(id) => match &self.resolve.types[*id].kind {
+                TypeDefKind::Type(t) => self.is_borrowed_export_param(t),
+                TypeDefKind::List(element) => self.is_list_canonical(element),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn print_borrowed_export_param(&mut self, ty: &Type) {
+        match ty {
+            Type::String => self.print_borrowed_str("'_"),
+            Type::Id(id) => match &self.resolve.types[*id].kind {
+                TypeDefKind::Type(t) => self.print_borrowed_export_param(t),
+                TypeDefKind::List(element) => {
+                    self.print_borrowed_slice(false, element, "'_", TypeMode::Owned)
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns whether lists of `ty` have the same representation in Rust as
+    /// in the canonical ABI.
+    pub(super) fn is_list_canonical(&self, ty: &Type) -> bool {
+        if !self.resolve.all_bits_valid(ty) {
+            return false;
+        }
+        match ty {
+            Type::Id(id) => {
+                // Remapped types have a different layout than their WIT
+                // representation.
+                let mut live = LiveTypes::default();
+                live.add_type_id(self.resolve, *id);
+                !self.gen.types.get(*id).has_resource
+                    && !live
+                        .iter()
+                        .any(|id| self.gen.remapped_types.contains_key(&id))
+            }
+            _ => true,
+        }
+    }
+
     fn print_borrowed_str(&mut self, lifetime: &'static str) {
         self.push_str("&");
         if lifetime != "'_" {
@@ -1599,6 +2298,13 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.resolve
     }
 
+    fn types(&mut self, iface: InterfaceId) {
+        for (name, id) in self.resolve.interfaces[iface].types.iter() {
+            self.define_type(name, *id);
+            self.print_remapped_alias(*id);
+        }
+    }
+
     fn type_record(&mut self, id: TypeId, _name: &str, record: &Record, docs: &Docs) {
         self.print_typedef_record(id, record, docs, false);
     }
@@ -1648,6 +2354,31 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
                     }}
                 "#
             );
+            if self.gen.opts.mock {
+                uwriteln!(
+                    self.src,
+                    r#"
+                        #[cfg(not(target_arch = "wasm32"))]
+                        impl {camel} {{
+                            /// Creates a resource for a `Host` implementation
+                            /// which owns `state`.
+                            pub fn mock_new<T: 'static>(state: T) -> Self {{
+                                unsafe {{ Self::from_handle({rt}::mock::resource_new_state(state)) }}
+                            }}
+
+                            /// Returns the state this resource was created
+                            /// with by [`{camel}::mock_new`].
+                            ///
+                            /// # Panics
+                            ///
+                            /// Panics if the state isn't a `T`.
+                            pub fn mock_state<T: 'static>(&self) -> &T {{
+                                unsafe {{ {rt}::mock::resource_state(self.handle()) }}
+                            }}
+                        }}
+                    "#
+                );
+            }
             self.wasm_import_module.unwrap().to_string()
         } else {
             // Exported resources are represented as `Resource<T>` as opposed
@@ -1670,6 +2401,21 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
                 .lookup_export(&self.export_key(Some(name)))
                 .unwrap_or_else(|_| "ERROR".to_string());
             let path_to_root = self.path_to_root();
+            let representation = match self.export_key(Some(name)) {
+                ExportKey::Name(key) => self.gen.opts.resource_reps.get(&key).cloned(),
+                ExportKey::World => None,
+            }
+            .unwrap_or_else(|| format!("{rt}::rep::Boxed"));
+            let (native_new, native_rep) = if self.gen.opts.mock {
+                (
+                    format!(
+                        "return {rt}::mock::resource_new(rep, {rt}::Resource::<{camel}>::dtor);"
+                    ),
+                    format!("return {rt}::mock::resource_rep(handle);"),
+                )
+            } else {
+                ("unreachable!();".to_string(), "unreachable!();".to_string())
+            };
             uwriteln!(
                 self.src,
                 r#"
@@ -1683,9 +2429,11 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
                         }}
                     }};
                     unsafe impl {rt}::RustResource for {camel} {{
+                        type Rep = {representation};
+
                         unsafe fn new(rep: usize) -> u32 {{
                             #[cfg(not(target_arch = "wasm32"))]
-                            unreachable!();
+                            {native_new}
 
                             #[cfg(target_arch = "wasm32")]
                             {{
@@ -1700,7 +2448,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
 
                         unsafe fn rep(handle: u32) -> usize {{
                             #[cfg(not(target_arch = "wasm32"))]
-                            unreachable!();
+                            {native_rep}
 
                             #[cfg(target_arch = "wasm32")]
                             {{
@@ -1719,6 +2467,13 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
             format!("[export]{module}")
         };
 
+        // In `mock` mode both imported and exported resources are dropped
+        // through the emulated handle table.
+        let native_drop = if self.gen.opts.mock {
+            format!("return {rt}::mock::resource_drop(handle);")
+        } else {
+            "unreachable!();".to_string()
+        };
         uwriteln!(
             self.src,
             r#"
@@ -1726,7 +2481,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
                      #[inline]
                      unsafe fn drop(handle: u32) {{
                          #[cfg(not(target_arch = "wasm32"))]
-                         unreachable!();
+                         {native_drop}
 
                          #[cfg(target_arch = "wasm32")]
                          {{
@@ -1748,7 +2503,8 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.print_typedef_tuple(id, tuple, docs);
     }
 
-    fn type_flags(&mut self, _id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
+    fn type_flags(&mut self, id: TypeId, name: &str, flags: &Flags, docs: &Docs) {
+        let name = &self.repr_name(id, name);
         self.src.push_str(&format!(
             "{bitflags}::bitflags! {{\n",
             bitflags = self.gen.bitflags_path()
@@ -1756,7 +2512,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.rustdoc(docs);
         let repr = RustFlagsRepr::new(flags);
         self.src.push_str(&format!(
-            "#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]\npub struct {}: {repr} {{\n",
+            "#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]\npub struct {}: {repr} {{\n",
             name.to_upper_camel_case(),
         ));
         for (i, flag) in flags.flags.iter().enumerate() {
@@ -1769,6 +2525,10 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         }
         self.src.push_str("}\n");
         self.src.push_str("}\n");
+        self.print_flags_conversions(id, name, flags);
+        if self.gen.opts.serde {
+            self.print_flags_serde(name, flags);
+        }
     }
 
     fn type_variant(&mut self, id: TypeId, _name: &str, variant: &Variant, docs: &Docs) {
@@ -1784,6 +2544,7 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
     }
 
     fn type_enum(&mut self, id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
+        let name = &self.repr_name(id, name);
         self.print_typedef_enum(id, name, enum_, docs, &[], Box::new(|_| String::new()));
 
         let name = to_upper_camel_case(name);
@@ -1793,18 +2554,29 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
             let case = case.name.to_upper_camel_case();
             cases.push_str(&format!("{i} => {name}::{case},\n"));
         }
+        let rt = self.gen.runtime_path();
+        let (unchecked, rt) = if self.gen.opts.validate_lifts {
+            (String::new(), format!("{rt}::checked"))
+        } else {
+            (
+                format!(
+                    "if !{rt}::VALIDATE_LIFTS {{
+                        return ::core::mem::transmute(val);
+                    }}"
+                ),
+                rt.to_string(),
+            )
+        };
         uwriteln!(
             self.src,
             r#"
                 impl {name} {{
                     pub(crate) unsafe fn _lift(val: {repr}) -> {name} {{
-                        if !cfg!(debug_assertions) {{
-                            return ::core::mem::transmute(val);
-                        }}
+                        {unchecked}
 
                         match val {{
                             {cases}
-                            _ => panic!("invalid enum discriminant"),
+                            _ => {rt}::invalid_enum_discriminant(),
                         }}
                     }}
                 }}
@@ -1820,6 +2592,18 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.print_type_list(id, ty, docs);
     }
 
+    fn type_future(&mut self, id: TypeId, _name: &str, _ty: &Option<Type>, docs: &Docs) {
+        self.print_typedef_anonymous(id, docs);
+    }
+
+    fn type_stream(&mut self, id: TypeId, _name: &str, _stream: &Stream, docs: &Docs) {
+        self.print_typedef_anonymous(id, docs);
+    }
+
+    fn type_handle(&mut self, id: TypeId, _name: &str, _handle: &Handle, docs: &Docs) {
+        self.print_typedef_anonymous(id, docs);
+    }
+
     fn type_builtin(&mut self, _id: TypeId, name: &str, ty: &Type, docs: &Docs) {
         self.rustdoc(docs);
         self.src
@@ -1829,3 +2613,77 @@ impl<'a> wit_bindgen_core::InterfaceGenerator<'a> for InterfaceGenerator<'a> {
         self.src.push_str(";\n");
     }
 }
+
+/// Returns the comparison and hashing traits which can be derived for a type,
+/// namely everything but for types with handles, which can't be compared,
+/// types with remapped or `with` types, which might not implement them, and
+/// floats, which only have a partial order.
+fn comparison_derives(info: &TypeInfo) -> Vec<&'static str> {
+    let mut derives = Vec::new();
+    if info.is_partial_eq() {
+        derives.extend(["PartialEq", "PartialOrd"]);
+    }
+    if info.is_eq() {
+        derives.extend(["Eq", "Ord", "Hash"]);
+    }
+    derives
+}
+
+/// Adds `builtin` derives to `derives` unless they were already requested,
+/// possibly by their full path, through `additional_derive_attributes`.
+fn extend_derives(derives: &mut BTreeSet<String>, builtin: Vec<&str>) {
+    for derive in builtin {
+        let suffix = format!("::{derive}");
+        if !derives.iter().any(|d| d == derive || d.ends_with(&suffix)) {
+            derives.insert(derive.to_string());
+        }
+    }
+}
+
+/// Returns the name of the `Host` trait method for `func` in `mock` mode.
+fn mock_host_method(func: &Function) -> String {
+    func.name.to_snake_case().replace('.', "_")
+}
+
+/// Returns the core export name of `func` with `prefix` applied to the
+/// function's own name, such as `ns:pkg/iface#[async]name`.
+fn async_export_name(func: &Function, interface: Option<&str>, prefix: &str) -> String {
+    match interface {
+        Some(interface) => format!("{interface}#{prefix}{}", func.name),
+        None => format!("{prefix}{}", func.name),
+    }
+}
+
+/// Tidies up a signature printed for a trait, which has no spaces after its
+/// commas and a trailing comma after its parameters, to be read and edited.
+fn tidy_signature(sig: &str) -> String {
+    let sig = sig
+        .replace("wit_bindgen::rt::string::String", "String")
+        .replace("wit_bindgen::rt::vec::Vec::<", "Vec<");
+    let chars = sig.chars().collect::<Vec<_>>();
+    let open = sig.find('(').unwrap();
+    let mut depth = 0;
+    let mut close = open;
+    for (i, c) in chars.iter().enumerate().skip(open) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => continue,
+        }
+        if depth == 0 {
+            close = i;
+            break;
+        }
+    }
+    let mut tidy = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == ',' && i + 1 == close {
+            continue;
+        }
+        tidy.push(*c);
+        if *c == ',' && chars.get(i + 1) != Some(&')') {
+            tidy.push(' ');
+        }
+    }
+    tidy
+}
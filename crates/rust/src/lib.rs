@@ -14,6 +14,7 @@ use wit_bindgen_core::{
 
 mod bindgen;
 mod interface;
+mod scaffold;
 
 #[derive(Default, Copy, Clone, PartialEq, Eq)]
 enum Direction {
@@ -54,6 +55,13 @@ struct RustWasm {
     resources: HashMap<TypeId, ResourceInfo>,
     import_funcs_called: bool,
     with_name_counter: usize,
+    /// Rust types used in place of WIT types, from `Opts::remap`.
+    remapped_types: HashMap<TypeId, String>,
+    /// Types used within remapped types, whose owned versions are needed
+    /// to convert to and from the remapped types.
+    remapped_reprs: HashSet<TypeId>,
+    /// Modules implementing exported interfaces, for `Opts::scaffold`.
+    scaffold_modules: Vec<scaffold::ScaffoldModule>,
 }
 
 #[cfg(feature = "clap")]
@@ -162,6 +170,20 @@ pub struct Opts {
     #[cfg_attr(feature = "clap", arg(long, default_value_t = Ownership::Owning))]
     pub ownership: Ownership,
 
+    /// Whether parameters of exported functions are owned or borrowed.
+    ///
+    /// Valid values include:
+    ///
+    /// - `owning`: Parameters are lifted into owned values.
+    ///
+    /// - `borrowing`: Parameters which are strings or lists of plain data,
+    ///   such as `list<u8>`, are passed as `&str` and `&[T]` borrowing from
+    ///   the buffer the caller passed in, which is freed once the function
+    ///   returns. Other parameters, and those of async exports, are still
+    ///   owned.
+    #[cfg_attr(feature = "clap", arg(long, default_value_t = ExportOwnership::Owning))]
+    pub export_ownership: ExportOwnership,
+
     /// The optional path to the wit-bindgen runtime module to use.
     ///
     /// This defaults to `wit_bindgen::rt`.
@@ -184,6 +206,124 @@ pub struct Opts {
     /// Remapping of interface names to rust module names.
     #[cfg_attr(feature = "clap", arg(long, value_parser = parse_with, default_value = ""))]
     pub with: HashMap<String, String>,
+
+    /// Remapping of WIT types to existing Rust types, which are used
+    /// everywhere the WIT type appears.
+    ///
+    /// Types are named by their interface and name, such as
+    /// `ns:pkg/iface/name`, or just by their name if they're defined in the
+    /// world. The Rust type is named with a path that's valid in any module,
+    /// such as `uuid::Uuid` or `crate::Name`.
+    ///
+    /// The WIT type itself is still generated with a `Repr` suffix, and the
+    /// Rust type must implement the generated `Lower` and `Lift` traits to
+    /// convert to and from it. It must also implement `Debug` along with
+    /// the traits derived for types which contain it. It's an error to
+    /// remap a type which isn't found or which contains handles.
+    #[cfg_attr(feature = "clap", arg(long, value_parser = parse_with, default_value = ""))]
+    pub remap: HashMap<String, String>,
+
+    /// How the values of exported resources are stored, as a path to a type
+    /// implementing `wit_bindgen::rep::Representation` for each resource.
+    ///
+    /// Resources are named like in `exports`, such as `ns:pkg/iface/name`,
+    /// and those which aren't named here are boxed. The type is named with a
+    /// path that's valid in any module, such as `wit_bindgen::rep::RcShared`
+    /// or `crate::Slab` for a slab of the component's own.
+    #[cfg_attr(feature = "clap", arg(long, value_parser = parse_with, default_value = ""))]
+    pub resource_reps: HashMap<String, String>,
+
+    /// If true, generate async bindings: imported functions return
+    /// `impl Future` and exported traits are defined with `async fn`.
+    ///
+    /// This is unstable. Calls are driven by the executor in
+    /// `wit_bindgen::rt::async_support` using an early draft of the callback
+    /// ABI of the component model's async proposal, whose exact signatures and
+    /// codes are listed there. No released runtime implements that draft, and
+    /// it will change incompatibly along with the proposal.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub unstable_async: bool,
+
+    /// If true, on non-wasm targets imports call a `Host` trait object
+    /// installed with each interface's generated `set_host` function.
+    ///
+    /// This lets guests run natively, e.g. with `cargo test`, against fake
+    /// hosts. Resources are emulated in-process, which requires the `mock`
+    /// feature of the `wit-bindgen` crate.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub mock: bool,
+
+    /// If true, generated types implement `serde::Serialize` and
+    /// `serde::Deserialize`, which requires a dependency on `serde` with its
+    /// `derive` and `alloc` features.
+    ///
+    /// Types are represented as:
+    ///
+    /// * records as maps keyed by the WIT names of their fields,
+    /// * variants and enums as the WIT name of their case, with variant
+    ///   payloads externally tagged as `{"case-name": payload}`,
+    /// * flags as a list of the WIT names of the flags which are set,
+    /// * options, results, tuples and lists as serde does for Rust's
+    ///   `Option`, `Result`, tuples and `Vec`.
+    ///
+    /// Types containing resources or other handles don't implement either
+    /// trait, and types which borrow their contents, as used for parameters
+    /// of imports, only implement `Serialize`.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub serde: bool,
+
+    /// If true, values lifted from the host are always validated, and an
+    /// invalid one traps or calls the hook installed with
+    /// `wit_bindgen::set_invalid_lift_hook`.
+    ///
+    /// This covers UTF-8 strings, chars, bools, discriminants of enums,
+    /// options and results, and unknown bits of flags. Without it validation
+    /// is only done in debug builds or with the `validate_lifts` feature of
+    /// the `wit-bindgen` crate, and invalid values are otherwise undefined
+    /// behavior, except that unknown bits of flags are kept unless the
+    /// feature is enabled. Unknown discriminants of variants always trap.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub validate_lifts: bool,
+
+    /// If true, each call of an import and each call of an export is
+    /// reported along with its arguments and results to the hook installed
+    /// with `wit_bindgen::rt::trace::set_hook`.
+    ///
+    /// Values are formatted with `Debug`, except for borrowed handles of
+    /// exported resources.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub trace: bool,
+
+    /// If true, `borrow<T>` handles are `wit_bindgen::Borrow<'a, T>` and
+    /// `own<T>` handles of imported resources are `wit_bindgen::Own<T>`,
+    /// rather than `&T` and `T`.
+    ///
+    /// A borrow then can't be kept past the call it was passed to, and
+    /// moving an owned handle into an import is spelled out at the call.
+    /// Exported resources continue to be owned through their `Own*` types.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub typed_handles: bool,
+
+    /// If true, exports take their arguments from and lower their results
+    /// into the arena of the `arena` feature of the `wit-bindgen` crate,
+    /// which is reset once each export call is over instead of freeing
+    /// values one at a time.
+    ///
+    /// Strings and lists of canonical values given to exports are borrowed
+    /// from the arena, as with `export_ownership: Borrowing`, and other
+    /// owned lists and strings lifted from it are copied out. Bindings must
+    /// be generated with this option if and only if the feature is enabled,
+    /// which is checked when they're compiled.
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub arena: bool,
+
+    /// If true, a new crate implementing the world is generated instead of
+    /// bindings, as done by `wit-bindgen rust --scaffold`.
+    ///
+    /// Only the crate's `Cargo.toml` and `src` are generated, and the crate
+    /// expects the WIT it was generated from in its `wit` directory.
+    #[cfg_attr(feature = "clap", arg(skip))]
+    pub scaffold: bool,
 }
 
 impl Opts {
@@ -278,10 +418,11 @@ impl RustWasm {
             ExportKey::World => "world",
             ExportKey::Name(name) => name,
         };
-        if self.opts.exports.is_empty() {
-            bail!("no `exports` map provided in configuration but key is required for `{key}`")
+        Err(MissingExport {
+            key: key.to_string(),
+            no_exports: self.opts.exports.is_empty(),
         }
-        bail!("expected `exports` map to contain key `{key}`")
+        .into())
     }
 
     fn name_interface(
@@ -314,6 +455,90 @@ impl RustWasm {
 
         remapped
     }
+
+    /// Marks the types defined outside of the generated bindings through
+    /// `Opts::remap` or `Opts::with`, so that nothing containing them derives
+    /// comparison traits which they might not implement.
+    fn mark_foreign_types(&mut self, resolve: &Resolve, world: WorldId) {
+        for key in self.opts.remap.keys() {
+            if let Some(id) = find_type(resolve, world, key) {
+                self.types.mark_foreign(id);
+            }
+        }
+        let world = &resolve.worlds[world];
+        for (key, item) in world.imports.iter().chain(world.exports.iter()) {
+            let WorldItem::Interface(id) = item else {
+                continue;
+            };
+            if self.opts.with.contains_key(&resolve.name_world_key(key)) {
+                for (_, id) in resolve.interfaces[*id].types.iter() {
+                    self.types.mark_foreign(*id);
+                }
+            }
+        }
+    }
+
+    fn remap_types(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
+        let mut live = LiveTypes::default();
+        for (key, path) in self.opts.remap.iter() {
+            let Some(id) = find_type(resolve, world, key) else {
+                bail!("cannot remap `{key}` since no such type was found");
+            };
+            if self.types.get(id).has_resource {
+                bail!("cannot remap `{key}` to `{path}` since it contains handles");
+            }
+            live.add_type_id(resolve, id);
+            self.remapped_types.insert(id, path.clone());
+        }
+        self.remapped_reprs = live.iter().collect();
+        if self.remapped_types.is_empty() {
+            return Ok(());
+        }
+
+        self.src.push_str(
+            "
+            /// Converts a Rust type used in place of a WIT type into `R`, the
+            /// representation of the WIT type.
+            pub trait Lower<R> {
+                fn lower(&self) -> R;
+            }
+
+            impl<R, T: Lower<R> + ?Sized> Lower<R> for &T {
+                fn lower(&self) -> R {
+                    T::lower(self)
+                }
+            }
+
+            /// Converts `R`, the representation of a WIT type, into the Rust
+            /// type used in its place.
+            pub trait Lift<R> {
+                fn lift(repr: R) -> Self;
+            }
+            ",
+        );
+        Ok(())
+    }
+}
+
+/// Finds the type named by `key` in `Opts::remap`, either
+/// `ns:pkg/iface/name` or the name of a type in `world`.
+fn find_type(resolve: &Resolve, world: WorldId, key: &str) -> Option<TypeId> {
+    match key.rsplit_once('/') {
+        Some((interface, name)) => resolve.interfaces.iter().find_map(|(id, i)| {
+            if resolve.id_of(id).as_deref() == Some(interface) {
+                i.types.get(name).copied()
+            } else {
+                None
+            }
+        }),
+        None => resolve.worlds[world]
+            .imports
+            .iter()
+            .find_map(|(k, item)| match (k, item) {
+                (WorldKey::Name(n), WorldItem::Type(id)) if n == key => Some(*id),
+                _ => None,
+            }),
+    }
 }
 
 /// If the package `id` is the only package with its namespace/name combo
@@ -361,10 +586,52 @@ fn name_package_module(resolve: &Resolve, id: PackageId) -> String {
     format!("{base}{version}")
 }
 
+/// Returned when no implementation was specified for an export.
+#[derive(Debug)]
+struct MissingExport {
+    key: String,
+    no_exports: bool,
+}
+
+impl fmt::Display for MissingExport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = &self.key;
+        if self.no_exports {
+            write!(
+                f,
+                "no `exports` map provided in configuration but key is required for `{key}`"
+            )
+        } else {
+            write!(f, "expected `exports` map to contain key `{key}`")
+        }
+    }
+}
+
+impl std::error::Error for MissingExport {}
+
 impl WorldGenerator for RustWasm {
-    fn preprocess(&mut self, resolve: &Resolve, _world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
+        wit_bindgen_core::check_futures_and_streams(resolve, world)?;
         wit_bindgen_core::generated_preamble(&mut self.src, env!("CARGO_PKG_VERSION"));
+        self.mark_foreign_types(resolve, world);
         self.types.analyze(resolve);
+        self.remap_types(resolve, world)?;
+        if self.opts.scaffold {
+            self.prepare_scaffold(resolve, world);
+        }
+        Ok(())
+    }
+
+    fn cli_hint(&self, err: &anyhow::Error) -> Option<String> {
+        if !err.chain().any(|e| e.is::<MissingExport>()) {
+            return None;
+        }
+        Some(
+            "Specify export implementations using the `--exports` option.\n    \
+             For example: `--exports world=MyWorld,ns:pkg/iface=MyIface`\n    \
+             Alternatively, specify `--stubs` to generate stub implementations."
+                .to_string(),
+        )
     }
 
     fn import_interface(
@@ -451,6 +718,7 @@ impl WorldGenerator for RustWasm {
         let mut gen = self.interface(Identifier::World(world), Some("$root"), resolve, true);
         for (name, ty) in types {
             gen.define_type(name, *ty);
+            gen.print_remapped_alias(*ty);
         }
         let src = gen.finish();
         self.src.push_str(&src);
@@ -466,6 +734,9 @@ impl WorldGenerator for RustWasm {
     }
 
     fn finish(&mut self, resolve: &Resolve, world: WorldId, files: &mut Files) {
+        if self.opts.scaffold {
+            return self.finish_scaffold(resolve, world, files);
+        }
         let name = &resolve.worlds[world].name;
 
         let imports = mem::take(&mut self.import_modules);
@@ -514,6 +785,20 @@ impl WorldGenerator for RustWasm {
         ",
         );
 
+        // With the `arena` feature everything given to the component by
+        // `cabi_realloc` is in the arena, which bindings generated without
+        // the option would free one value at a time.
+        let (enabled, message) = if self.opts.arena {
+            ("", "bindings generated with the `arena` option require the `arena` feature of `wit-bindgen`")
+        } else {
+            ("!", "the `arena` feature of `wit-bindgen` requires bindings generated with the `arena` option")
+        };
+        let rt = self.runtime_path().to_string();
+        uwriteln!(
+            self.src,
+            "const _: () = assert!({enabled}{rt}::arena::ENABLED, \"{message}\");",
+        );
+
         if self.opts.stubs {
             self.src.push_str("\n#[derive(Debug)]\npub struct Stub;\n");
             let world_id = world;
@@ -693,6 +978,41 @@ impl fmt::Display for Ownership {
     }
 }
 
+/// How parameters of exported functions are passed, see
+/// `Opts::export_ownership`.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum ExportOwnership {
+    /// Parameters are lifted into owned values.
+    #[default]
+    Owning,
+
+    /// Strings and lists of plain data borrow from the caller's buffer.
+    Borrowing,
+}
+
+impl FromStr for ExportOwnership {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owning" => Ok(Self::Owning),
+            "borrowing" => Ok(Self::Borrowing),
+            _ => Err(format!(
+                "unrecognized export ownership: `{s}`; expected `owning` or `borrowing`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportOwnership {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ExportOwnership::Owning => "owning",
+            ExportOwnership::Borrowing => "borrowing",
+        })
+    }
+}
+
 #[derive(Default)]
 struct FnSig {
     async_: bool,
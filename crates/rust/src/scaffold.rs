@@ -0,0 +1,224 @@
+//! Generation of a new guest crate for `wit-bindgen rust --scaffold`.
+//!
+//! Rather than bindings this generates a crate which invokes `generate!` on
+//! its `wit` directory along with a module per exported interface, holding
+//! structs whose trait implementations are left to be filled in. The structs
+//! are named in the `exports` of the `generate!` invocation, and the same
+//! names are used while generating so that the skeletons match the traits.
+
+use crate::{
+    compute_module_path, group_by_resource, to_rust_ident, to_upper_camel_case, ExportKey,
+    Identifier, RustWasm,
+};
+use heck::*;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use wit_bindgen_core::{uwriteln, wit_parser::*, Files};
+
+/// An exported interface along with the name of the module which implements
+/// it in the crate.
+pub(crate) struct ScaffoldModule {
+    name: String,
+    key: WorldKey,
+    id: InterfaceId,
+}
+
+impl RustWasm {
+    /// Names a module for each exported interface and configures the structs
+    /// in them as the implementations of its exports.
+    pub(crate) fn prepare_scaffold(&mut self, resolve: &Resolve, world: WorldId) {
+        let mut modules = Vec::new();
+        for (key, item) in resolve.worlds[world].exports.iter() {
+            if let WorldItem::Interface(id) = item {
+                let name = match key {
+                    WorldKey::Name(name) => name,
+                    WorldKey::Interface(id) => resolve.interfaces[*id].name.as_ref().unwrap(),
+                };
+                modules.push(ScaffoldModule {
+                    name: to_rust_ident(name),
+                    key: key.clone(),
+                    id: *id,
+                });
+            }
+        }
+        // Interfaces from different packages may share a name, in which case
+        // their modules are named after their packages too.
+        let clashes = modules
+            .iter()
+            .map(|module| modules.iter().filter(|m| m.name == module.name).count() > 1)
+            .collect::<Vec<_>>();
+        for (i, clashes) in clashes.into_iter().enumerate() {
+            if let (true, WorldKey::Interface(id)) = (clashes, &modules[i].key) {
+                let pkg = resolve.interfaces[*id].package.unwrap();
+                let pkg = &resolve.packages[pkg].name;
+                modules[i].name = to_rust_ident(&format!(
+                    "{}-{}-{}",
+                    pkg.namespace,
+                    pkg.name,
+                    resolve.interfaces[*id].name.as_ref().unwrap()
+                ));
+            }
+        }
+
+        for module in modules.iter() {
+            let gen = self.interface(
+                Identifier::Interface(module.id, &module.key),
+                None,
+                resolve,
+                false,
+            );
+            let mut exports = Vec::new();
+            for (resource, _) in group_by_resource(resolve.interfaces[module.id].functions.values())
+            {
+                let resource_name = resource.map(|id| resolve.types[id].name.as_deref().unwrap());
+                let key = gen.export_key(resource_name);
+                let ty = scaffold_struct_name(resolve, module, resource);
+                exports.push((key, format!("{}::{ty}", module.name)));
+            }
+            self.opts.exports.extend(exports);
+        }
+        let has_funcs = resolve.worlds[world]
+            .exports
+            .values()
+            .any(|item| matches!(item, WorldItem::Function(_)));
+        if has_funcs {
+            self.opts
+                .exports
+                .insert(ExportKey::World, "Component".to_string());
+        }
+        self.scaffold_modules = modules;
+    }
+
+    /// Writes the crate's manifest, `src/lib.rs`, and a module for each
+    /// exported interface.
+    pub(crate) fn finish_scaffold(&mut self, resolve: &Resolve, world: WorldId, files: &mut Files) {
+        let world_name = &resolve.worlds[world].name;
+        let mut version = env!("CARGO_PKG_VERSION").split('.');
+        let (major, minor) = (version.next().unwrap(), version.next().unwrap());
+        files.push(
+            "Cargo.toml",
+            format!(
+                "\
+[package]
+name = \"{}\"
+version = \"0.1.0\"
+edition = \"2021\"
+
+[lib]
+crate-type = [\"cdylib\"]
+
+[dependencies]
+wit-bindgen = \"{major}.{minor}\"
+",
+                world_name.to_kebab_case()
+            )
+            .as_bytes(),
+        );
+
+        let exports = self
+            .opts
+            .exports
+            .iter()
+            .map(|(key, ty)| {
+                let key = match key {
+                    ExportKey::World => "world".to_string(),
+                    ExportKey::Name(name) => format!("\"{name}\""),
+                };
+                (key, ty)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let mut lib = String::new();
+        uwriteln!(lib, "wit_bindgen::generate!({{");
+        uwriteln!(lib, "    world: \"{world_name}\",");
+        uwriteln!(lib, "    exports: {{");
+        for (key, ty) in exports {
+            uwriteln!(lib, "        {key}: {ty},");
+        }
+        uwriteln!(lib, "    }},");
+        uwriteln!(lib, "}});");
+        let mut names = self
+            .scaffold_modules
+            .iter()
+            .map(|m| &m.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        if !names.is_empty() {
+            uwriteln!(lib, "");
+        }
+        for name in names {
+            uwriteln!(lib, "mod {name};");
+        }
+
+        let funcs = resolve.worlds[world]
+            .exports
+            .values()
+            .filter_map(|item| match item {
+                WorldItem::Function(func) => Some(func),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !funcs.is_empty() {
+            let mut gen = self.interface(Identifier::World(world), None, resolve, false);
+            let skeleton = gen.generate_scaffold(None, "Component", &funcs);
+            uwriteln!(lib, "\n{}", skeleton.trim_end());
+        }
+        files.push("src/lib.rs", lib.as_bytes());
+
+        for module in std::mem::take(&mut self.scaffold_modules) {
+            let mut path = compute_module_path(&module.key, resolve, true);
+            *path.last_mut().unwrap() = to_rust_ident(match &module.key {
+                WorldKey::Name(name) => name,
+                WorldKey::Interface(id) => resolve.interfaces[*id].name.as_ref().unwrap(),
+            });
+            let mut src = String::new();
+            uwriteln!(src, "use crate::{}::*;", path.join("::"));
+            let mut gen = self.interface(
+                Identifier::Interface(module.id, &module.key),
+                None,
+                resolve,
+                false,
+            );
+            for (resource, funcs) in
+                group_by_resource(resolve.interfaces[module.id].functions.values())
+            {
+                let ty = scaffold_struct_name(resolve, &module, resource);
+                let skeleton = gen.generate_scaffold(resource, &ty, &funcs);
+                uwriteln!(src, "\n{}", skeleton.trim_end());
+            }
+            files.push(
+                &format!("src/{}.rs", module.name),
+                format!("{}\n", src.trim_end()).as_bytes(),
+            );
+        }
+    }
+}
+
+/// Resources are implemented by structs of the same name, and freestanding
+/// functions by a struct named after the interface unless a resource already
+/// took that name.
+fn scaffold_struct_name(
+    resolve: &Resolve,
+    module: &ScaffoldModule,
+    resource: Option<TypeId>,
+) -> String {
+    let iface = &resolve.interfaces[module.id];
+    match resource {
+        Some(id) => to_upper_camel_case(resolve.types[id].name.as_deref().unwrap()),
+        None => {
+            let name = match &module.key {
+                WorldKey::Name(name) => name,
+                WorldKey::Interface(_) => iface.name.as_ref().unwrap(),
+            };
+            let name = name.to_upper_camel_case();
+            if iface
+                .types
+                .keys()
+                .any(|ty| ty.to_upper_camel_case() == name)
+            {
+                format!("{name}Impl")
+            } else {
+                name
+            }
+        }
+    }
+}
@@ -2,6 +2,10 @@
 
 mod codegen_tests {
     macro_rules! codegen_test {
+        // TODO: wit-component can't encode futures and streams into the
+        // component type yet, so generating bindings for them fails. Remove the
+        // following line once it can:
+        (future_stream_alias $name:tt $test:tt) => {};
         ($id:ident $name:tt $test:tt) => {
             mod $id {
                 wit_bindgen::generate!({
@@ -31,6 +35,78 @@ mod codegen_tests {
                     #[test]
                     fn works() {}
                 }
+
+                mod async_ {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        unstable_async,
+                        stubs,
+                        export_prefix: "[async-test]",
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod serde {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        serde,
+                        stubs,
+                        export_prefix: "[serde]",
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod export_borrowing {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        export_ownership: Borrowing,
+                        stubs,
+                        export_prefix: "[export-borrowing]",
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod validate_lifts {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        validate_lifts,
+                        stubs,
+                        export_prefix: "[validate-lifts]",
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod trace {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        trace,
+                        stubs,
+                        export_prefix: "[trace]",
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
+
+                mod typed_handles {
+                    wit_bindgen::generate!({
+                        path: $test,
+                        typed_handles,
+                        stubs,
+                        export_prefix: "[typed-handles]",
+                    });
+
+                    #[test]
+                    fn works() {}
+                }
             }
 
         };
@@ -400,3 +476,781 @@ mod with {
         my::inline::bar::bar(&msg);
     }
 }
+
+mod async_ {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface fetch {
+                get: func(url: string) -> list<u8>;
+            }
+
+            world proxy {
+                import fetch;
+                export handle: func(url: string) -> u32;
+            }
+        ",
+        exports: {
+            world: Component,
+        },
+        unstable_async,
+    });
+
+    struct Component;
+
+    impl Guest for Component {
+        // Test that exports are `async fn` which may await imports, which
+        // borrow their arguments.
+        async fn handle(url: String) -> u32 {
+            let body = my::inline::fetch::get(&url).await;
+            body.len() as u32
+        }
+    }
+}
+
+mod futures_and_streams {
+    use wit_bindgen_core::wit_parser::{Resolve, UnresolvedPackage};
+    use wit_bindgen_core::Files;
+
+    fn generate(wit: &str) -> anyhow::Result<()> {
+        let mut resolve = Resolve::default();
+        let pkg = resolve.push(UnresolvedPackage::parse("test.wit".as_ref(), wit)?)?;
+        let world = resolve.select_world(pkg, None)?;
+        let mut gen = wit_bindgen_rust::Opts::default().build();
+        gen.generate(&resolve, world, &mut Files::default())
+    }
+
+    // Test that futures and streams are an error rather than a panic while
+    // the component type can't encode them.
+    #[test]
+    fn unsupported() {
+        let err = generate(
+            "
+            package my:inline;
+
+            interface channels {
+                type reply = future<u32>;
+                open: func(size: u32) -> stream<u8, string>;
+            }
+
+            world guest {
+                import channels;
+            }
+            ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "world `guest` uses `reply`, a future, which can't be embedded in the component type yet"
+        );
+
+        let err = generate(
+            "
+            package my:inline;
+
+            world guest {
+                export open: func(size: u32) -> stream<u8, string>;
+            }
+            ",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "world `guest` uses a stream, which can't be embedded in the component type yet"
+        );
+    }
+}
+
+mod serde {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface types {
+                record point {
+                    x: s32,
+                    %type: string,
+                }
+                variant shape {
+                    circle(u32),
+                    poly(list<point>),
+                    empty,
+                }
+                enum color { light-red, blue }
+                flags perms { read, write, exec-all }
+                resource thing;
+                record holder {
+                    thing: thing,
+                }
+
+                draw: func(p: point, s: shape, c: color, f: perms) -> option<point>;
+                take: func(h: holder);
+            }
+
+            world guest {
+                import types;
+            }
+        ",
+        serde,
+    });
+
+    use my::inline::types::{Color, Perms, Point, Shape};
+    use serde_json::json;
+
+    fn round_trip<T>(value: T, expected: serde_json::Value)
+    where
+        T: ::serde::Serialize + ::serde::de::DeserializeOwned + std::fmt::Debug + PartialEq,
+    {
+        let json = serde_json::to_value(&value).unwrap();
+        assert_eq!(json, expected);
+        assert_eq!(serde_json::from_value::<T>(json).unwrap(), value);
+    }
+
+    #[test]
+    fn representation() {
+        let point = Point {
+            x: -1,
+            type_: "a".to_string(),
+        };
+        round_trip(point.clone(), json!({"x": -1, "type": "a"}));
+        round_trip(Shape::Circle(3), json!({"circle": 3}));
+        round_trip(
+            Shape::Poly(vec![point]),
+            json!({"poly": [{"x": -1, "type": "a"}]}),
+        );
+        round_trip(Shape::Empty, json!("empty"));
+        round_trip(Color::LightRed, json!("light-red"));
+        round_trip(Perms::READ | Perms::EXEC_ALL, json!(["read", "exec-all"]));
+        round_trip(Perms::empty(), json!([]));
+        assert!(serde_json::from_value::<Perms>(json!(["nope"])).is_err());
+    }
+}
+
+mod derives {
+    use std::collections::{BTreeSet, HashMap};
+
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface types {
+                record key {
+                    name: string,
+                    tags: list<u32>,
+                    kind: option<kind>,
+                    perms: perms,
+                }
+                variant kind { a, b(string) }
+                flags perms { read, write }
+                record point {
+                    x: float64,
+                    y: float64,
+                }
+                record picked {
+                    choice: result<u32>,
+                }
+
+                get: func(k: key, p: point) -> tuple<key, point, picked>;
+            }
+
+            world guest {
+                import types;
+            }
+        ",
+    });
+
+    use my::inline::types::{Key, Kind, Perms, Point};
+
+    #[test]
+    fn works() {
+        let key = Key {
+            name: "a".to_string(),
+            tags: vec![1],
+            kind: Some(Kind::B("b".to_string())),
+            perms: Perms::READ,
+        };
+        let mut map = HashMap::new();
+        map.insert(key.clone(), 1);
+        assert_eq!(map[&key], 1);
+
+        let set = BTreeSet::from([key.clone(), Key::default()]);
+        assert_eq!(set.first(), Some(&Key::default()));
+        assert!(Kind::A < Kind::B(String::new()));
+
+        let point = Point { x: 1.0, y: 0.5 };
+        assert_eq!(point, Point { x: 1.0, y: 0.5 });
+        assert!(Point::default() < point);
+    }
+}
+
+// Types defined outside of the bindings, through `remap` or `with`, which
+// don't implement comparison traits, so that nothing containing them may
+// derive those.
+mod foreign_derives {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface blobs {
+                record blob {
+                    data: list<u8>,
+                }
+            }
+
+            interface types {
+                use blobs.{blob};
+
+                type stamp = u64;
+                record stamped {
+                    at: stamp,
+                    count: u32,
+                }
+                record wrapped {
+                    blob: blob,
+                    count: u32,
+                }
+                variant either {
+                    stamped(stamped),
+                    wrapped(wrapped),
+                }
+                record plain {
+                    count: u32,
+                }
+
+                get: func(e: either, p: plain) -> tuple<stamped, plain>;
+            }
+
+            world guest {
+                import types;
+            }
+        ",
+        with: {
+            "my:inline/blobs": crate::foreign_derives::blobs,
+        },
+        remap: {
+            "my:inline/types/stamp": crate::foreign_derives::Stamp,
+        },
+    });
+
+    pub mod blobs {
+        #[derive(Clone, Debug)]
+        pub struct Blob {
+            pub data: Vec<u8>,
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Stamp(u64);
+
+    impl Lower<u64> for Stamp {
+        fn lower(&self) -> u64 {
+            self.0
+        }
+    }
+
+    impl Lift<u64> for Stamp {
+        fn lift(repr: u64) -> Self {
+            Stamp(repr)
+        }
+    }
+
+    use my::inline::types::{Either, Plain, Wrapped};
+
+    #[test]
+    fn works() {
+        let plain = Plain { count: 1 };
+        assert_eq!(plain, plain.clone());
+        assert!(Plain::default() < plain);
+
+        let wrapped = Either::Wrapped(Wrapped {
+            blob: blobs::Blob { data: vec![1] },
+            count: 2,
+        });
+        assert!(matches!(wrapped.clone(), Either::Wrapped(w) if w.blob.data == [1]));
+    }
+
+    #[allow(dead_code)]
+    fn test() -> u64 {
+        let stamped = my::inline::types::Stamped {
+            at: Stamp(1),
+            count: 2,
+        };
+        let (stamped, _) = my::inline::types::get(&Either::Stamped(stamped), Plain { count: 3 });
+        stamped.at.0
+    }
+}
+
+mod remap {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface types {
+                record uuid {
+                    hi: u64,
+                    lo: u64,
+                }
+                type timestamp = string;
+                enum level { low, high }
+                record event {
+                    id: uuid,
+                    at: timestamp,
+                    level: level,
+                }
+
+                log: func(events: list<event>, at: option<timestamp>) -> list<uuid>;
+            }
+
+            interface sink {
+                use types.{event, timestamp, level, uuid};
+
+                flush: func(events: list<event>) -> timestamp;
+                raise: func(level: level) -> level;
+                high-bits: func(id: uuid, level: level) -> u64;
+            }
+
+            world guest {
+                import types;
+                export sink;
+            }
+        ",
+        exports: {
+            "my:inline/sink": Sink,
+        },
+        remap: {
+            "my:inline/types/uuid": u128,
+            "my:inline/types/timestamp": crate::remap::Timestamp,
+            "my:inline/types/level": crate::remap::Level,
+        },
+        export_prefix: "[remap]",
+    });
+
+    use my::inline::types::{Event, LevelRepr, UuidRepr};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Timestamp(u64);
+
+    impl Lower<String> for Timestamp {
+        fn lower(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    impl Lift<String> for Timestamp {
+        fn lift(repr: String) -> Self {
+            Timestamp(repr.parse().unwrap())
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum Level {
+        Info,
+        Error,
+    }
+
+    impl Lower<LevelRepr> for Level {
+        fn lower(&self) -> LevelRepr {
+            match self {
+                Level::Info => LevelRepr::Low,
+                Level::Error => LevelRepr::High,
+            }
+        }
+    }
+
+    impl Lift<LevelRepr> for Level {
+        fn lift(repr: LevelRepr) -> Self {
+            match repr {
+                LevelRepr::Low => Level::Info,
+                LevelRepr::High => Level::Error,
+            }
+        }
+    }
+
+    impl Lower<UuidRepr> for u128 {
+        fn lower(&self) -> UuidRepr {
+            UuidRepr {
+                hi: (self >> 64) as u64,
+                lo: *self as u64,
+            }
+        }
+    }
+
+    impl Lift<UuidRepr> for u128 {
+        fn lift(repr: UuidRepr) -> Self {
+            (u128::from(repr.hi) << 64) | u128::from(repr.lo)
+        }
+    }
+
+    pub struct Sink;
+
+    impl exports::my::inline::sink::Guest for Sink {
+        fn flush(events: Vec<Event>) -> Timestamp {
+            events.iter().map(|e| e.at).max().unwrap_or(Timestamp(0))
+        }
+
+        fn raise(level: Level) -> Level {
+            assert_eq!(level, Level::Info);
+            Level::Error
+        }
+
+        fn high_bits(id: u128, level: Level) -> u64 {
+            assert_eq!(level, Level::Error);
+            (id >> 64) as u64
+        }
+    }
+
+    extern "C" {
+        #[link_name = "[remap]my:inline/sink#raise"]
+        fn export_raise(level: i32) -> i32;
+        #[link_name = "[remap]my:inline/sink#high-bits"]
+        fn export_high_bits(hi: i64, lo: i64, level: i32) -> i64;
+    }
+
+    #[allow(dead_code)]
+    fn test() -> Vec<u128> {
+        let event = Event {
+            id: 1,
+            at: Timestamp(2),
+            level: Level::Error,
+        };
+        my::inline::types::log(&[event], Some(&Timestamp(3)))
+    }
+
+    #[test]
+    fn conversions() {
+        let id: my::inline::types::Uuid = u128::MAX - 1;
+        assert_eq!(u128::lift(id.lower()), id);
+        assert_eq!(Timestamp::lift(Timestamp(4).lower()), Timestamp(4));
+        assert_eq!(Level::lift(Level::Info.lower()), Level::Info);
+    }
+
+    // Test that exports lift their arguments into the remapped types and
+    // lower their results from them.
+    #[test]
+    fn export_round_trip() {
+        unsafe {
+            assert_eq!(export_raise(LevelRepr::Low as i32), LevelRepr::High as i32);
+            assert_eq!(export_high_bits(7, -1, LevelRepr::High as i32), 7,);
+        }
+    }
+
+    mod errors {
+        use wit_bindgen_core::wit_parser::{Resolve, UnresolvedPackage};
+        use wit_bindgen_core::Files;
+
+        fn generate(key: &str) -> anyhow::Result<()> {
+            let wit = "
+                package my:inline;
+
+                interface types {
+                    resource file;
+                    record entry {
+                        file: borrow<file>,
+                    }
+                    type name = string;
+                }
+
+                world guest {
+                    import types;
+                }
+            ";
+            let mut resolve = Resolve::default();
+            let pkg = resolve.push(UnresolvedPackage::parse("test.wit".as_ref(), wit)?)?;
+            let world = resolve.select_world(pkg, None)?;
+            let mut gen = wit_bindgen_rust::Opts {
+                remap: [(key.to_string(), "crate::Name".to_string())].into(),
+                ..Default::default()
+            }
+            .build();
+            gen.generate(&resolve, world, &mut Files::default())
+        }
+
+        #[test]
+        fn remap_errors() {
+            generate("my:inline/types/name").unwrap();
+            assert_eq!(
+                generate("my:inline/types/nmae").unwrap_err().to_string(),
+                "cannot remap `my:inline/types/nmae` since no such type was found"
+            );
+            assert_eq!(
+                generate("my:inline/types/entry").unwrap_err().to_string(),
+                "cannot remap `my:inline/types/entry` to `crate::Name` since it contains handles"
+            );
+        }
+    }
+}
+
+mod export_ownership {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface blob {
+                record chunk {
+                    data: list<u8>,
+                }
+
+                put: func(key: string, data: list<u8>, sizes: list<u32>) -> u32;
+                put-many: func(keys: list<string>, chunk: chunk);
+            }
+
+            world guest {
+                export blob;
+            }
+        ",
+        exports: {
+            "my:inline/blob": Blob,
+        },
+        export_ownership: Borrowing,
+    });
+
+    use exports::my::inline::blob::Chunk;
+
+    pub struct Blob;
+
+    impl exports::my::inline::blob::Guest for Blob {
+        // Strings and canonical lists are borrowed from the caller's buffer.
+        fn put(key: &str, data: &[u8], sizes: &[u32]) -> u32 {
+            (key.len() + data.len()) as u32 + sizes.iter().sum::<u32>()
+        }
+
+        // Lists which need lifting, and anything nested in other types, are
+        // still owned.
+        fn put_many(keys: Vec<String>, chunk: Chunk) {
+            drop((keys, chunk.data));
+        }
+    }
+}
+
+// Invalid values passed to exports are tested in `tests/runtime/validate_lifts`
+// since they trap.
+mod validate_lifts {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface types {
+                enum level { low, high }
+                flags perms { read, write }
+                variant shape { point, circle(u32) }
+
+                get: func() -> tuple<level, perms, option<char>>;
+            }
+
+            world guest {
+                import types;
+                use types.{perms, shape};
+
+                export take-bool: func(b: bool) -> u32;
+                export take-perms: func(p: perms) -> u32;
+                export take-shape: func(s: shape) -> u32;
+                export take-string: func(s: string) -> u32;
+            }
+        ",
+        exports: {
+            world: Checks,
+        },
+        export_prefix: "[validate-lifts]",
+        validate_lifts,
+    });
+
+    use my::inline::types::Level;
+
+    pub struct Checks;
+
+    impl Guest for Checks {
+        fn take_bool(b: bool) -> u32 {
+            b.into()
+        }
+
+        fn take_perms(p: Perms) -> u32 {
+            p.bits().into()
+        }
+
+        fn take_shape(s: Shape) -> u32 {
+            match s {
+                Shape::Point => 0,
+                Shape::Circle(r) => r,
+            }
+        }
+
+        fn take_string(s: String) -> u32 {
+            s.len() as u32
+        }
+    }
+
+    extern "C" {
+        #[link_name = "[validate-lifts]take-bool"]
+        fn export_take_bool(b: i32) -> i32;
+        #[link_name = "[validate-lifts]take-perms"]
+        fn export_take_perms(p: i32) -> i32;
+        #[link_name = "[validate-lifts]take-shape"]
+        fn export_take_shape(discriminant: i32, payload: i32) -> i32;
+    }
+
+    #[test]
+    fn valid() {
+        unsafe {
+            assert_eq!(export_take_bool(1), 1);
+            assert_eq!(export_take_perms(3), 3);
+            assert_eq!(export_take_shape(0, 0), 0);
+            assert_eq!(export_take_shape(1, 7), 7);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid discriminant")]
+    fn enum_discriminant() {
+        wit_bindgen::set_invalid_lift_hook(|what| panic!("invalid {what}"));
+        unsafe {
+            Level::_lift(2);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid char")]
+    fn char_range() {
+        wit_bindgen::set_invalid_lift_hook(|what| panic!("invalid {what}"));
+        wit_bindgen::rt::checked::char_lift(0xd800);
+    }
+
+    // Without the option unknown bits of flags are kept unless the
+    // `validate_lifts` feature is enabled.
+    mod unvalidated {
+        wit_bindgen::generate!({
+            inline: "
+                package my:inline;
+
+                world guest {
+                    flags perms { read, write }
+
+                    export take-perms: func(p: perms) -> u32;
+                }
+            ",
+            exports: {
+                world: Checks,
+            },
+            export_prefix: "[unvalidated-lifts]",
+        });
+
+        pub struct Checks;
+
+        impl Guest for Checks {
+            fn take_perms(p: Perms) -> u32 {
+                p.bits().into()
+            }
+        }
+
+        extern "C" {
+            #[link_name = "[unvalidated-lifts]take-perms"]
+            fn export_take_perms(p: i32) -> i32;
+        }
+
+        #[test]
+        fn unknown_flags() {
+            assert_eq!(unsafe { export_take_perms(7) }, 7);
+        }
+    }
+}
+
+mod wit_roots {
+    mod paths {
+        wit_bindgen::generate!({
+            path: ["tests/wit-roots/app", "tests/wit-roots/shared"],
+            deps: {
+                "my:io": "tests/wit-roots/io",
+            },
+        });
+
+        #[allow(dead_code)]
+        fn imports() -> (fn(u64, &str), fn(u32) -> Vec<u8>) {
+            (my::shared::logging::log, my::io::streams::read)
+        }
+    }
+
+    mod inline {
+        wit_bindgen::generate!({
+            inline: "
+                package my:inline;
+
+                world guest {
+                    use my:clock/clock.{instant};
+
+                    import my:shared/logging;
+                    import my:io/streams;
+
+                    import now: func() -> instant;
+                }
+            ",
+            path: "tests/wit-roots/shared",
+            deps: {
+                "my:io" => "tests/wit-roots/io",
+            },
+        });
+
+        #[allow(dead_code)]
+        fn imports() -> (fn() -> u64, fn(u64, &str)) {
+            (now, my::shared::logging::log)
+        }
+    }
+}
+
+mod enum_and_flags_conversions {
+    wit_bindgen::generate!({
+        inline: "
+            package my:inline;
+
+            interface types {
+                enum log-level { debug, warn, error }
+                enum errno { again, bad-fd }
+                flags perms { read, write, execute-all }
+
+                get: func() -> tuple<log-level, perms, result<_, errno>>;
+            }
+
+            world guest {
+                import types;
+            }
+        ",
+    });
+
+    use my::inline::types::{Errno, LogLevel, Perms};
+
+    #[test]
+    fn enums() {
+        assert_eq!("warn".parse(), Ok(LogLevel::Warn));
+        assert_eq!(
+            "info".parse::<LogLevel>().unwrap_err().to_string(),
+            "`info` is not a name in `log-level`"
+        );
+        assert_eq!(LogLevel::Error.to_string(), "error");
+        assert_eq!(Errno::BadFd.name(), "bad-fd");
+
+        assert_eq!(LogLevel::try_from(1), Ok(LogLevel::Warn));
+        assert!(LogLevel::try_from(3).is_err());
+        assert_eq!(u32::from(LogLevel::Error), 2);
+
+        let all = LogLevel::iter().collect::<Vec<_>>();
+        assert_eq!(all, LogLevel::ALL);
+        assert_eq!(all, [LogLevel::Debug, LogLevel::Warn, LogLevel::Error]);
+    }
+
+    #[test]
+    fn flags() {
+        let perms = Perms::READ | Perms::EXECUTE_ALL;
+        assert_eq!(perms.to_string(), "read | execute-all");
+        assert_eq!(Perms::empty().to_string(), "");
+        assert_eq!("read|execute-all".parse(), Ok(perms));
+        assert_eq!(" write ".parse(), Ok(Perms::WRITE));
+        assert_eq!("".parse(), Ok(Perms::empty()));
+        assert!("read | exec".parse::<Perms>().is_err());
+
+        assert_eq!(perms.names().collect::<Vec<_>>(), ["read", "execute-all"]);
+        assert_eq!(Perms::all().iter().count(), 3);
+
+        assert_eq!(Perms::try_from(0b011), Ok(Perms::READ | Perms::WRITE));
+        assert!(Perms::try_from(0b1000).is_err());
+        assert_eq!(u8::from(perms), 0b101);
+    }
+}
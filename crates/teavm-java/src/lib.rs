@@ -86,9 +86,11 @@ impl TeaVmJava {
 }
 
 impl WorldGenerator for TeaVmJava {
-    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) {
+    fn preprocess(&mut self, resolve: &Resolve, world: WorldId) -> Result<()> {
+        wit_bindgen_core::check_futures_and_streams(resolve, world)?;
         self.name = world_name(resolve, world);
         self.sizes.fill(resolve);
+        Ok(())
     }
 
     fn import_interface(
@@ -1382,6 +1384,17 @@ impl Bindgen for FunctionBindgen<'_, '_> {
 
             Instruction::HandleLower { .. } | Instruction::HandleLift { .. } => todo!(),
 
+            Instruction::FutureLower { .. }
+            | Instruction::FutureLift { .. }
+            | Instruction::StreamLower { .. }
+            | Instruction::StreamLift { .. } => {
+                unreachable!("rejected by check_futures_and_streams")
+            }
+
+            Instruction::LowerRemapped { .. } | Instruction::LiftRemapped { .. } => {
+                unreachable!("no types are remapped")
+            }
+
             Instruction::RecordLower { record, .. } => {
                 let op = &operands[0];
                 for field in record.fields.iter() {
@@ -15,12 +15,17 @@ macro_rules! codegen_test {
     (resource_local_alias_borrow_import $name:tt $test:tt) => {};
     (resource_borrow_in_record $name:tt $test:tt) => {};
     (resource_borrow_in_record_export $name:tt $test:tt) => {};
+    (resource_handle_alias $name:tt $test:tt) => {};
     (resource_own_in_other_interface $name:tt $test:tt) => {};
     (same_names5 $name:tt $test:tt) => {};
     (resources_in_aggregates $name:tt $test:tt) => {};
     (issue668 $name:tt $test:tt) => {};
     (multiversion $name:tt $test:tt) => {};
 
+    // TODO: wit-component can't encode futures and streams into the
+    // component type yet, so generating bindings for them fails. Remove the
+    // following line once it can:
+    (future_stream_alias $name:tt $test:tt) => {};
     ($id:ident $name:tt $test:tt) => {
         #[test]
         fn $id() {
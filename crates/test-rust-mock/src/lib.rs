@@ -0,0 +1,2 @@
+//! Tests of bindings generated with the `mock` option, which need the `mock`
+//! feature of `wit-bindgen` to run natively.
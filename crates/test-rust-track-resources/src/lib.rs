@@ -0,0 +1,2 @@
+//! Tests of the `track_resources` feature of `wit-bindgen`, with resources
+//! emulated natively by the `mock` feature.
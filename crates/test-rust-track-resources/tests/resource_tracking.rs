@@ -0,0 +1,82 @@
+use std::any::type_name;
+use std::cell::Cell;
+use std::mem::ManuallyDrop;
+use wit_bindgen::rt::live_resources;
+
+wit_bindgen::generate!({
+    inline: "
+        package my:inline;
+
+        interface store {
+            resource blob {}
+        }
+
+        interface counters {
+            resource counter {
+                constructor(start: u32);
+                next: func() -> u32;
+            }
+        }
+
+        world guest {
+            import store;
+            export counters;
+        }
+    ",
+    exports: {
+        "my:inline/counters/counter": MyCounter,
+    },
+    mock,
+});
+
+use exports::my::inline::counters::{GuestCounter, OwnCounter};
+use my::inline::store::Blob;
+
+pub struct MyCounter(Cell<u32>);
+
+impl GuestCounter for MyCounter {
+    fn new(start: u32) -> Self {
+        MyCounter(Cell::new(start))
+    }
+
+    fn next(&self) -> u32 {
+        self.0.replace(self.0.get() + 1)
+    }
+}
+
+#[test]
+fn live() {
+    let blob = Blob::mock_new(());
+    let counter = OwnCounter::new(MyCounter::new(0));
+    let live = live_resources();
+    assert_eq!(live.len(), 2);
+    assert_eq!(live.get(type_name::<Blob>()), [blob.handle()]);
+    assert_eq!(live.get(type_name::<MyCounter>()).len(), 1);
+
+    let handle = OwnCounter::into_handle(counter);
+    assert!(live_resources().get(type_name::<MyCounter>()).is_empty());
+    let counter = unsafe { OwnCounter::from_handle(handle) };
+    assert_eq!(live_resources().len(), 2);
+
+    drop(blob);
+    drop(counter);
+    assert!(live_resources().is_empty(), "{}", live_resources());
+}
+
+#[test]
+#[should_panic(expected = "resource_tracking::my::inline::store::Blob` dropped twice")]
+fn double_drop() {
+    let blob = Blob::mock_new(());
+    let copy = unsafe { Blob::from_handle(blob.handle()) };
+    drop(blob);
+    drop(copy);
+}
+
+#[test]
+#[should_panic(expected = "resource_tracking::MyCounter` used after it was transferred")]
+fn use_after_transfer() {
+    let counter = OwnCounter::new(MyCounter::new(0));
+    let copy = ManuallyDrop::new(unsafe { OwnCounter::from_handle(OwnCounter::handle(&counter)) });
+    OwnCounter::into_handle(counter);
+    copy.next();
+}
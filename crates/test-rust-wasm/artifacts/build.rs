@@ -9,14 +9,23 @@ fn main() {
 
     let wasi_adapter = out_dir.join("../../../../../tests/wasi_snapshot_preview1.reactor.wasm");
 
-    let mut cmd = Command::new("cargo");
-    cmd.arg("build")
-        .current_dir("../../test-rust-wasm")
-        .arg("--target=wasm32-wasi")
-        .env("CARGO_TARGET_DIR", &out_dir)
-        .env("CARGO_PROFILE_DEV_DEBUG", "1");
-    let status = cmd.status().unwrap();
-    assert!(status.success());
+    let build = |args: &[&str]| {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("build")
+            .current_dir("../../test-rust-wasm")
+            .arg("--target=wasm32-wasi")
+            .args(args)
+            .env("CARGO_TARGET_DIR", &out_dir)
+            .env("CARGO_PROFILE_DEV_DEBUG", "1");
+        let status = cmd.status().unwrap();
+        assert!(status.success());
+    };
+    build(&[]);
+    // The bins of features which change all bindings are built on their own,
+    // so the other bins are built without them.
+    for feature in ["alloc_stats", "arena"] {
+        build(&["--features", feature, "--bin", feature]);
+    }
 
     let mut wasms = Vec::new();
     for file in out_dir.join("wasm32-wasi/debug").read_dir().unwrap() {
@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/alloc_stats/wasm.rs");
+
+fn main() {}
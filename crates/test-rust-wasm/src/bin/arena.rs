@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/arena/wasm.rs");
+
+fn main() {}
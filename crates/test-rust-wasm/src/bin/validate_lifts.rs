@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/validate_lifts/wasm.rs");
+
+fn main() {}
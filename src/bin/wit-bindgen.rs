@@ -1,9 +1,13 @@
 use anyhow::{bail, Context, Result};
 use clap::Parser;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::str;
+use wit_bindgen_core::load::{Loader, WitError};
 use wit_bindgen_core::{wit_parser, Files, WorldGenerator};
-use wit_parser::{Resolve, UnresolvedPackage};
+use wit_parser::{PackageId, Resolve, WorldId};
 
 /// Helper for passing VERSION to opt.
 /// If CARGO_VERSION_INFO is set, use it, otherwise use CARGO_PKG_VERSION.
@@ -27,6 +31,17 @@ enum Opt {
     Rust {
         #[clap(flatten)]
         opts: wit_bindgen_rust::Opts,
+
+        /// Writes a new crate implementing the world to this directory
+        /// instead of bindings.
+        ///
+        /// The crate has a `Cargo.toml` for a `cdylib`, the WIT files in
+        /// `wit`, and a module in `src` per exported interface with skeleton
+        /// implementations of its exports. Other options are ignored by the
+        /// crate's `generate!` invocation.
+        #[clap(long, value_name = "DIR")]
+        scaffold: Option<PathBuf>,
+
         #[clap(flatten)]
         args: Common,
     },
@@ -64,6 +79,95 @@ enum Opt {
         #[clap(flatten)]
         args: Common,
     },
+
+    /// Prints the core wasm signature and canonical ABI instructions of each
+    /// function in a world.
+    Abi {
+        /// WIT document to print the ABI of.
+        #[clap(value_name = "DOCUMENT", index = 1)]
+        wit: PathBuf,
+
+        /// World within the WIT document specified to print the ABI of.
+        #[clap(short, long)]
+        world: Option<String>,
+    },
+
+    /// Generates every target listed in a `wit-bindgen.toml` file.
+    Generate {
+        /// Path to the configuration file.
+        #[clap(long, default_value = "wit-bindgen.toml")]
+        config: PathBuf,
+
+        /// Indicates that no files are written and instead files of all
+        /// targets are checked if they're up-to-date with the source files.
+        #[clap(long)]
+        check: bool,
+
+        /// How errors are reported, see `--message-format` of generators.
+        #[clap(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+    },
+}
+
+impl Opt {
+    /// Returns the generator for a language subcommand along with its
+    /// arguments.
+    fn generator(self) -> Option<(Box<dyn WorldGenerator>, Common)> {
+        Some(match self {
+            #[cfg(feature = "markdown")]
+            Opt::Markdown { opts, args } => (opts.build(), args),
+            #[cfg(feature = "c")]
+            Opt::C { opts, args } => (opts.build(), args),
+            #[cfg(feature = "rust")]
+            Opt::Rust {
+                mut opts,
+                scaffold,
+                mut args,
+            } => {
+                if let Some(dir) = scaffold {
+                    opts.scaffold = true;
+                    args.out_dir = Some(dir);
+                }
+                (opts.build(), args)
+            }
+            #[cfg(feature = "teavm-java")]
+            Opt::TeavmJava { opts, args } => (opts.build(), args),
+            #[cfg(feature = "go")]
+            Opt::TinyGo { opts, args } => (opts.build(), args),
+            #[cfg(feature = "csharp")]
+            Opt::CSharp { opts, args } => (opts.build(), args),
+            Opt::Abi { .. } | Opt::Generate { .. } => return None,
+        })
+    }
+
+    /// Returns the name of the generator's subcommand.
+    fn language(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "markdown")]
+            Opt::Markdown { .. } => "markdown",
+            #[cfg(feature = "rust")]
+            Opt::Rust { .. } => "rust",
+            #[cfg(feature = "c")]
+            Opt::C { .. } => "c",
+            #[cfg(feature = "teavm-java")]
+            Opt::TeavmJava { .. } => "teavm-java",
+            #[cfg(feature = "go")]
+            Opt::TinyGo { .. } => "tiny-go",
+            #[cfg(feature = "csharp")]
+            Opt::CSharp { .. } => "c-sharp",
+            Opt::Abi { .. } => "abi",
+            Opt::Generate { .. } => "generate",
+        }
+    }
+
+    /// Returns the directory of the crate written by `rust --scaffold`.
+    fn scaffold_dir(&self) -> Option<&Path> {
+        match self {
+            #[cfg(feature = "rust")]
+            Opt::Rust { scaffold, .. } => scaffold.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -73,9 +177,28 @@ struct Common {
     out_dir: Option<PathBuf>,
 
     /// WIT document to generate bindings for.
+    ///
+    /// This may also be a component or a binary-encoded WIT package, in
+    /// which case the WIT is decoded from the binary.
     #[clap(value_name = "DOCUMENT", index = 1)]
     wit: PathBuf,
 
+    /// A further root of WIT packages which the document may `use`, such as
+    /// a directory of packages shared by several components.
+    ///
+    /// Each root is read like the document, including the packages in its
+    /// `deps` directory. This may be passed several times.
+    #[clap(long = "path", value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// Reads the WIT package `NAME`, such as `wasi:io`, from `PATH` for the
+    /// document to `use`.
+    ///
+    /// Packages given this way take precedence over those in `deps`
+    /// directories. This may be passed several times.
+    #[clap(long = "dep", value_name = "NAME=PATH", value_parser = parse_dep)]
+    deps: Vec<(String, PathBuf)>,
+
     /// World within the WIT document specified to generate bindings for.
     ///
     /// This can either be `foo` which is the default world in document `foo` or
@@ -87,36 +210,165 @@ struct Common {
     /// they're up-to-date with the source files.
     #[clap(long)]
     check: bool,
+
+    /// Removes files which the previous run with `--prune` generated in the
+    /// output directory but which are no longer generated.
+    ///
+    /// The generated files are recorded in a `.wit-bindgen-manifest` file in
+    /// the output directory for the next run, separately for each language
+    /// and world so that several of them can share an output directory. With
+    /// `--check` such stale files are reported whenever a manifest exists.
+    #[clap(long)]
+    prune: bool,
+
+    /// Writes a Make-style depfile to this path listing every WIT file that
+    /// was read, including those of `deps` packages, as a prerequisite of
+    /// every generated file.
+    #[clap(long, value_name = "PATH")]
+    depfile: Option<PathBuf>,
+
+    /// How errors are reported.
+    ///
+    /// With `json` each error is printed to stdout as a single-line JSON
+    /// object with `message`, `hint` and `span` fields, where `span` holds
+    /// the `file`, `line` and `column` in WIT the error points at, if any.
+    #[clap(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+}
+
+fn parse_dep(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, path)) => Ok((name.to_string(), PathBuf::from(path))),
+        None => Err(format!("expected `NAME=PATH` but found `{s}`")),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json,
 }
 
 fn main() -> Result<()> {
-    let mut files = Files::default();
-    let (generator, opt) = match Opt::parse() {
-        #[cfg(feature = "markdown")]
-        Opt::Markdown { opts, args } => (opts.build(), args),
-        #[cfg(feature = "c")]
-        Opt::C { opts, args } => (opts.build(), args),
-        #[cfg(feature = "rust")]
-        Opt::Rust { opts, args } => (opts.build(), args),
-        #[cfg(feature = "teavm-java")]
-        Opt::TeavmJava { opts, args } => (opts.build(), args),
-        #[cfg(feature = "go")]
-        Opt::TinyGo { opts, args } => (opts.build(), args),
-        #[cfg(feature = "csharp")]
-        Opt::CSharp { opts, args } => (opts.build(), args),
-    };
+    let opt = Opt::parse();
+    match &opt {
+        Opt::Abi { wit, world } => {
+            let (resolve, pkg, _) = parse_package(wit, &[], &[])?;
+            let world = resolve.select_world(pkg, world.as_deref())?;
+            print!("{}", wit_bindgen_core::dump::world(&resolve, world));
+            return Ok(());
+        }
+        Opt::Generate {
+            config,
+            check,
+            message_format,
+        } => {
+            if !generate(config, *check, *message_format) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
 
-    gen_world(generator, &opt, &mut files)?;
+    let scaffold = opt.scaffold_dir().map(Path::to_owned);
+    let language = opt.language();
+    let (mut generator, opt) = opt.generator().unwrap();
+    if let Err(e) = gen_world(generator.as_mut(), language, scaffold.as_deref(), &opt) {
+        report(opt.message_format, &e, generator.cli_hint(&e));
+        std::process::exit(1);
+    }
 
-    for (name, contents) in files.iter() {
-        let dst = match &opt.out_dir {
+    Ok(())
+}
+
+/// Generates the world of a language subcommand, which is written as a new
+/// crate in `scaffold` if given.
+fn gen_world(
+    generator: &mut dyn WorldGenerator,
+    language: &str,
+    scaffold: Option<&Path>,
+    opt: &Common,
+) -> Result<()> {
+    let (resolve, world, sources) = parse_world(opt)?;
+    if let Some(dir) = scaffold {
+        if !opt.paths.is_empty() || !opt.deps.is_empty() {
+            bail!("`--scaffold` only copies the WIT of the document, not `--path` or `--dep`");
+        }
+        check_scaffold(dir, opt)?;
+    }
+    run(generator, language, &resolve, world, &sources, opt)?;
+    match scaffold {
+        Some(dir) if !opt.check => copy_wit(&opt.wit, &sources, dir),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a new crate can be scaffolded in `dir`.
+fn check_scaffold(dir: &Path, opt: &Common) -> Result<()> {
+    if !opt.check && dir.join("Cargo.toml").exists() {
+        bail!("a crate already exists in {dir:?}");
+    }
+    Ok(())
+}
+
+fn run(
+    generator: &mut dyn WorldGenerator,
+    language: &str,
+    resolve: &Resolve,
+    world: WorldId,
+    sources: &[PathBuf],
+    opt: &Common,
+) -> Result<()> {
+    let mut files = Files::default();
+    generator.generate(resolve, world, &mut files)?;
+
+    let outputs = files
+        .iter()
+        .map(|(name, _)| match &opt.out_dir {
             Some(path) => path.join(name),
             None => name.into(),
-        };
-        println!("Generating {:?}", dst);
+        })
+        .collect::<Vec<_>>();
+
+    let out_dir = opt.out_dir.as_deref().unwrap_or(Path::new(""));
+    let manifest = out_dir.join(MANIFEST);
+    let key = manifest_key(language, resolve, world);
+    let mut sections = if opt.prune || (opt.check && manifest.exists()) {
+        read_manifest(&manifest)?
+    } else {
+        BTreeMap::new()
+    };
+    // Files which another section lists are still generated by someone else.
+    let stale = sections
+        .get(&key)
+        .into_iter()
+        .flatten()
+        .filter(|name| !files.iter().any(|(n, _)| n == *name))
+        .filter(|name| {
+            sections
+                .iter()
+                .all(|(k, names)| *k == key || !names.contains(name))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut out_of_date = Vec::new();
+    let mut diffs = String::new();
+    for ((name, contents), dst) in files.iter().zip(&outputs) {
+        if opt.message_format == MessageFormat::Human {
+            println!("Generating {:?}", dst);
+        }
 
         if opt.check {
-            let prev = std::fs::read(&dst).with_context(|| format!("failed to read {:?}", dst))?;
+            let prev = match std::fs::read(dst) {
+                Ok(prev) => prev,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    out_of_date.push(format!("{} (missing)", dst.display()));
+                    continue;
+                }
+                Err(e) => return Err(e).with_context(|| format!("failed to read {:?}", dst)),
+            };
             if prev != contents {
                 // The contents differ. If it looks like textual contents, do a
                 // line-by-line comparison so that we can tell users what the
@@ -131,10 +383,11 @@ fn main() -> Result<()> {
                     {
                         bail!("{} differs only in line endings (CRLF vs. LF). If this is a text file, configure git to mark the file as `text eol=lf`.", dst.display());
                     }
+                    // Show what changed for text, while binary contents
+                    // only get listed.
+                    diffs.push_str(&unified_diff(name, utf8_prev, utf8_contents));
                 }
-                // The contents are binary or there are other differences; just
-                // issue a generic error.
-                bail!("not up to date: {}", dst.display());
+                out_of_date.push(dst.display().to_string());
             }
             continue;
         }
@@ -143,38 +396,1385 @@ fn main() -> Result<()> {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("failed to create {:?}", parent))?;
         }
-        std::fs::write(&dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
+        std::fs::write(dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
+    }
+
+    if opt.check {
+        out_of_date.extend(
+            stale
+                .iter()
+                .map(|name| format!("{} (no longer generated)", out_dir.join(name).display())),
+        );
+        if !out_of_date.is_empty() {
+            bail!(
+                "not up to date: {}\n\n{}",
+                out_of_date.join(", "),
+                diffs.trim_end()
+            );
+        }
+    } else if opt.prune {
+        for name in stale.iter() {
+            let path = out_dir.join(name);
+            if opt.message_format == MessageFormat::Human {
+                println!("Removing {:?}", path);
+            }
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("failed to remove {:?}", path)),
+            }
+            // Directories which are left empty by the removal are removed
+            // as well, which fails for any that still have files in them.
+            let mut dir = path.parent();
+            while let Some(d) = dir.filter(|d| d.starts_with(out_dir) && *d != out_dir) {
+                if std::fs::remove_dir(d).is_err() {
+                    break;
+                }
+                dir = d.parent();
+            }
+        }
+        let names = files.iter().map(|(name, _)| name.to_string()).collect();
+        sections.insert(key, names);
+        std::fs::write(&manifest, write_manifest(&sections))
+            .with_context(|| format!("failed to write {:?}", manifest))?;
+    }
+
+    if let Some(path) = &opt.depfile {
+        if !opt.check {
+            std::fs::write(path, depfile(&outputs, sources))
+                .with_context(|| format!("failed to write {:?}", path))?;
+        }
     }
 
     Ok(())
 }
 
-fn gen_world(
-    mut generator: Box<dyn WorldGenerator>,
-    opts: &Common,
-    files: &mut Files,
-) -> Result<()> {
-    let mut resolve = Resolve::default();
-    let pkg = if opts.wit.is_dir() {
-        resolve.push_dir(&opts.wit)?.0
-    } else {
-        resolve.push(UnresolvedPackage::parse_file(&opts.wit)?)?
+/// The name of the file in the output directory which lists the files
+/// generated by the last run with `--prune`.
+///
+/// The manifest has a `[language world]` section for each generator and
+/// world which was pruned in the directory, listing one file per line.
+const MANIFEST: &str = ".wit-bindgen-manifest";
+
+/// Returns the name of the manifest section of `language` and `world`.
+fn manifest_key(language: &str, resolve: &Resolve, world: WorldId) -> String {
+    let world = &resolve.worlds[world];
+    match world.package {
+        Some(pkg) => format!("{language} {}/{}", resolve.packages[pkg].name, world.name),
+        None => format!("{language} {}", world.name),
+    }
+}
+
+/// Reads the sections of the manifest at `path`, if it exists.
+///
+/// Names which could refer to files outside of the output directory are
+/// ignored so that a corrupted manifest can't delete arbitrary files, and so
+/// are names outside of any section.
+fn read_manifest(path: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {:?}", path)),
     };
-    let world = resolve.select_world(pkg, opts.world.as_deref())?;
-    if let Err(e) = generator.generate(&resolve, world, files) {
-        eprintln!(
-            "{e:?}\n\n\
-             help: Specify export implementations using the `--exports` option.\n    \
-             For example: `--exports world=MyWorld,ns:pkg/iface=MyIface`\n    \
-             Alternatively, specify `--stubs` to generate stub implementations."
-        );
+    let mut sections = BTreeMap::new();
+    let mut section = None;
+    for line in contents.lines() {
+        if let Some(key) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(sections.entry(key.to_string()).or_insert_with(Vec::new));
+            continue;
+        }
+        let valid = !line.is_empty()
+            && Path::new(line)
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+        if let (Some(names), true) = (section.as_mut(), valid) {
+            names.push(line.to_string());
+        }
+    }
+    Ok(sections)
+}
+
+fn write_manifest(sections: &BTreeMap<String, Vec<String>>) -> String {
+    let mut ret = String::new();
+    for (key, names) in sections {
+        ret.push_str(&format!("[{key}]\n"));
+        for name in names {
+            ret.push_str(&format!("{name}\n"));
+        }
+    }
+    ret
+}
+
+/// Renders a unified diff, with three lines of context, from the `old`
+/// contents of the file `name` to the `new` ones.
+fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let edits = diff_lines(&old, &new);
+
+    let mut ret = format!("--- a/{name}\n+++ b/{name}\n");
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !matches!(e, Edit::Equal(_)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut i = 0;
+    while i < changes.len() {
+        // Changes which are close enough for their context to overlap are
+        // placed in the same hunk.
+        let mut j = i;
+        while j + 1 < changes.len() && changes[j + 1] - changes[j] <= 2 * CONTEXT + 1 {
+            j += 1;
+        }
+        let start = changes[i].saturating_sub(CONTEXT);
+        let end = (changes[j] + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+
+        // The position of a hunk is where it starts in either file, which is
+        // the line before it if it's empty in that file.
+        let (mut old_start, mut new_start) = edits[..start].iter().fold((0, 0), |(o, n), e| {
+            let (do_, dn) = e.lens();
+            (o + do_, n + dn)
+        });
+        let (old_len, new_len) = hunk.iter().fold((0, 0), |(o, n), e| {
+            let (do_, dn) = e.lens();
+            (o + do_, n + dn)
+        });
+        if old_len > 0 {
+            old_start += 1;
+        }
+        if new_len > 0 {
+            new_start += 1;
+        }
+        ret.push_str(&format!(
+            "@@ -{old_start},{old_len} +{new_start},{new_len} @@\n"
+        ));
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i) => (' ', old[i]),
+                Edit::Delete(i) => ('-', old[i]),
+                Edit::Insert(i) => ('+', new[i]),
+            };
+            ret.push(prefix);
+            ret.push_str(line);
+            ret.push('\n');
+        }
+        i = j + 1;
+    }
+    ret
+}
+
+enum Edit {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+impl Edit {
+    /// Returns how many lines of the old and new files this edit covers.
+    fn lens(&self) -> (usize, usize) {
+        match self {
+            Edit::Equal(_) => (1, 1),
+            Edit::Delete(_) => (1, 0),
+            Edit::Insert(_) => (0, 1),
+        }
+    }
+}
+
+/// Computes a shortest edit script from `a` to `b` with Myers' algorithm.
+///
+/// Past a fixed number of differences this gives up and replaces all of `a`
+/// with all of `b` to bound the time and memory spent on unrelated files.
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    const MAX_EDITS: usize = 2000;
+
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max.min(MAX_EDITS) as isize {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d as usize == MAX_EDITS {
+            return (0..a.len())
+                .map(Edit::Delete)
+                .chain((0..b.len()).map(Edit::Insert))
+                .collect();
+        }
+    }
+
+    // Walk back through the furthest reaching paths of each step to recover
+    // the edits which were made.
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    edits
+}
+
+/// Renders a depfile, as understood by Make and Ninja, with a single rule
+/// making all of `outputs` depend on all of `sources`.
+fn depfile(outputs: &[PathBuf], sources: &[PathBuf]) -> String {
+    fn escape(path: &Path) -> String {
+        let mut ret = String::new();
+        for c in path.display().to_string().chars() {
+            match c {
+                ' ' | '#' => ret.push('\\'),
+                '$' => ret.push('$'),
+                _ => {}
+            }
+            ret.push(c);
+        }
+        ret
+    }
+
+    let mut ret = String::new();
+    for (i, output) in outputs.iter().enumerate() {
+        if i > 0 {
+            ret.push(' ');
+        }
+        ret.push_str(&escape(output));
+    }
+    ret.push(':');
+    for source in sources {
+        ret.push_str(" \\\n  ");
+        ret.push_str(&escape(source));
     }
+    ret.push('\n');
+    ret
+}
+
+/// The contents of a `wit-bindgen.toml` file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// The WIT file or directory, relative to the configuration file, which
+    /// is shared by all targets.
+    wit: PathBuf,
+    /// Further roots of WIT packages which `wit` may use, like `--path`.
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+    /// Paths of WIT packages by name, like `--dep`.
+    #[serde(default)]
+    deps: BTreeMap<String, PathBuf>,
+    #[serde(rename = "target", default)]
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Target {
+    /// The generator subcommand to use, for example `rust` or `c`.
+    language: String,
+    world: Option<String>,
+    /// Where to place output files, relative to the configuration file.
+    out_dir: Option<PathBuf>,
+    /// Options for the generator, keyed by the name of their long flag. The
+    /// directory of `scaffold` is relative to the configuration file.
+    #[serde(default)]
+    options: toml::value::Table,
+}
+
+impl Target {
+    /// Returns the command line for generating this target.
+    ///
+    /// Options are translated to flags so each generator's options work the
+    /// same way as on the command line: `true` passes the flag, arrays pass
+    /// it once per element, and tables pass `key=value` pairs joined with
+    /// commas, as used by options such as `--exports`.
+    fn args(&self, root: &Path, wit: &Path, check: bool) -> Result<Vec<OsString>> {
+        fn scalar(value: &toml::Value) -> Result<String> {
+            Ok(match value {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                    value.to_string()
+                }
+                _ => bail!("unsupported option value `{value}`"),
+            })
+        }
+
+        let mut args = vec!["wit-bindgen".into(), self.language.clone().into()];
+        for (key, value) in self.options.iter() {
+            let flag = format!("--{key}");
+            match value {
+                toml::Value::Boolean(true) => args.push(flag.into()),
+                // The crate is scaffolded relative to the configuration file
+                // like `out-dir`.
+                toml::Value::String(dir) if key == "scaffold" => {
+                    args.push(flag.into());
+                    args.push(root.join(dir).into());
+                }
+                toml::Value::Boolean(false) => {}
+                toml::Value::Array(values) => {
+                    for value in values {
+                        args.push(flag.clone().into());
+                        args.push(scalar(value)?.into());
+                    }
+                }
+                toml::Value::Table(pairs) => {
+                    let pairs = pairs
+                        .iter()
+                        .map(|(k, v)| Ok(format!("{k}={}", scalar(v)?)))
+                        .collect::<Result<Vec<_>>>()?;
+                    args.push(flag.into());
+                    args.push(pairs.join(",").into());
+                }
+                value => {
+                    args.push(flag.into());
+                    args.push(scalar(value)?.into());
+                }
+            }
+        }
+        if let Some(world) = &self.world {
+            args.push("--world".into());
+            args.push(world.into());
+        }
+        if let Some(out_dir) = &self.out_dir {
+            args.push("--out-dir".into());
+            args.push(root.join(out_dir).into());
+        }
+        if check {
+            args.push("--check".into());
+        }
+        args.push(wit.into());
+        Ok(args)
+    }
+}
+
+/// Generates all targets in the configuration file at `path`, returning
+/// whether they all succeeded.
+///
+/// Every target is attempted, even if an earlier one fails, so that all
+/// errors are reported at once.
+fn generate(path: &Path, check: bool, format: MessageFormat) -> bool {
+    let (config, resolve, pkg, wit_sources) = match load_config(path) {
+        Ok(config) => config,
+        Err(e) => {
+            report(format, &e, None);
+            return false;
+        }
+    };
+    let root = path.parent().unwrap_or(Path::new(""));
+    let wit = root.join(&config.wit);
+    let mut sources = wit_sources.clone();
+    sources.push(path.to_owned());
+
+    let mut ok = true;
+    for target in config.targets.iter() {
+        let opt = target
+            .args(root, &wit, check)
+            .and_then(|args| Ok(Opt::try_parse_from(args)?));
+        let opt = match opt {
+            Ok(opt) => opt,
+            Err(e) => {
+                let e = e.context(format!("invalid target `{}`", target.language));
+                report(format, &e, None);
+                ok = false;
+                continue;
+            }
+        };
+        let language = opt.language();
+        let scaffold = opt.scaffold_dir().map(Path::to_owned);
+        let Some((mut generator, mut opt)) = opt.generator() else {
+            let e = anyhow::anyhow!("`{}` is not a language", target.language);
+            report(format, &e, None);
+            ok = false;
+            continue;
+        };
+        opt.message_format = format;
+        let result = resolve
+            .select_world(pkg, opt.world.as_deref())
+            .and_then(|world| {
+                if let Some(dir) = &scaffold {
+                    if !config.paths.is_empty() || !config.deps.is_empty() {
+                        bail!("`scaffold` only copies the WIT of `wit`, not `paths` or `deps`");
+                    }
+                    check_scaffold(dir, &opt)?;
+                }
+                run(
+                    generator.as_mut(),
+                    language,
+                    &resolve,
+                    world,
+                    &sources,
+                    &opt,
+                )?;
+                match &scaffold {
+                    Some(dir) if !opt.check => copy_wit(&wit, &wit_sources, dir),
+                    _ => Ok(()),
+                }
+            });
+        if let Err(e) = result {
+            let hint = generator.cli_hint(&e);
+            let e = e.context(format!("failed to generate target `{}`", target.language));
+            report(format, &e, hint);
+            ok = false;
+        }
+    }
+    ok
+}
 
+fn load_config(path: &Path) -> Result<(Config, Resolve, PackageId, Vec<PathBuf>)> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+    let config: Config =
+        toml::from_str(&contents).with_context(|| format!("failed to parse {path:?}"))?;
+    let root = path.parent().unwrap_or(Path::new(""));
+    let paths = config
+        .paths
+        .iter()
+        .map(|p| root.join(p))
+        .collect::<Vec<_>>();
+    let deps = config
+        .deps
+        .iter()
+        .map(|(name, p)| (name.clone(), root.join(p)))
+        .collect::<Vec<_>>();
+    let (resolve, pkg, sources) = parse_package(&root.join(&config.wit), &paths, &deps)?;
+    Ok((config, resolve, pkg, sources))
+}
+
+fn report(format: MessageFormat, err: &anyhow::Error, hint: Option<String>) {
+    match format {
+        MessageFormat::Human => {
+            eprintln!("Error: {err:?}");
+            if let Some(hint) = hint {
+                eprintln!("\nhelp: {hint}");
+            }
+        }
+        MessageFormat::Json => println!("{}", json_diagnostic(err, hint.as_deref())),
+    }
+}
+
+/// A diagnostic printed as a single line of JSON with
+/// `--message-format json`.
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    level: &'static str,
+    message: String,
+    hint: Option<&'a str>,
+    span: Option<JsonSpan>,
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    file: String,
+    line: u32,
+    column: u32,
+}
+
+/// Renders `err` as a single line of JSON for `--message-format json`.
+fn json_diagnostic(err: &anyhow::Error, hint: Option<&str>) -> String {
+    let (message, wit) = diagnostic(err);
+    let span = wit.map(|wit| {
+        let (file, line, column) = &wit.location;
+        JsonSpan {
+            file: file.display().to_string(),
+            line: *line,
+            column: *column,
+        }
+    });
+    serde_json::to_string(&JsonDiagnostic {
+        level: "error",
+        message,
+        hint,
+        span,
+    })
+    .unwrap()
+}
+
+/// Joins the first line of each error in the chain of `err` into a one-line
+/// message, and returns the error in WIT which it's caused by, if any.
+fn diagnostic(err: &anyhow::Error) -> (String, Option<&WitError>) {
+    let mut messages = Vec::new();
+    let mut wit = None;
+    for cause in err.chain() {
+        let message = match cause.downcast_ref::<WitError>() {
+            Some(cause) => {
+                wit.get_or_insert(cause);
+                cause.message.clone()
+            }
+            None => cause.to_string(),
+        };
+        messages.push(message.lines().next().unwrap_or("").to_string());
+    }
+    (messages.join(": "), wit)
+}
+
+/// Copies the WIT files read from `wit` to the `wit` directory of a crate
+/// scaffolded in `dir`, keeping the layout of a directory such as its `deps`.
+fn copy_wit(wit: &Path, sources: &[PathBuf], dir: &Path) -> Result<()> {
+    let dst_dir = dir.join("wit");
+    for src in sources {
+        let rel = if wit.is_dir() {
+            src.strip_prefix(wit)
+                .with_context(|| format!("{src:?} is not within {wit:?}"))?
+        } else {
+            Path::new(src.file_name().unwrap())
+        };
+        if rel.extension() != Some("wit".as_ref()) {
+            bail!("scaffolding requires WIT source files but {src:?} was read");
+        }
+        let dst = dst_dir.join(rel);
+        std::fs::create_dir_all(dst.parent().unwrap())
+            .with_context(|| format!("failed to create {:?}", dst.parent().unwrap()))?;
+        std::fs::copy(src, &dst).with_context(|| format!("failed to copy {src:?} to {dst:?}"))?;
+        println!("Copying {src:?} to {dst:?}");
+    }
     Ok(())
 }
 
+fn parse_world(opt: &Common) -> Result<(Resolve, WorldId, Vec<PathBuf>)> {
+    let (resolve, pkg, sources) = parse_package(&opt.wit, &opt.paths, &opt.deps)?;
+    let world = resolve.select_world(pkg, opt.world.as_deref())?;
+    Ok((resolve, world, sources))
+}
+
+/// Parses the WIT at `wit`, which is either a directory of WIT files, a
+/// single WIT file, or a wasm binary that is either a component or a
+/// binary-encoded WIT package, along with the packages it may use from the
+/// further roots in `paths` and those named in `deps`.
+///
+/// Also returns the paths of all files that were read.
+fn parse_package(
+    wit: &Path,
+    paths: &[PathBuf],
+    deps: &[(String, PathBuf)],
+) -> Result<(Resolve, PackageId, Vec<PathBuf>)> {
+    let mut loader = Loader::new();
+    for (name, path) in deps {
+        loader.push_dep(name, path)?;
+    }
+    loader.push_path(wit)?;
+    for path in paths {
+        loader.push_path(path)?;
+    }
+    loader.finish()
+}
+
 #[test]
 fn verify_cli() {
     use clap::CommandFactory;
     Opt::command().debug_assert()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory for a test to write files to, which is removed again.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir()
+                .join(format!("wit-bindgen-test-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const TWO_WORLDS: &str = "
+        package my:test;
+
+        world a {
+            import f: func();
+        }
+
+        world b {
+            import g: func();
+        }
+    ";
+
+    #[test]
+    fn prune_targets_sharing_out_dir() {
+        let dir = TempDir::new("prune-shared");
+        dir.write("wit/test.wit", TWO_WORLDS);
+        let config = dir.write(
+            "wit-bindgen.toml",
+            r#"
+                wit = "wit"
+
+                [[target]]
+                language = "markdown"
+                world = "a"
+                out-dir = "out"
+                options = { prune = true }
+
+                [[target]]
+                language = "markdown"
+                world = "b"
+                out-dir = "out"
+                options = { prune = true }
+            "#,
+        );
+        let out = dir.0.join("out");
+        let generated = ["a.md", "a.html", "b.md", "b.html"];
+
+        for _ in 0..2 {
+            assert!(generate(&config, false, MessageFormat::Human));
+            for name in generated {
+                assert!(out.join(name).exists(), "{name} was removed");
+            }
+        }
+        assert!(generate(&config, true, MessageFormat::Human));
+
+        let manifest = read_manifest(&out.join(MANIFEST)).unwrap();
+        assert_eq!(manifest["markdown my:test/a"], ["a.html", "a.md"]);
+        assert_eq!(manifest["markdown my:test/b"], ["b.html", "b.md"]);
+    }
+
+    #[test]
+    fn prune_stale_files_of_target() {
+        let dir = TempDir::new("prune-stale");
+        let wit = dir.write("test.wit", TWO_WORLDS);
+        dir.write("out/old.md", "");
+        dir.write("out/kept.md", "");
+        dir.write(
+            &format!("out/{MANIFEST}"),
+            "[markdown my:test/a]\nold.md\nkept.md\n[markdown my:test/b]\nkept.md\n",
+        );
+        let out = dir.0.join("out");
+
+        let opt = Opt::try_parse_from([
+            "wit-bindgen".as_ref(),
+            "markdown".as_ref(),
+            wit.as_os_str(),
+            "--world".as_ref(),
+            "a".as_ref(),
+            "--out-dir".as_ref(),
+            out.as_os_str(),
+            "--check".as_ref(),
+        ])
+        .unwrap();
+        let (mut generator, mut opt) = opt.generator().unwrap();
+        let (resolve, world, sources) = parse_world(&opt).unwrap();
+        opt.check = false;
+        run(
+            generator.as_mut(),
+            "markdown",
+            &resolve,
+            world,
+            &sources,
+            &opt,
+        )
+        .unwrap();
+        opt.check = true;
+        let err = run(
+            generator.as_mut(),
+            "markdown",
+            &resolve,
+            world,
+            &sources,
+            &opt,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("old.md (no longer generated)"), "{err}");
+        assert!(!err.contains("kept.md"), "{err}");
+
+        opt.check = false;
+        opt.prune = true;
+        run(
+            generator.as_mut(),
+            "markdown",
+            &resolve,
+            world,
+            &sources,
+            &opt,
+        )
+        .unwrap();
+        assert!(!out.join("old.md").exists());
+        assert!(out.join("kept.md").exists());
+        assert!(out.join("a.md").exists());
+    }
+
+    #[test]
+    fn parse_wasm_packages() {
+        use wit_component::{ComponentEncoder, StringEncoding};
+
+        let dir = TempDir::new("parse-wasm");
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(wit_parser::UnresolvedPackage::parse("test.wit".as_ref(), TWO_WORLDS).unwrap())
+            .unwrap();
+
+        // A binary-encoded WIT package has every world of the package.
+        let path = dir.0.join("package.wasm");
+        std::fs::write(&path, wit_component::encode(None, &resolve, pkg).unwrap()).unwrap();
+        let (decoded, main, files) = parse_package(&path, &[], &[]).unwrap();
+        assert_eq!(decoded.packages[main].name.to_string(), "my:test");
+        assert!(decoded.select_world(main, Some("a")).is_ok());
+        assert!(decoded.select_world(main, Some("b")).is_ok());
+        assert_eq!(files, [path]);
+
+        // A component has the world it was built for.
+        let world = resolve.select_world(pkg, Some("a")).unwrap();
+        let mut module = wit_component::dummy_module(&resolve, world);
+        wit_component::embed_component_metadata(&mut module, &resolve, world, StringEncoding::UTF8)
+            .unwrap();
+        let component = ComponentEncoder::default()
+            .module(&module)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let path = dir.0.join("component.wasm");
+        std::fs::write(&path, component).unwrap();
+        let (decoded, main, _) = parse_package(&path, &[], &[]).unwrap();
+        let world = decoded.select_world(main, None).unwrap();
+        let imports = decoded.worlds[world]
+            .imports
+            .keys()
+            .map(|key| decoded.name_world_key(key))
+            .collect::<Vec<_>>();
+        assert_eq!(imports, ["f"]);
+
+        let mut files = Files::default();
+        wit_bindgen_markdown::Opts::default()
+            .build()
+            .generate(&decoded, world, &mut files)
+            .unwrap();
+        assert!(files.iter().count() > 0);
+    }
+
+    #[test]
+    fn config_target_args() {
+        let config: Config = toml::from_str(
+            r#"
+                wit = "wit"
+                paths = ["shared"]
+                deps = { "my:dep" = "vendor/dep" }
+
+                [[target]]
+                language = "rust"
+                world = "a"
+                out-dir = "src/gen"
+                options = { std-feature = true, ownership = false, skip = ["f", "g"], exports = { world = "Guest", "my:test/i" = "I" }, export-prefix = "x" }
+
+                [[target]]
+                language = "c"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.paths, [PathBuf::from("shared")]);
+        assert_eq!(config.deps["my:dep"], PathBuf::from("vendor/dep"));
+        assert_eq!(config.targets.len(), 2);
+
+        let root = Path::new("root");
+        let args = config.targets[0]
+            .args(root, Path::new("root/wit"), true)
+            .unwrap();
+        assert_eq!(
+            args,
+            [
+                "wit-bindgen",
+                "rust",
+                "--export-prefix",
+                "x",
+                "--exports",
+                "my:test/i=I,world=Guest",
+                "--skip",
+                "f",
+                "--skip",
+                "g",
+                "--std-feature",
+                "--world",
+                "a",
+                "--out-dir",
+                "root/src/gen",
+                "--check",
+                "root/wit",
+            ]
+            .map(OsString::from)
+        );
+        let opt = Opt::try_parse_from(args).unwrap();
+        assert_eq!(opt.language(), "rust");
+
+        let args = config.targets[1]
+            .args(root, Path::new("root/wit"), false)
+            .unwrap();
+        assert_eq!(args, ["wit-bindgen", "c", "root/wit"].map(OsString::from));
+
+        let target: Target = toml::from_str(
+            r#"
+                language = "rust"
+                options = { exports = { world = [1] } }
+            "#,
+        )
+        .unwrap();
+        let err = target.args(root, root, false).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported option value `[1]`");
+
+        let err = toml::from_str::<Config>("wit = \"wit\"\nworld = \"a\"\n")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("unknown field `world`"), "{err}");
+    }
+
+    /// Writes a package using one from a further root and one given by
+    /// name, where the latter also has a stale copy vendored in `deps`.
+    fn roots_and_deps(dir: &TempDir) {
+        dir.write(
+            "wit/test.wit",
+            "package my:test;\n\nworld a {\n  import my:shared/i;\n  import my:dep/i;\n}\n",
+        );
+        dir.write(
+            "wit/deps/dep/i.wit",
+            "package my:dep;\n\ninterface i {\n  vendored: func();\n}\n",
+        );
+        dir.write(
+            "shared/i.wit",
+            "package my:shared;\n\ninterface i {\n  shared: func();\n}\n",
+        );
+        dir.write(
+            "vendor/dep/i.wit",
+            "package my:dep;\n\ninterface i {\n  given: func();\n}\n",
+        );
+    }
+
+    /// Returns the functions imported by `world`, by interface.
+    fn imported_functions(resolve: &Resolve, world: WorldId) -> Vec<String> {
+        use wit_parser::WorldItem;
+
+        let mut names = Vec::new();
+        for (key, item) in resolve.worlds[world].imports.iter() {
+            if let WorldItem::Interface(id) = item {
+                for name in resolve.interfaces[*id].functions.keys() {
+                    names.push(format!("{}#{name}", resolve.name_world_key(key)));
+                }
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn cli_paths_and_deps() {
+        let dir = TempDir::new("cli-paths");
+        roots_and_deps(&dir);
+        let wit = dir.0.join("wit");
+        let shared = dir.0.join("shared");
+        let dep = format!("my:dep={}", dir.0.join("vendor/dep").display());
+        let opt = Opt::try_parse_from([
+            "wit-bindgen".as_ref(),
+            "markdown".as_ref(),
+            wit.as_os_str(),
+            "--path".as_ref(),
+            shared.as_os_str(),
+            "--dep".as_ref(),
+            dep.as_ref(),
+        ])
+        .unwrap();
+        let (_, opt) = opt.generator().unwrap();
+        assert_eq!(opt.paths, [shared]);
+        assert_eq!(opt.deps, [("my:dep".to_string(), dir.0.join("vendor/dep"))]);
+        let (resolve, world, sources) = parse_world(&opt).unwrap();
+        assert_eq!(
+            imported_functions(&resolve, world),
+            ["my:shared/i#shared", "my:dep/i#given"]
+        );
+        assert!(sources.contains(&dir.0.join("shared/i.wit")));
+        assert!(sources.contains(&dir.0.join("vendor/dep/i.wit")));
+
+        // Without them the shared package is missing.
+        let opt =
+            Opt::try_parse_from(["wit-bindgen".as_ref(), "markdown".as_ref(), wit.as_os_str()])
+                .unwrap();
+        let (_, opt) = opt.generator().unwrap();
+        let err = parse_world(&opt).err().unwrap();
+        assert!(format!("{err:?}").contains("my:shared"), "{err:?}");
+
+        let err = Opt::try_parse_from(["wit-bindgen", "markdown", "wit", "--dep", "my:dep"])
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .contains("expected `NAME=PATH` but found `my:dep`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn config_paths_and_deps() {
+        let dir = TempDir::new("config-paths");
+        roots_and_deps(&dir);
+        let config = dir.write(
+            "wit-bindgen.toml",
+            r#"
+                wit = "wit"
+                paths = ["shared"]
+
+                [deps]
+                "my:dep" = "vendor/dep"
+
+                [[target]]
+                language = "markdown"
+                out-dir = "out"
+            "#,
+        );
+        let (_, resolve, pkg, sources) = load_config(&config).unwrap();
+        let world = resolve.select_world(pkg, None).unwrap();
+        assert_eq!(
+            imported_functions(&resolve, world),
+            ["my:shared/i#shared", "my:dep/i#given"]
+        );
+        assert!(sources.contains(&dir.0.join("shared/i.wit")));
+        assert!(sources.contains(&dir.0.join("vendor/dep/i.wit")));
+        assert!(generate(&config, false, MessageFormat::Human));
+        assert!(dir.0.join("out/a.md").exists());
+    }
+
+    #[test]
+    fn config_check_covers_all_targets() {
+        let dir = TempDir::new("config-check");
+        dir.write("wit/test.wit", TWO_WORLDS);
+        let config = dir.write(
+            "wit-bindgen.toml",
+            r#"
+                wit = "wit"
+
+                [[target]]
+                language = "markdown"
+                world = "a"
+                out-dir = "a"
+
+                [[target]]
+                language = "markdown"
+                world = "b"
+                out-dir = "b"
+            "#,
+        );
+        assert!(generate(&config, false, MessageFormat::Human));
+        assert!(generate(&config, true, MessageFormat::Human));
+
+        // A stale output of either target fails the check.
+        dir.write("b/b.md", "stale");
+        assert!(!generate(&config, true, MessageFormat::Human));
+        assert!(generate(&config, false, MessageFormat::Human));
+        assert!(generate(&config, true, MessageFormat::Human));
+    }
+
+    #[test]
+    fn json_diagnostics() {
+        let err = anyhow::anyhow!("expected `\"`\nin \\path").context("outer");
+        assert_eq!(
+            json_diagnostic(&err, Some("try \"this\"")),
+            r#"{"level":"error","message":"outer: expected `\"`","hint":"try \"this\"","span":null}"#
+        );
+
+        // Parse errors point at their location in the WIT.
+        let dir = TempDir::new("json-diagnostics");
+        let wit = dir.write(
+            "a \"b\".wit",
+            "package my:test;\n\nworld w {\n  import f: func() -> x;\n}\n",
+        );
+        let err = parse_package(&wit, &[], &[]).err().unwrap();
+        let (message, span) = diagnostic(&err);
+        assert_eq!(message, "name `x` is not defined");
+        assert_eq!(span.unwrap().location, (wit.clone(), 4, 23));
+        let json = json_diagnostic(&err, None);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "level": "error",
+                "message": "name `x` is not defined",
+                "hint": null,
+                "span": {
+                    "file": wit.display().to_string(),
+                    "line": 4,
+                    "column": 23,
+                },
+            })
+        );
+
+        // The rendered error is kept for humans.
+        assert!(format!("{err:?}").contains("--> "), "{err:?}");
+    }
+
+    #[test]
+    fn depfile_escaping() {
+        assert_eq!(
+            depfile(
+                &["out dir/a.md".into(), "out/b#1.md".into()],
+                &["wit/$x.wit".into(), "wit/deps/a b/c#d.wit".into()],
+            ),
+            "\
+out\\ dir/a.md out/b\\#1.md: \\
+  wit/$$x.wit \\
+  wit/deps/a\\ b/c\\#d.wit
+"
+        );
+    }
+
+    #[test]
+    fn depfile_lists_deps() {
+        let dir = TempDir::new("depfile");
+        dir.write(
+            "my wit/test.wit",
+            "package my:test;\n\nworld a {\n  import my:dep/i;\n}\n",
+        );
+        dir.write(
+            "my wit/deps/dep/i.wit",
+            "package my:dep;\n\ninterface i {\n  f: func();\n}\n",
+        );
+        let wit = dir.0.join("my wit");
+        let out = dir.0.join("out");
+        let depfile_path = dir.0.join("out.d");
+        let opt = Opt::try_parse_from([
+            "wit-bindgen".as_ref(),
+            "markdown".as_ref(),
+            wit.as_os_str(),
+            "--out-dir".as_ref(),
+            out.as_os_str(),
+            "--depfile".as_ref(),
+            depfile_path.as_os_str(),
+        ])
+        .unwrap();
+        let (mut generator, opt) = opt.generator().unwrap();
+        let (resolve, world, sources) = parse_world(&opt).unwrap();
+        run(
+            generator.as_mut(),
+            "markdown",
+            &resolve,
+            world,
+            &sources,
+            &opt,
+        )
+        .unwrap();
+
+        let escaped = |path: PathBuf| path.display().to_string().replace(' ', "\\ ");
+        let contents = std::fs::read_to_string(&depfile_path).unwrap();
+        let (outputs, inputs) = contents.split_once(": \\\n").unwrap();
+        assert_eq!(
+            outputs,
+            format!(
+                "{} {}",
+                escaped(out.join("a.html")),
+                escaped(out.join("a.md"))
+            )
+        );
+        let mut inputs = inputs.split(" \\\n").map(str::trim).collect::<Vec<_>>();
+        inputs.sort();
+        assert_eq!(
+            inputs,
+            [
+                escaped(wit.join("deps/dep/i.wit")),
+                escaped(wit.join("test.wit"))
+            ]
+        );
+    }
+
+    /// Runs `wit-bindgen rust --scaffold` on `wit`, writing the crate to
+    /// `dir`.
+    fn scaffold(wit: &Path, dir: &Path) -> Result<()> {
+        let opt = Opt::try_parse_from([
+            "wit-bindgen".as_ref(),
+            "rust".as_ref(),
+            wit.as_os_str(),
+            "--scaffold".as_ref(),
+            dir.as_os_str(),
+        ])?;
+        let scaffold = opt.scaffold_dir().map(Path::to_owned);
+        let (mut generator, opt) = opt.generator().unwrap();
+        gen_world(generator.as_mut(), "rust", scaffold.as_deref(), &opt)
+    }
+
+    #[test]
+    fn scaffold_crate() {
+        let dir = TempDir::new("scaffold");
+        dir.write(
+            "wit/app.wit",
+            "
+                package my:app;
+
+                interface store {
+                    resource blob {
+                        constructor(init: list<u8>);
+                        read: func() -> list<u8>;
+                    }
+                    get: func(key: string) -> option<blob>;
+                }
+
+                world guest {
+                    import other:lib/store;
+                    export store;
+                    export other:lib/store;
+                    export run: func(args: list<string>) -> u32;
+                }
+            ",
+        );
+        dir.write(
+            "wit/deps/lib/store.wit",
+            "
+                package other:lib;
+
+                interface store {
+                    put: func(value: string);
+                }
+            ",
+        );
+        let out = dir.0.join("guest");
+        scaffold(&dir.0.join("wit"), &out).unwrap();
+        let read = |name: &str| std::fs::read_to_string(out.join(name)).unwrap();
+        let mut version = env!("CARGO_PKG_VERSION").split('.');
+        let (major, minor) = (version.next().unwrap(), version.next().unwrap());
+        assert_eq!(
+            read("Cargo.toml"),
+            format!(
+                "\
+[package]
+name = \"guest\"
+version = \"0.1.0\"
+edition = \"2021\"
+
+[lib]
+crate-type = [\"cdylib\"]
+
+[dependencies]
+wit-bindgen = \"{major}.{minor}\"
+"
+            )
+        );
+        // Interfaces with the same name are in modules named after their
+        // packages.
+        assert_eq!(
+            read("src/lib.rs"),
+            "\
+wit_bindgen::generate!({
+    world: \"guest\",
+    exports: {
+        \"my:app/store\": my_app_store::Store,
+        \"my:app/store/blob\": my_app_store::Blob,
+        \"other:lib/store\": other_lib_store::Store,
+        world: Component,
+    },
+});
+
+mod my_app_store;
+mod other_lib_store;
+
+pub struct Component;
+
+impl Guest for Component {
+    fn run(args: Vec<String>) -> u32 {
+        unimplemented!()
+    }
+}
+"
+        );
+        assert_eq!(
+            read("src/my_app_store.rs"),
+            "\
+use crate::exports::my::app::store::*;
+
+pub struct Store;
+
+impl Guest for Store {
+    fn get(key: String) -> Option<OwnBlob> {
+        unimplemented!()
+    }
+}
+
+pub struct Blob;
+
+impl GuestBlob for Blob {
+    fn new(init: Vec<u8>) -> Self {
+        unimplemented!()
+    }
+
+    fn read(&self) -> Vec<u8> {
+        unimplemented!()
+    }
+}
+"
+        );
+        assert_eq!(
+            read("src/other_lib_store.rs"),
+            "\
+use crate::exports::other::lib::store::*;
+
+pub struct Store;
+
+impl Guest for Store {
+    fn put(value: String) {
+        unimplemented!()
+    }
+}
+"
+        );
+
+        // The WIT is copied along with its dependencies.
+        assert_eq!(
+            read("wit/app.wit"),
+            std::fs::read_to_string(dir.0.join("wit/app.wit")).unwrap()
+        );
+        assert!(out.join("wit/deps/lib/store.wit").exists());
+
+        let err = scaffold(&dir.0.join("wit"), &out).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("a crate already exists in {out:?}")
+        );
+    }
+
+    #[test]
+    fn config_scaffold() {
+        let dir = TempDir::new("config-scaffold");
+        let wit = dir.write(
+            "wit/app.wit",
+            "package my:app;\n\nworld guest {\n    export run: func();\n}\n",
+        );
+        let config = dir.write(
+            "wit-bindgen.toml",
+            r#"
+                wit = "wit"
+
+                [[target]]
+                language = "rust"
+                options = { scaffold = "guest" }
+            "#,
+        );
+        assert!(generate(&config, false, MessageFormat::Human));
+        assert!(dir.0.join("guest/Cargo.toml").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.0.join("guest/wit/app.wit")).unwrap(),
+            std::fs::read_to_string(&wit).unwrap()
+        );
+
+        // The crate is only written once, but can be checked.
+        assert!(generate(&config, true, MessageFormat::Human));
+        assert!(!generate(&config, false, MessageFormat::Human));
+    }
+
+    #[test]
+    fn copy_wit_sources() {
+        let dir = TempDir::new("copy-wit");
+        let file = dir.write("a.wit", "package my:test;\n\nworld w {}\n");
+        let out = dir.0.join("out");
+        copy_wit(&file, &[file.clone()], &out).unwrap();
+        assert!(out.join("wit/a.wit").exists());
+
+        // Only WIT text can be copied into a crate.
+        let wasm = dir.write("a.wasm", "");
+        let err = copy_wit(&wasm, &[wasm.clone()], &out).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("scaffolding requires WIT source files but {wasm:?} was read")
+        );
+        let wit = dir.write("wit/b.wit", "").parent().unwrap().to_owned();
+        let err = copy_wit(&wit, &[file.clone()], &out).unwrap_err();
+        assert_eq!(err.to_string(), format!("{file:?} is not within {wit:?}"));
+    }
+
+    #[test]
+    fn unified_diffs() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        assert_eq!(
+            unified_diff("x.rs", old, new),
+            "\
+--- a/x.rs
++++ b/x.rs
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -10,3 +10,4 @@
+ j
+ k
+ l
++m
+"
+        );
+        assert_eq!(unified_diff("x.rs", old, old), "--- a/x.rs\n+++ b/x.rs\n");
+        assert_eq!(
+            unified_diff("x.rs", "", "a\n"),
+            "--- a/x.rs\n+++ b/x.rs\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
+
+    #[test]
+    fn diff_lines_is_minimal() {
+        let a = ["a", "b", "c", "a", "b", "b", "a"];
+        let b = ["c", "b", "a", "b", "a", "c"];
+        let edits = diff_lines(&a, &b);
+        let changes = edits
+            .iter()
+            .filter(|e| !matches!(e, Edit::Equal(_)))
+            .count();
+        assert_eq!(changes, 5);
+
+        // Applying the edits to `a` gives `b`.
+        let applied = edits
+            .iter()
+            .filter_map(|e| match *e {
+                Edit::Equal(i) => Some(a[i]),
+                Edit::Insert(i) => Some(b[i]),
+                Edit::Delete(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(applied, b);
+    }
+}
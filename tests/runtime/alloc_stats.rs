@@ -0,0 +1,35 @@
+use anyhow::Result;
+use wasmtime::Store;
+
+wasmtime::component::bindgen!(in "tests/runtime/alloc_stats");
+
+#[test]
+fn run() -> Result<()> {
+    crate::run_test(
+        "alloc_stats",
+        |_| Ok(()),
+        |store, component, linker| AllocStats::instantiate(store, component, linker),
+        run_test,
+    )
+}
+
+fn run_test(exports: AllocStats, store: &mut Store<crate::Wasi<()>>) -> Result<()> {
+    // The argument is allocated by the host and owned by the export, and the
+    // result is allocated by the export and freed in its post-return.
+    assert_eq!(exports.call_echo(&mut *store, "hello")?, "hello");
+    let last = exports.call_last_call(&mut *store)?.unwrap();
+    assert_eq!(
+        (last.function.as_str(), last.allocated, last.freed),
+        ("echo", 10, 5)
+    );
+
+    // Counts start over with each call, so those of `last-call` itself
+    // aren't included.
+    assert_eq!(exports.call_sum(&mut *store, &[1, 2, 3])?, 6);
+    let last = exports.call_last_call(&mut *store)?.unwrap();
+    assert_eq!(
+        (last.function.as_str(), last.allocated, last.freed),
+        ("sum", 12, 0)
+    );
+    Ok(())
+}
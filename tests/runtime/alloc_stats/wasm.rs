@@ -0,0 +1,39 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/alloc_stats",
+    exports: {
+        world: Exports
+    },
+});
+
+use std::sync::Mutex;
+use wit_bindgen::alloc_stats::{self, CallStats};
+
+static LAST_CALL: Mutex<Option<CallCounts>> = Mutex::new(None);
+
+fn record(call: &CallStats<'_>) {
+    if call.function != "last-call" {
+        *LAST_CALL.lock().unwrap() = Some(CallCounts {
+            function: call.function.to_string(),
+            allocated: call.stats.allocated as u32,
+            freed: call.stats.freed as u32,
+        });
+    }
+}
+
+struct Exports;
+
+impl Guest for Exports {
+    fn echo(s: String) -> String {
+        alloc_stats::set_hook(record);
+        s
+    }
+
+    fn sum(l: Vec<u32>) -> u32 {
+        alloc_stats::set_hook(record);
+        l.iter().sum()
+    }
+
+    fn last_call() -> Option<CallCounts> {
+        LAST_CALL.lock().unwrap().take()
+    }
+}
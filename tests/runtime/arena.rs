@@ -0,0 +1,31 @@
+use anyhow::Result;
+use wasmtime::Store;
+
+wasmtime::component::bindgen!(in "tests/runtime/arena");
+
+#[test]
+fn run() -> Result<()> {
+    crate::run_test(
+        "arena",
+        |_| Ok(()),
+        |store, component, linker| Arena::instantiate(store, component, linker),
+        run_test,
+    )
+}
+
+fn run_test(exports: Arena, store: &mut Store<crate::Wasi<()>>) -> Result<()> {
+    let address = exports.call_address(&mut *store, "hello")?;
+
+    // Arguments are copied out of the arena where they're owned, and results
+    // are lowered into it.
+    assert_eq!(exports.call_concat(&mut *store, "ab", b"cd")?, "abcd");
+    let strings = ["a", "bcd", ""].map(String::from);
+    assert_eq!(exports.call_lengths(&mut *store, &strings)?, [1, 3, 0]);
+
+    // The arena is reset once each call is over, either in post-return or
+    // when the export returns, so the next argument is allocated in the same
+    // place.
+    assert_eq!(exports.call_address(&mut *store, "world")?, address);
+    assert_eq!(exports.call_address(&mut *store, "!")?, address);
+    Ok(())
+}
@@ -0,0 +1,23 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/arena",
+    exports: {
+        world: Exports
+    },
+    arena,
+});
+
+struct Exports;
+
+impl Guest for Exports {
+    fn address(s: &str) -> u32 {
+        s.as_ptr() as u32
+    }
+
+    fn concat(a: &str, b: &[u8]) -> String {
+        format!("{a}{}", std::str::from_utf8(b).unwrap())
+    }
+
+    fn lengths(l: Vec<String>) -> Vec<u32> {
+        l.iter().map(|s| s.len() as u32).collect()
+    }
+}
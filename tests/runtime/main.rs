@@ -13,6 +13,8 @@ use wasmtime_wasi::preview2::{Table, WasiCtx, WasiCtxBuilder, WasiView};
 use wit_component::{ComponentEncoder, StringEncoding};
 use wit_parser::{Resolve, WorldItem};
 
+mod alloc_stats;
+mod arena;
 mod flavorful;
 mod lists;
 mod many_arguments;
@@ -31,6 +33,7 @@ mod resource_with_lists;
 mod resources;
 mod smoke;
 mod strings;
+mod validate_lifts;
 mod variants;
 
 struct MyCtx {}
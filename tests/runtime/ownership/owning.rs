@@ -6,12 +6,6 @@ wit_bindgen::generate!({
     ownership: Owning
 });
 
-impl PartialEq for thing_in_and_out::Thing {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.value == other.value
-    }
-}
-
 struct Exports;
 
 impl Guest for Exports {
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use std::cell::Cell;
+use wasmtime::{Store, Trap};
+
+wasmtime::component::bindgen!(in "tests/runtime/validate_lifts");
+
+std::thread_local! {
+    /// The export which the guest calls with an invalid value, as each one
+    /// traps and needs a new instance.
+    static EXPORT: Cell<&'static str> = const { Cell::new("") };
+}
+
+fn run(export: &'static str) -> Result<()> {
+    EXPORT.with(|e| e.set(export));
+    crate::run_test(
+        "validate_lifts",
+        |_| Ok(()),
+        |store, component, linker| ValidateLifts::instantiate(store, component, linker),
+        run_test,
+    )
+}
+
+fn run_test(exports: ValidateLifts, store: &mut Store<crate::Wasi<()>>) -> Result<()> {
+    assert_eq!(exports.call_take_bool(&mut *store, true)?, 1);
+    assert_eq!(exports.call_take_char(&mut *store, 'a')?, 97);
+    assert_eq!(exports.call_take_level(&mut *store, Level::High)?, 1);
+    assert_eq!(exports.call_take_perms(&mut *store, Perms::all())?, 3);
+    assert_eq!(exports.call_take_shape(&mut *store, Shape::Circle(7))?, 7);
+    assert_eq!(exports.call_take_string(&mut *store, "abc")?, 3);
+
+    let export = EXPORT.with(|e| e.get());
+    let err = exports.call_lift_invalid(&mut *store, export).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Trap>(),
+        Some(&Trap::UnreachableCodeReached),
+        "{err:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn bool() -> Result<()> {
+    run("take-bool")
+}
+
+#[test]
+fn char_range() -> Result<()> {
+    run("take-char")
+}
+
+#[test]
+fn enum_discriminant() -> Result<()> {
+    run("take-level")
+}
+
+#[test]
+fn flag_bits() -> Result<()> {
+    run("take-perms")
+}
+
+#[test]
+fn variant_discriminant() -> Result<()> {
+    run("take-shape")
+}
+
+#[test]
+fn utf8() -> Result<()> {
+    run("take-string")
+}
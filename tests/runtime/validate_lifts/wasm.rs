@@ -0,0 +1,73 @@
+wit_bindgen::generate!({
+    path: "../../tests/runtime/validate_lifts",
+    exports: {
+        world: Exports
+    },
+    validate_lifts,
+});
+
+struct Exports;
+
+impl Guest for Exports {
+    fn take_bool(b: bool) -> u32 {
+        b.into()
+    }
+
+    fn take_char(c: char) -> u32 {
+        c.into()
+    }
+
+    fn take_level(l: Level) -> u32 {
+        l as u32
+    }
+
+    fn take_perms(p: Perms) -> u32 {
+        p.bits().into()
+    }
+
+    fn take_shape(s: Shape) -> u32 {
+        match s {
+            Shape::Point => 0,
+            Shape::Circle(r) => r,
+        }
+    }
+
+    fn take_string(s: String) -> u32 {
+        s.len() as u32
+    }
+
+    fn lift_invalid(name: String) {
+        unsafe {
+            match name.as_str() {
+                "take-bool" => export_take_bool(2),
+                "take-char" => export_take_char(0xd800),
+                "take-level" => export_take_level(2),
+                "take-perms" => export_take_perms(4),
+                "take-shape" => export_take_shape(2, 0),
+                "take-string" => {
+                    // The export takes ownership of the string.
+                    let bytes = Vec::leak(vec![0xff, b'a', b'b']);
+                    export_take_string(bytes.as_ptr() as i32, bytes.len() as i32)
+                }
+                _ => unreachable!(),
+            };
+        }
+    }
+}
+
+// The host can only pass valid values, so invalid ones are passed to the core
+// functions which the bindings export instead.
+extern "C" {
+    #[link_name = "take-bool"]
+    fn export_take_bool(b: i32) -> i32;
+    #[link_name = "take-char"]
+    fn export_take_char(c: i32) -> i32;
+    #[link_name = "take-level"]
+    fn export_take_level(l: i32) -> i32;
+    #[link_name = "take-perms"]
+    fn export_take_perms(p: i32) -> i32;
+    #[link_name = "take-shape"]
+    fn export_take_shape(discriminant: i32, payload: i32) -> i32;
+    #[link_name = "take-string"]
+    fn export_take_string(ptr: i32, len: i32) -> i32;
+}